@@ -0,0 +1,204 @@
+use crate::auth::authenticator::{self, Authenticator, Credentials};
+use crate::auth::entitlements::Entitlements;
+use crate::auth::operators::Operators;
+use crate::config::server_config::ServerConfig;
+use crate::fix::fix_message::FixMessage;
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::fix::messages::business_message_reject::BusinessMessageReject;
+use crate::session::fix_session::NOT_AUTHORIZED;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AccessDenied {
+    Unauthenticated(String),
+    NotEntitled(String),
+}
+
+/// A message `AccessControl::authorize` refused, with the BusinessMessageReject that answers it,
+/// as a FIX session would.
+#[derive(Debug)]
+pub struct Refused {
+    pub denied: AccessDenied,
+    pub reject: Box<BusinessMessageReject>,
+}
+
+impl Refused {
+    fn new(denied: AccessDenied, fields: &HashMap<String, String>) -> Self {
+        let text = match &denied {
+            AccessDenied::Unauthenticated(text) | AccessDenied::NotEntitled(text) => text.clone(),
+        };
+        let reject = Box::new(BusinessMessageReject {
+            ref_seq_num: None,
+            ref_msg_type: fields.get("35").cloned().unwrap_or_default(),
+            business_reject_ref_id: fields.get("11").cloned(),
+            business_reject_reason: NOT_AUTHORIZED,
+            text: Some(text),
+        });
+        Refused { denied, reject }
+    }
+}
+
+/// Who is calling an HTTP endpoint other than the FIX one.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Caller {
+    Operator(String),
+    Counterparty(String),
+}
+
+/// The configured authenticator, entitlements and operators, shared by the FIX sessions and the
+/// HTTP and WebSocket endpoints.
+#[derive(Clone)]
+pub struct AccessControl {
+    authenticator: Option<Arc<dyn Authenticator>>,
+    entitlements: Arc<Entitlements>,
+    operators: Arc<Operators>,
+    /// CompID of each counterparty by its Username, for callers giving only their credentials.
+    comp_ids_by_username: Arc<HashMap<String, String>>,
+}
+
+impl AccessControl {
+    pub fn new(authenticator: Option<Arc<dyn Authenticator>>, entitlements: Entitlements) -> Self {
+        AccessControl {
+            authenticator,
+            entitlements: Arc::new(entitlements),
+            operators: Arc::new(Operators::default()),
+            comp_ids_by_username: Arc::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_operators(mut self, operators: Operators) -> Self {
+        self.operators = Arc::new(operators);
+        self
+    }
+
+    pub fn with_usernames(mut self, comp_ids_by_username: HashMap<String, String>) -> Self {
+        self.comp_ids_by_username = Arc::new(comp_ids_by_username);
+        self
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let usernames = config.counterparties.iter()
+            .filter_map(|counterparty| counterparty.username.clone().map(|username| (username, counterparty.comp_id.clone())))
+            .collect();
+        Self::new(
+            authenticator::from_config(&config.auth, &config.counterparties),
+            Entitlements::new(&config.counterparties),
+        )
+        .with_operators(Operators::new(&config.operators))
+        .with_usernames(usernames)
+    }
+
+    pub fn authenticator(&self) -> Option<Arc<dyn Authenticator>> {
+        self.authenticator.clone()
+    }
+
+    pub fn entitlements(&self) -> Arc<Entitlements> {
+        Arc::clone(&self.entitlements)
+    }
+
+    /// Authorizes a message received outside a FIX session, returning it with the SenderCompID
+    /// it came from. There is no Logon on HTTP or WebSocket, so every message carries its own
+    /// SenderCompID and, if required, Username and Password.
+    pub fn authorize(&self, raw: &str) -> Result<(FixMessage, String), Refused> {
+        let fields = FixMessageParser::parse_fields(raw);
+        let credentials = Credentials::from_fields(&fields);
+        if let Some(authenticator) = &self.authenticator {
+            authenticator.authenticate(&credentials).map_err(|e| Refused::new(AccessDenied::Unauthenticated(e), &fields))?;
+        }
+
+        let msg_type = fields.get("35").cloned().unwrap_or_default();
+        let message = FixMessageParser::parse_message(raw);
+        self.entitlements.check(&credentials.comp_id, &msg_type, &message).map_err(|e| Refused::new(AccessDenied::NotEntitled(e), &fields))?;
+        Ok((message, credentials.comp_id))
+    }
+
+    /// Identifies the caller of an HTTP endpoint from its Authorization header: an operator by
+    /// `Bearer <token>`, or a counterparty by `Basic` with its Username and Password, checked as
+    /// they would be on a FIX message.
+    pub fn identify(&self, authorization: Option<&str>) -> Result<Caller, AccessDenied> {
+        let unauthenticated = |text: &str| AccessDenied::Unauthenticated(text.to_string());
+        let authorization = authorization.ok_or_else(|| unauthenticated("Authorization is required"))?;
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            let name = self.operators.authenticate(token).map_err(AccessDenied::Unauthenticated)?;
+            return Ok(Caller::Operator(name.to_string()));
+        }
+
+        let basic = authorization
+            .strip_prefix("Basic ")
+            .and_then(|encoded| STANDARD.decode(encoded).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or_else(|| unauthenticated("Expected Bearer or Basic authorization"))?;
+        let (username, password) = basic.split_once(':').ok_or_else(|| unauthenticated("Expected Bearer or Basic authorization"))?;
+        let comp_id = self.comp_ids_by_username.get(username).ok_or_else(|| unauthenticated("Invalid username or password"))?;
+        if let Some(authenticator) = &self.authenticator {
+            let credentials = Credentials { comp_id: comp_id.clone(), username: Some(username.to_string()), password: Some(password.to_string()) };
+            authenticator.authenticate(&credentials).map_err(AccessDenied::Unauthenticated)?;
+        }
+        Ok(Caller::Counterparty(comp_id.clone()))
+    }
+
+    /// Name of the operator calling an admin endpoint; counterparties are refused.
+    pub fn authorize_operator(&self, authorization: Option<&str>) -> Result<String, AccessDenied> {
+        match self.identify(authorization)? {
+            Caller::Operator(name) => Ok(name),
+            Caller::Counterparty(comp_id) => Err(AccessDenied::NotEntitled(format!("{} is not an operator", comp_id))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access_control() -> AccessControl {
+        let config = ServerConfig::parse(r#"
+            [auth]
+            mode = "password"
+
+            [[counterparty]]
+            comp_id = "CLIENT1"
+            username = "client1"
+            password = "secret"
+            symbols = ["XYZ"]
+
+            [[operator]]
+            name = "ops"
+            token = "ops-token"
+        "#).unwrap();
+        AccessControl::from_config(&config)
+    }
+
+    const ORDER: &str = "35=D|11=1|54=1|60=20230101-12:00:00|38=100|40=2|44=50.5|";
+
+    #[test]
+    fn test_authorize_checks_credentials_then_entitlements() {
+        let access_control = access_control();
+
+        let result = access_control.authorize(&format!("49=CLIENT1|553=client1|554=secret|{}55=XYZ|", ORDER));
+        assert!(matches!(result, Ok((FixMessage::NewOrder(_), comp_id)) if comp_id == "CLIENT1"));
+
+        let result = access_control.authorize(&format!("49=CLIENT1|553=client1|554=wrong|{}55=XYZ|", ORDER));
+        assert_eq!(result.unwrap_err().denied, AccessDenied::Unauthenticated("Invalid username or password".to_string()));
+
+        let refused = access_control.authorize(&format!("49=CLIENT1|553=client1|554=secret|{}55=ABC|", ORDER)).unwrap_err();
+        assert_eq!(refused.denied, AccessDenied::NotEntitled("CLIENT1 may not trade symbol ABC".to_string()));
+        assert_eq!(refused.reject.to_writer().encode_body(), "35=j|372=D|379=1|380=6|58=CLIENT1 may not trade symbol ABC|");
+    }
+
+    #[test]
+    fn test_identify_operators_by_token_and_counterparties_by_credentials() {
+        let access_control = access_control();
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+
+        assert_eq!(access_control.identify(Some("Bearer ops-token")), Ok(Caller::Operator("ops".to_string())));
+        assert_eq!(access_control.identify(Some(&basic("client1:secret"))), Ok(Caller::Counterparty("CLIENT1".to_string())));
+        assert!(matches!(access_control.identify(Some(&basic("client1:wrong"))), Err(AccessDenied::Unauthenticated(_))));
+        assert!(matches!(access_control.identify(None), Err(AccessDenied::Unauthenticated(_))));
+
+        assert_eq!(access_control.authorize_operator(Some("Bearer ops-token")), Ok("ops".to_string()));
+        assert_eq!(access_control.authorize_operator(Some(&basic("client1:secret"))).unwrap_err(), AccessDenied::NotEntitled("CLIENT1 is not an operator".to_string()));
+    }
+}
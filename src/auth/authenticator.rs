@@ -0,0 +1,139 @@
+use crate::config::server_config::{AuthConfig, AuthMode, CounterpartyConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Who a message or Logon claims to come from.
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub comp_id: String,            // Tag 49: SenderCompID
+    pub username: Option<String>,   // Tag 553: Username
+    pub password: Option<String>,   // Tag 554: Password
+}
+
+impl Credentials {
+    pub fn from_fields(fields: &HashMap<String, String>) -> Self {
+        Credentials {
+            comp_id: fields.get("49").cloned().unwrap_or_default(),
+            username: fields.get("553").cloned(),
+            password: fields.get("554").cloned(),
+        }
+    }
+}
+
+/// Decides whether a counterparty may log on or submit messages. Errors carry the reason sent
+/// back to the counterparty, so they must not echo the credentials.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), String>;
+}
+
+/// Accepts any counterparty whose SenderCompID is on the list.
+pub struct CompIdAllowList {
+    comp_ids: HashSet<String>,
+}
+
+impl CompIdAllowList {
+    pub fn new<I: IntoIterator<Item = String>>(comp_ids: I) -> Self {
+        CompIdAllowList { comp_ids: comp_ids.into_iter().collect() }
+    }
+}
+
+impl Authenticator for CompIdAllowList {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), String> {
+        if self.comp_ids.contains(&credentials.comp_id) {
+            Ok(())
+        } else {
+            Err(format!("CompID {} is not allowed", credentials.comp_id))
+        }
+    }
+}
+
+struct Account {
+    password: String,
+    comp_id: String,
+}
+
+/// Checks Username and Password against the configured counterparties; the username must also
+/// belong to the SenderCompID it is used from.
+pub struct PasswordAuthenticator {
+    accounts: HashMap<String, Account>,
+}
+
+impl PasswordAuthenticator {
+    pub fn new(counterparties: &[CounterpartyConfig]) -> Self {
+        let accounts = counterparties.iter()
+            .filter_map(|counterparty| match (&counterparty.username, &counterparty.password) {
+                (Some(username), Some(password)) => Some((username.clone(), Account {
+                    password: password.clone(),
+                    comp_id: counterparty.comp_id.clone(),
+                })),
+                _ => None,
+            })
+            .collect();
+        PasswordAuthenticator { accounts }
+    }
+}
+
+impl Authenticator for PasswordAuthenticator {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), String> {
+        let (username, password) = match (&credentials.username, &credentials.password) {
+            (Some(username), Some(password)) => (username, password),
+            _ => return Err("Username and Password are required".to_string()),
+        };
+        match self.accounts.get(username) {
+            Some(account) if account.password == *password && account.comp_id == credentials.comp_id => Ok(()),
+            _ => Err("Invalid username or password".to_string()),
+        }
+    }
+}
+
+/// Builds the authenticator for the configured mode, or `None` when authentication is off.
+pub fn from_config(auth: &AuthConfig, counterparties: &[CounterpartyConfig]) -> Option<Arc<dyn Authenticator>> {
+    match auth.mode {
+        AuthMode::None => None,
+        AuthMode::CompId => Some(Arc::new(CompIdAllowList::new(counterparties.iter().map(|counterparty| counterparty.comp_id.clone())))),
+        AuthMode::Password => Some(Arc::new(PasswordAuthenticator::new(counterparties))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counterparty(comp_id: &str, username: &str, password: &str) -> CounterpartyConfig {
+        CounterpartyConfig {
+            comp_id: comp_id.to_string(),
+            username: Some(username.to_string()),
+            password: Some(password.to_string()),
+            accounts: None,
+            symbols: None,
+            msg_types: None,
+        }
+    }
+
+    fn credentials(comp_id: &str, username: Option<&str>, password: Option<&str>) -> Credentials {
+        Credentials {
+            comp_id: comp_id.to_string(),
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_comp_id_allow_list() {
+        let authenticator = CompIdAllowList::new(vec!["CLIENT1".to_string()]);
+
+        assert!(authenticator.authenticate(&credentials("CLIENT1", None, None)).is_ok());
+        assert_eq!(authenticator.authenticate(&credentials("CLIENT2", None, None)).unwrap_err(), "CompID CLIENT2 is not allowed");
+    }
+
+    #[test]
+    fn test_password_authenticator() {
+        let authenticator = PasswordAuthenticator::new(&[counterparty("CLIENT1", "client1", "secret")]);
+
+        assert!(authenticator.authenticate(&credentials("CLIENT1", Some("client1"), Some("secret"))).is_ok());
+        assert!(authenticator.authenticate(&credentials("CLIENT1", Some("client1"), Some("wrong"))).is_err());
+        assert!(authenticator.authenticate(&credentials("CLIENT1", Some("client1"), None)).is_err());
+        // Valid credentials used from another CompID
+        assert!(authenticator.authenticate(&credentials("CLIENT2", Some("client1"), Some("secret"))).is_err());
+    }
+}
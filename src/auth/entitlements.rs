@@ -0,0 +1,170 @@
+use crate::config::server_config::CounterpartyConfig;
+use crate::fix::fix_message::FixMessage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+
+/// What a single counterparty may send; `None` allows any value.
+#[derive(Debug, Default)]
+struct CounterpartyEntitlements {
+    accounts: Option<HashSet<String>>,
+    symbols: Option<HashSet<String>>,
+    msg_types: Option<HashSet<String>>,
+}
+
+/// Per-CompID limits on the application messages a counterparty may send. Once any
+/// counterparty is configured, messages without a SenderCompID or from one without an entry
+/// are refused; with none configured, everyone is unrestricted.
+/// Accounts can also be blocked at runtime by the kill switch, which holds its state in memory
+/// only, so a restart re-enables them.
+#[derive(Debug, Default)]
+pub struct Entitlements {
+    counterparties: HashMap<String, CounterpartyEntitlements>,
+    blocked_accounts: Mutex<BTreeSet<String>>,
+}
+
+impl Entitlements {
+    pub fn new(counterparties: &[CounterpartyConfig]) -> Self {
+        let to_set = |values: &Option<Vec<String>>| values.as_ref().map(|values| values.iter().cloned().collect());
+        let counterparties = counterparties.iter()
+            .map(|counterparty| (counterparty.comp_id.clone(), CounterpartyEntitlements {
+                accounts: to_set(&counterparty.accounts),
+                symbols: to_set(&counterparty.symbols),
+                msg_types: to_set(&counterparty.msg_types),
+            }))
+            .collect();
+        Entitlements { counterparties, blocked_accounts: Mutex::default() }
+    }
+
+    /// Refuses new orders for `account` until it is unblocked. Returns false if it already was.
+    pub fn block_account(&self, account: &str) -> bool {
+        self.blocked_accounts.lock().unwrap().insert(account.to_string())
+    }
+
+    /// Lets `account` trade again. Returns false if it was not blocked.
+    pub fn unblock_account(&self, account: &str) -> bool {
+        self.blocked_accounts.lock().unwrap().remove(account)
+    }
+
+    pub fn blocked_accounts(&self) -> Vec<String> {
+        self.blocked_accounts.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Whether `comp_id` may see what is held in `account`, being entitled to trade it.
+    pub fn may_trade_account(&self, comp_id: &str, account: Option<&str>) -> bool {
+        match self.counterparties.get(comp_id) {
+            Some(entitlements) => entitlements.accounts.as_ref().is_none_or(|accounts| account.is_some_and(|account| accounts.contains(account))),
+            None => self.counterparties.is_empty(),
+        }
+    }
+
+    /// Returns the reason the message is refused, if `comp_id` is not entitled to send it.
+    pub fn check(&self, comp_id: &str, msg_type: &str, message: &FixMessage) -> Result<(), String> {
+        if let (FixMessage::NewOrder(_), Some(account)) = (message, message.account()) {
+            if self.blocked_accounts.lock().unwrap().contains(account) {
+                return Err(format!("Account {} is blocked by the kill switch", account));
+            }
+        }
+
+        let entitlements = match self.counterparties.get(comp_id) {
+            Some(entitlements) => entitlements,
+            None if self.counterparties.is_empty() => return Ok(()),
+            None if comp_id.is_empty() => return Err("SenderCompID (49) is required".to_string()),
+            None => return Err(format!("Unknown CompID {}", comp_id)),
+        };
+
+        if entitlements.msg_types.as_ref().is_some_and(|msg_types| !msg_types.contains(msg_type)) {
+            return Err(format!("{} may not send MsgType {}", comp_id, msg_type));
+        }
+        if let Some(accounts) = &entitlements.accounts {
+            match message.account() {
+                Some(account) if !accounts.contains(account) => {
+                    return Err(format!("{} may not trade account {}", comp_id, account));
+                }
+                None if matches!(message, FixMessage::NewOrder(_)) => {
+                    return Err(format!("{} must specify an Account", comp_id));
+                }
+                _ => {}
+            }
+        }
+        if let (Some(symbols), Some(symbol)) = (&entitlements.symbols, message.symbol()) {
+            if !symbols.contains(symbol) {
+                return Err(format!("{} may not trade symbol {}", comp_id, symbol));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+
+    fn entitlements() -> Entitlements {
+        Entitlements::new(&[CounterpartyConfig {
+            comp_id: "CLIENT1".to_string(),
+            username: None,
+            password: None,
+            accounts: Some(vec!["ACC1".to_string()]),
+            symbols: Some(vec!["XYZ".to_string()]),
+            msg_types: Some(vec!["D".to_string()]),
+        }])
+    }
+
+    fn new_order(account: &str, symbol: &str) -> FixMessage {
+        FixMessageParser::parse_message(&format!("35=D|11=1|54=1|60=20230101-12:00:00|38=100|40=2|44=50.5|55={}|{}", symbol, account))
+    }
+
+    #[test]
+    fn test_entitled_order_is_allowed() {
+        assert!(entitlements().check("CLIENT1", "D", &new_order("1=ACC1|", "XYZ")).is_ok());
+    }
+
+    #[test]
+    fn test_accounts_a_counterparty_may_trade() {
+        let entitlements = entitlements();
+        assert!(entitlements.may_trade_account("CLIENT1", Some("ACC1")));
+        assert!(!entitlements.may_trade_account("CLIENT1", Some("ACC2")));
+        assert!(!entitlements.may_trade_account("CLIENT1", None));
+        assert!(!entitlements.may_trade_account("CLIENT2", Some("ACC1")));
+    }
+
+    #[test]
+    fn test_violations_are_refused() {
+        let entitlements = entitlements();
+        let cancel = FixMessageParser::parse_message("35=F|41=1|11=2|54=1|55=XYZ|60=20230101-12:00:00|");
+
+        assert_eq!(entitlements.check("CLIENT1", "D", &new_order("1=ACC2|", "XYZ")).unwrap_err(), "CLIENT1 may not trade account ACC2");
+        assert_eq!(entitlements.check("CLIENT1", "D", &new_order("", "XYZ")).unwrap_err(), "CLIENT1 must specify an Account");
+        assert_eq!(entitlements.check("CLIENT1", "D", &new_order("1=ACC1|", "ABC")).unwrap_err(), "CLIENT1 may not trade symbol ABC");
+        assert_eq!(entitlements.check("CLIENT1", "F", &cancel).unwrap_err(), "CLIENT1 may not send MsgType F");
+    }
+
+    #[test]
+    fn test_kill_switch_blocks_new_orders_of_an_account() {
+        let entitlements = entitlements();
+        let cancel = FixMessageParser::parse_message("35=F|41=1|11=2|54=1|55=XYZ|1=ACC1|60=20230101-12:00:00|");
+
+        assert!(entitlements.block_account("ACC1"));
+        assert!(!entitlements.block_account("ACC1"));
+        assert_eq!(entitlements.blocked_accounts(), vec!["ACC1".to_string()]);
+        assert_eq!(entitlements.check("CLIENT1", "D", &new_order("1=ACC1|", "XYZ")).unwrap_err(), "Account ACC1 is blocked by the kill switch");
+        // Whoever sends them, and cancels still go through
+        let unrestricted = Entitlements::default();
+        unrestricted.block_account("ACC1");
+        assert_eq!(unrestricted.check("CLIENT2", "D", &new_order("1=ACC1|", "XYZ")).unwrap_err(), "Account ACC1 is blocked by the kill switch");
+        assert!(unrestricted.check("CLIENT2", "F", &cancel).is_ok());
+
+        assert!(entitlements.unblock_account("ACC1"));
+        assert!(entitlements.check("CLIENT1", "D", &new_order("1=ACC1|", "XYZ")).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_or_missing_comp_id_is_refused_once_counterparties_are_configured() {
+        let entitlements = entitlements();
+        assert_eq!(entitlements.check("CLIENT2", "D", &new_order("", "ABC")).unwrap_err(), "Unknown CompID CLIENT2");
+        assert_eq!(entitlements.check("", "D", &new_order("", "ABC")).unwrap_err(), "SenderCompID (49) is required");
+
+        assert!(Entitlements::default().check("", "D", &new_order("", "ABC")).is_ok());
+    }
+}
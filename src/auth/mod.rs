@@ -0,0 +1,4 @@
+pub mod access_control;
+pub mod authenticator;
+pub mod entitlements;
+pub mod operators;
@@ -0,0 +1,38 @@
+use crate::config::server_config::OperatorConfig;
+use std::collections::HashMap;
+
+/// Operators who may use the admin endpoints, known by the bearer token each presents. With
+/// none configured, the admin endpoints are refused to everyone.
+#[derive(Debug, Default)]
+pub struct Operators {
+    names_by_token: HashMap<String, String>,
+}
+
+impl Operators {
+    pub fn new(operators: &[OperatorConfig]) -> Self {
+        let names_by_token = operators.iter().map(|operator| (operator.token.clone(), operator.name.clone())).collect();
+        Operators { names_by_token }
+    }
+
+    /// Name of the operator holding `token`.
+    pub fn authenticate(&self, token: &str) -> Result<&str, String> {
+        if self.names_by_token.is_empty() {
+            return Err("No operators are configured".to_string());
+        }
+        self.names_by_token.get(token).map(String::as_str).ok_or_else(|| "Invalid operator token".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operators_are_known_by_token_and_refused_when_none_are_configured() {
+        let operators = Operators::new(&[OperatorConfig { name: "ops".to_string(), token: "secret".to_string() }]);
+
+        assert_eq!(operators.authenticate("secret"), Ok("ops"));
+        assert_eq!(operators.authenticate("guess").unwrap_err(), "Invalid operator token");
+        assert_eq!(Operators::default().authenticate("secret").unwrap_err(), "No operators are configured");
+    }
+}
@@ -0,0 +1,564 @@
+use crate::session::session_schedule::SessionSchedule;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CONFIG_PATH: &str = "config/fixparser.toml";
+
+fn default_http_address() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8081))
+}
+
+fn default_begin_string() -> String {
+    "FIX.4.2".to_string()
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_heart_bt_int() -> u64 {
+    30
+}
+
+fn default_reconnect_interval() -> u64 {
+    30
+}
+
+fn default_queue_capacity() -> usize {
+    1024
+}
+
+fn default_shards() -> usize {
+    1
+}
+
+fn default_snapshot_interval() -> u64 {
+    300
+}
+
+fn default_time_zone() -> String {
+    "UTC".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub server: HttpConfig,
+    #[serde(default, rename = "session")]
+    pub sessions: Vec<SessionConfig>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default, rename = "counterparty")]
+    pub counterparties: Vec<CounterpartyConfig>,
+    #[serde(default, rename = "operator")]
+    pub operators: Vec<OperatorConfig>,
+    #[serde(default)]
+    pub oms: OmsConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HttpConfig {
+    #[serde(default = "default_http_address")]
+    pub http_address: SocketAddr,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig { http_address: default_http_address() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OmsConfig {
+    /// How many OMS threads share out the symbols, each with its own books, orders, queue and,
+    /// when there are several, its own journal, snapshots and GTC store at the configured path
+    /// suffixed with `.<shard>`. Changing it needs fresh stores, as symbols move between shards.
+    #[serde(default = "default_shards")]
+    pub shards: usize,
+    /// How many messages may wait for each OMS shard. When the queue is full, FIX sessions and
+    /// WebSockets wait for room and HTTP requests are turned away with 503.
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Trading hours: auctions run at the open and close, and Day orders expire at the close.
+    /// Without one, Day orders expire at midnight UTC and auctions never run by themselves.
+    pub schedule: Option<ScheduleConfig>,
+    /// Instrument master as a `.csv` or `.json` file. With one, orders are only accepted for its
+    /// symbols, on their tick and lot sizes and within their price limits.
+    pub instruments_path: Option<PathBuf>,
+    /// Keeps orders of the same owner from trading with each other. Without it they trade.
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    /// How OrderIDs and ExecIDs are formed and kept unique across restarts.
+    #[serde(default)]
+    pub ids: IdConfig,
+    /// File keeping working Good Till Cancel orders across restarts; without one they are lost.
+    pub gtc_store_path: Option<PathBuf>,
+    /// Journal of every message in and out of the OMS, replayed on startup to rebuild its
+    /// orders, books and positions. It restores GTC orders too, so takes the place of the store.
+    pub journal_path: Option<PathBuf>,
+    /// Directory for periodic snapshots of the OMS, so a restart only replays the journal from
+    /// the latest one on. Needs a journal.
+    pub snapshot_path: Option<PathBuf>,
+    /// Seconds between snapshots.
+    #[serde(default = "default_snapshot_interval")]
+    pub snapshot_interval_secs: u64,
+}
+
+impl OmsConfig {
+    /// Where `shard` keeps what the config puts at `path`: the path itself for a single OMS.
+    pub fn shard_path(&self, path: &Path, shard: usize) -> PathBuf {
+        if self.shards == 1 {
+            return path.to_path_buf();
+        }
+        let mut path = path.as_os_str().to_owned();
+        path.push(format!(".{}", shard));
+        PathBuf::from(path)
+    }
+}
+
+impl Default for OmsConfig {
+    fn default() -> Self {
+        OmsConfig {
+            shards: default_shards(),
+            queue_capacity: default_queue_capacity(),
+            schedule: None,
+            instruments_path: None,
+            self_trade_prevention: None,
+            ids: IdConfig::default(),
+            gtc_store_path: None,
+            journal_path: None,
+            snapshot_path: None,
+            snapshot_interval_secs: default_snapshot_interval(),
+        }
+    }
+}
+
+/// Format of the OrderIDs and ExecIDs the OMS assigns, e.g. `LDN-S1-ORD00000042` with prefix
+/// `LDN-` and width 8 on shard 1 of several.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IdConfig {
+    /// Put ahead of every ID, e.g. to tell instances apart. Shards add `S<shard>-` after it.
+    #[serde(default)]
+    pub prefix: String,
+    /// Digits the number in an ID is zero-padded to, so that IDs sort in the order issued.
+    #[serde(default)]
+    pub width: usize,
+    /// File keeping the next ID numbers, so that a restart never issues an ID twice, with or
+    /// without a journal.
+    pub store_path: Option<PathBuf>,
+}
+
+/// What happens when an incoming order would trade with a resting order of the same owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTradeMode {
+    /// The resting order is canceled and the incoming order matches on.
+    CancelResting,
+    /// What is left of the incoming order is canceled.
+    CancelAggressor,
+    CancelBoth,
+    /// The smaller order is canceled and the larger one decremented by its quantity; equal
+    /// orders are both canceled.
+    DecrementAndCancel,
+}
+
+/// Which field says who an order trades for. Orders without it are never prevented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTradeKey {
+    /// Account (1).
+    #[default]
+    Account,
+    /// SelfMatchPreventionID (2362), for firms sharing one identifier across accounts.
+    SelfMatchPreventionId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct SelfTradePrevention {
+    pub mode: SelfTradeMode,
+    #[serde(default)]
+    pub key: SelfTradeKey,
+}
+
+/// Pre-trade limits every new order must pass before it is accepted. Omitted limits are not
+/// enforced.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RiskConfig {
+    pub max_order_qty: Option<f64>,
+    /// Largest OrderQty × Price of a single order, keyed by Currency (15).
+    #[serde(default)]
+    pub max_notional: HashMap<String, f64>,
+    /// Currency of orders that do not state one.
+    pub default_currency: Option<String>,
+    /// How far a limit price may stray from the last traded price, in percent.
+    pub price_band_percent: Option<f64>,
+    /// Most working orders an account may have, across every OMS shard.
+    pub max_open_orders_per_account: Option<usize>,
+    /// Largest total notional of an account's working orders, across sides, currencies and OMS
+    /// shards.
+    pub max_gross_exposure_per_account: Option<f64>,
+    /// Symbols no order may be entered for.
+    #[serde(default)]
+    pub restricted_symbols: Vec<String>,
+}
+
+/// How counterparties prove who they are, on FIX Logon and on every HTTP or WebSocket message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// Anyone who can reach the ports may connect.
+    #[default]
+    None,
+    /// SenderCompID (49) must belong to a configured counterparty.
+    CompId,
+    /// Username (553) and Password (554) must match a configured counterparty.
+    Password,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub mode: AuthMode,
+}
+
+/// A counterparty identified by its CompID, with its credentials and what it may send. Omitted
+/// entitlement lists allow everything.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CounterpartyConfig {
+    pub comp_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub accounts: Option<Vec<String>>,
+    pub symbols: Option<Vec<String>>,
+    /// MsgTypes (35) the counterparty may send, e.g. `["D", "F", "H"]`.
+    pub msg_types: Option<Vec<String>>,
+}
+
+/// Someone running the venue, who may use the admin endpoints by presenting `token` as
+/// `Authorization: Bearer <token>`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OperatorConfig {
+    pub name: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionRole {
+    Acceptor,
+    Initiator,
+}
+
+/// When a session's sequence numbers and stored messages are reset back to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetPolicy {
+    #[default]
+    Never,
+    OnLogon,
+    OnLogout,
+    OnDisconnect,
+    Daily,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionConfig {
+    #[serde(default = "default_begin_string")]
+    pub begin_string: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+    pub role: SessionRole,
+    /// Address to bind as an acceptor or to connect to as an initiator.
+    #[serde(default = "default_host")]
+    pub host: String,
+    pub port: u16,
+    #[serde(default = "default_heart_bt_int")]
+    pub heart_bt_int: u64,
+    /// Seconds an initiator waits before reconnecting.
+    #[serde(default = "default_reconnect_interval")]
+    pub reconnect_interval: u64,
+    /// Directory for the file message store; sessions without one keep state in memory.
+    pub message_store_path: Option<PathBuf>,
+    pub data_dictionary: Option<PathBuf>,
+    pub schedule: Option<ScheduleConfig>,
+    #[serde(default)]
+    pub reset_policy: ResetPolicy,
+    /// Credentials an initiator sends on its Logon.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Seconds the session may stay disconnected before the orders entered through it are canceled.
+    /// Unset, orders outlive the connection.
+    pub cancel_on_disconnect_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    /// Local start of the trading session, `HH:MM:SS`.
+    pub start_time: String,
+    /// Local end of the trading session, `HH:MM:SS`.
+    pub end_time: String,
+    /// IANA time zone the times are expressed in, e.g. `Europe/London`.
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
+    /// Days a daily session starts on, e.g. `["Mon", "Tue", "Wed", "Thu", "Fri"]`.
+    pub weekdays: Option<Vec<String>>,
+    /// First day of a weekly session; requires `end_day`.
+    pub start_day: Option<String>,
+    /// Last day of a weekly session; requires `start_day`.
+    pub end_day: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let config: ServerConfig = toml::from_str(content)
+            .map_err(|e| format!("Invalid config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut session_ids = HashSet::new();
+        for session in &self.sessions {
+            let key = session.session_key();
+            if !session_ids.insert(key.clone()) {
+                return Err(format!("Duplicate session {}", key));
+            }
+            if let Some(schedule) = &session.schedule {
+                SessionSchedule::new(schedule).map_err(|e| format!("{} for session {}", e, key))?;
+            }
+            if session.reset_policy == ResetPolicy::Daily && session.schedule.is_none() {
+                return Err(format!("Session {} has a daily reset policy but no schedule", key));
+            }
+        }
+
+        if let Some(schedule) = &self.oms.schedule {
+            SessionSchedule::new(schedule).map_err(|e| format!("{} for the OMS", e))?;
+        }
+        if self.oms.gtc_store_path.is_some() && self.oms.journal_path.is_some() {
+            return Err("The OMS takes either a gtc_store_path or a journal_path, as both restore orders on startup".to_string());
+        }
+        if self.oms.shards == 0 {
+            return Err("The OMS needs at least 1 shard".to_string());
+        }
+        if self.oms.queue_capacity == 0 {
+            return Err("The OMS queue_capacity must be at least 1".to_string());
+        }
+        if self.oms.snapshot_path.is_some() && self.oms.journal_path.is_none() {
+            return Err("OMS snapshots need a journal_path to replay from".to_string());
+        }
+
+        let mut comp_ids = HashSet::new();
+        for counterparty in &self.counterparties {
+            if !comp_ids.insert(counterparty.comp_id.as_str()) {
+                return Err(format!("Duplicate counterparty {}", counterparty.comp_id));
+            }
+            if self.auth.mode == AuthMode::Password && (counterparty.username.is_none() || counterparty.password.is_none()) {
+                return Err(format!("Counterparty {} needs a username and password for password authentication", counterparty.comp_id));
+            }
+        }
+        let mut tokens = HashSet::new();
+        for operator in &self.operators {
+            if operator.token.is_empty() || !tokens.insert(operator.token.as_str()) {
+                return Err(format!("Operator {} needs a token of its own", operator.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SessionConfig {
+    fn session_key(&self) -> String {
+        format!("{}:{}->{}", self.begin_string, self.sender_comp_id, self.target_comp_id)
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multiple_sessions() {
+        let config = ServerConfig::parse(r#"
+            [server]
+            http_address = "0.0.0.0:9000"
+
+            [[session]]
+            sender_comp_id = "EXECUTOR"
+            target_comp_id = "CLIENT1"
+            role = "acceptor"
+            port = 9878
+            message_store_path = "store/CLIENT1"
+            reset_policy = "daily"
+            schedule = { start_time = "07:00:00", end_time = "17:30:00", time_zone = "Europe/London", weekdays = ["Mon", "Tue", "Wed", "Thu", "Fri"] }
+            cancel_on_disconnect_secs = 30
+
+            [[session]]
+            begin_string = "FIX.4.4"
+            sender_comp_id = "EXECUTOR"
+            target_comp_id = "VENUE"
+            role = "initiator"
+            host = "10.0.0.5"
+            port = 5001
+            heart_bt_int = 10
+        "#).unwrap();
+
+        assert_eq!(config.server.http_address, "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(config.sessions.len(), 2);
+
+        let acceptor = &config.sessions[0];
+        assert_eq!(acceptor.begin_string, "FIX.4.2");
+        assert_eq!(acceptor.role, SessionRole::Acceptor);
+        assert_eq!(acceptor.address(), "127.0.0.1:9878");
+        assert_eq!(acceptor.heart_bt_int, 30);
+        assert_eq!(acceptor.reset_policy, ResetPolicy::Daily);
+        assert_eq!(acceptor.schedule.as_ref().unwrap().time_zone, "Europe/London");
+        assert_eq!(acceptor.cancel_on_disconnect_secs, Some(30));
+
+        let initiator = &config.sessions[1];
+        assert_eq!(initiator.role, SessionRole::Initiator);
+        assert_eq!(initiator.address(), "10.0.0.5:5001");
+        assert_eq!(initiator.reset_policy, ResetPolicy::Never);
+        assert!(initiator.message_store_path.is_none());
+        assert!(initiator.cancel_on_disconnect_secs.is_none());
+    }
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = ServerConfig::parse("").unwrap();
+
+        assert_eq!(config.server.http_address, default_http_address());
+        assert!(config.sessions.is_empty());
+        assert_eq!(config.auth.mode, AuthMode::None);
+        assert!(config.counterparties.is_empty());
+        assert!(config.operators.is_empty());
+    }
+
+    #[test]
+    fn test_parse_counterparties() {
+        let config = ServerConfig::parse(r#"
+            [auth]
+            mode = "password"
+
+            [[counterparty]]
+            comp_id = "CLIENT1"
+            username = "client1"
+            password = "secret"
+            accounts = ["ACC1"]
+            msg_types = ["D", "F"]
+        "#).unwrap();
+
+        assert_eq!(config.auth.mode, AuthMode::Password);
+        let counterparty = &config.counterparties[0];
+        assert_eq!(counterparty.username.as_deref(), Some("client1"));
+        assert_eq!(counterparty.accounts, Some(vec!["ACC1".to_string()]));
+        assert!(counterparty.symbols.is_none());
+    }
+
+    #[test]
+    fn test_parse_oms() {
+        let config = ServerConfig::parse(r#"
+            [oms]
+            gtc_store_path = "store/gtc"
+            schedule = { start_time = "08:00:00", end_time = "16:30:00", time_zone = "Europe/London" }
+        "#).unwrap();
+
+        assert_eq!(config.oms.gtc_store_path, Some(PathBuf::from("store/gtc")));
+        assert_eq!(config.oms.schedule.as_ref().unwrap().end_time, "16:30:00");
+        assert!(ServerConfig::parse("[oms]\nschedule = { start_time = \"8am\", end_time = \"16:30:00\" }").is_err());
+        assert!(ServerConfig::parse("[oms]\ngtc_store_path = \"store/gtc\"\njournal_path = \"store/oms.journal\"").is_err());
+        assert!(ServerConfig::parse("[oms]\nsnapshot_path = \"store/snapshots\"").is_err());
+        assert_eq!(config.oms.snapshot_interval_secs, 300);
+        assert_eq!(config.oms.queue_capacity, 1024);
+        assert!(ServerConfig::parse("[oms]\nqueue_capacity = 0").is_err());
+        assert_eq!(config.oms.shard_path(Path::new("store/oms.journal"), 0), PathBuf::from("store/oms.journal"));
+
+        let sharded = ServerConfig::parse("[oms]\nshards = 4").unwrap();
+        assert_eq!(sharded.oms.shard_path(Path::new("store/oms.journal"), 2), PathBuf::from("store/oms.journal.2"));
+        assert!(ServerConfig::parse("[oms]\nshards = 0").is_err());
+
+        let prevention = ServerConfig::parse("[oms]\nself_trade_prevention = { mode = \"decrement_and_cancel\" }").unwrap();
+        let expected = SelfTradePrevention { mode: SelfTradeMode::DecrementAndCancel, key: SelfTradeKey::Account };
+        assert_eq!(prevention.oms.self_trade_prevention, Some(expected));
+        assert!(ServerConfig::parse("[oms]\nself_trade_prevention = { mode = \"cancel_newest\" }").is_err());
+
+        let ids = ServerConfig::parse("[oms]\nids = { prefix = \"LDN-\", width = 8, store_path = \"store/ids.json\" }").unwrap().oms.ids;
+        assert_eq!((ids.prefix.as_str(), ids.width, ids.store_path), ("LDN-", 8, Some(PathBuf::from("store/ids.json"))));
+        assert_eq!((config.oms.ids.prefix.as_str(), config.oms.ids.width), ("", 0));
+    }
+
+    #[test]
+    fn test_parse_risk_limits() {
+        let config = ServerConfig::parse(r#"
+            [risk]
+            max_order_qty = 100000
+            default_currency = "GBP"
+            price_band_percent = 10
+            restricted_symbols = ["XYZ.L"]
+
+            [risk.max_notional]
+            GBP = 1000000
+            USD = 1500000
+        "#).unwrap();
+
+        assert_eq!(config.risk.max_order_qty, Some(100000.0));
+        assert_eq!(config.risk.max_notional.get("USD"), Some(&1500000.0));
+        assert_eq!(config.risk.restricted_symbols, vec!["XYZ.L".to_string()]);
+        assert!(config.risk.max_open_orders_per_account.is_none());
+    }
+
+    #[test]
+    fn test_password_mode_requires_credentials() {
+        let result = ServerConfig::parse(r#"
+            [auth]
+            mode = "password"
+
+            [[counterparty]]
+            comp_id = "CLIENT1"
+        "#);
+
+        assert_eq!(result.unwrap_err(), "Counterparty CLIENT1 needs a username and password for password authentication");
+    }
+
+    #[test]
+    fn test_duplicate_sessions_are_rejected() {
+        let session = r#"
+            [[session]]
+            sender_comp_id = "EXECUTOR"
+            target_comp_id = "CLIENT1"
+            role = "acceptor"
+            port = 9878
+        "#;
+        let result = ServerConfig::parse(&format!("{}{}", session, session));
+
+        assert_eq!(result.unwrap_err(), "Duplicate session FIX.4.2:EXECUTOR->CLIENT1");
+    }
+
+    #[test]
+    fn test_invalid_schedule_time_is_rejected() {
+        let result = ServerConfig::parse(r#"
+            [[session]]
+            sender_comp_id = "EXECUTOR"
+            target_comp_id = "CLIENT1"
+            role = "acceptor"
+            port = 9878
+            schedule = { start_time = "7am", end_time = "17:30:00" }
+        "#);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Minimal data dictionary listing the tags each message type must carry, loaded from TOML:
+///
+/// ```toml
+/// [messages.D]
+/// required = [11, 54, 55, 38, 40]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct DataDictionary {
+    #[serde(default)]
+    messages: HashMap<String, MessageDefinition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDefinition {
+    #[serde(default)]
+    required: Vec<u32>,
+}
+
+impl DataDictionary {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read data dictionary {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid data dictionary {}: {}", path.display(), e))
+    }
+
+    /// Returns the first required tag missing from the message, if its type is defined.
+    pub fn missing_required_tag(&self, fields: &HashMap<String, String>) -> Option<u32> {
+        let definition = fields.get("35").and_then(|msg_type| self.messages.get(msg_type))?;
+        definition.required.iter()
+            .find(|tag| !fields.contains_key(&tag.to_string()))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+
+    #[test]
+    fn test_missing_required_tag() {
+        let dictionary: DataDictionary = toml::from_str("[messages.D]\nrequired = [11, 55, 54]\n").unwrap();
+
+        let complete = FixMessageParser::parse_fields("35=D|11=1|55=XYZ|54=1|");
+        let missing_side = FixMessageParser::parse_fields("35=D|11=1|55=XYZ|");
+        let undefined_type = FixMessageParser::parse_fields("35=F|");
+
+        assert_eq!(dictionary.missing_required_tag(&complete), None);
+        assert_eq!(dictionary.missing_required_tag(&missing_side), Some(54));
+        assert_eq!(dictionary.missing_required_tag(&undefined_type), None);
+    }
+}
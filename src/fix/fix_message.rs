@@ -1,18 +1,106 @@
 use std::collections::HashMap;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use crate::fix::messages::business_message_reject::BusinessMessageReject;
 use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::heartbeat::Heartbeat;
+use crate::fix::messages::logon::Logon;
+use crate::fix::messages::logout::Logout;
 use crate::fix::messages::new_order::NewOrder;
+use crate::fix::messages::order_cancel_reject::OrderCancelReject;
 use crate::fix::messages::order_cancel_request::OrderCancelRequest;
+use crate::fix::messages::order_mass_cancel_report::OrderMassCancelReport;
+use crate::fix::messages::order_mass_cancel_request::OrderMassCancelRequest;
 use crate::fix::messages::order_status_request::OrderStatusRequest;
+use crate::fix::messages::reject::Reject;
+use crate::fix::messages::resend_request::ResendRequest;
+use crate::fix::messages::sequence_reset::SequenceReset;
+use crate::fix::messages::test_request::TestRequest;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FixMessage {
     NewOrder(NewOrder),
     ExecutionReport(ExecutionReport),
     OrderCancelRequest(OrderCancelRequest),
+    OrderCancelReject(OrderCancelReject),
     OrderStatusRequest(OrderStatusRequest),
+    OrderMassCancelRequest(OrderMassCancelRequest),
+    OrderMassCancelReport(OrderMassCancelReport),
+    Logon(Logon),
+    Logout(Logout),
+    Heartbeat(Heartbeat),
+    TestRequest(TestRequest),
+    ResendRequest(ResendRequest),
+    SequenceReset(SequenceReset),
+    Reject(Reject),
+    BusinessMessageReject(BusinessMessageReject),
     Unknown,
 }
 
+impl FixMessage {
+    /// Writer for the messages this engine sends and the order messages it journals; other
+    /// inbound-only messages return `None`.
+    pub fn to_writer(&self) -> Option<FixMessageWriter> {
+        match self {
+            FixMessage::NewOrder(order) => Some(order.to_writer()),
+            FixMessage::OrderCancelRequest(request) => Some(request.to_writer()),
+            FixMessage::OrderStatusRequest(request) => Some(request.to_writer()),
+            FixMessage::OrderMassCancelRequest(request) => Some(request.to_writer()),
+            FixMessage::OrderMassCancelReport(report) => Some(report.to_writer()),
+            FixMessage::ExecutionReport(report) => Some(report.to_writer()),
+            FixMessage::OrderCancelReject(reject) => Some(reject.to_writer()),
+            FixMessage::BusinessMessageReject(reject) => Some(reject.to_writer()),
+            FixMessage::Reject(reject) => Some(reject.to_writer()),
+            FixMessage::Logon(logon) => Some(logon.to_writer()),
+            FixMessage::Logout(logout) => Some(logout.to_writer()),
+            FixMessage::Heartbeat(heartbeat) => Some(heartbeat.to_writer()),
+            FixMessage::TestRequest(test_request) => Some(test_request.to_writer()),
+            FixMessage::ResendRequest(resend_request) => Some(resend_request.to_writer()),
+            FixMessage::SequenceReset(sequence_reset) => Some(sequence_reset.to_writer()),
+            _ => None,
+        }
+    }
+
+    pub fn cl_ord_id(&self) -> Option<&str> {
+        match self {
+            FixMessage::NewOrder(order) => Some(&order.cl_ord_id),
+            FixMessage::OrderCancelRequest(request) => Some(&request.cl_ord_id),
+            FixMessage::OrderStatusRequest(request) => Some(&request.cl_ord_id),
+            FixMessage::ExecutionReport(report) => Some(&report.cl_ord_id),
+            FixMessage::OrderCancelReject(reject) => Some(&reject.cl_ord_id),
+            FixMessage::OrderMassCancelRequest(request) => Some(&request.cl_ord_id),
+            FixMessage::OrderMassCancelReport(report) => Some(&report.cl_ord_id),
+            _ => None,
+        }
+    }
+
+    pub fn account(&self) -> Option<&str> {
+        match self {
+            FixMessage::NewOrder(order) => order.account.as_deref(),
+            FixMessage::OrderCancelRequest(request) => request.account.as_deref(),
+            FixMessage::OrderMassCancelRequest(request) => request.account.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            FixMessage::NewOrder(order) => Some(&order.symbol),
+            FixMessage::OrderCancelRequest(request) => Some(&request.symbol),
+            FixMessage::OrderStatusRequest(request) => Some(&request.symbol),
+            FixMessage::ExecutionReport(report) => Some(&report.symbol),
+            FixMessage::OrderMassCancelRequest(request) => request.symbol.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Session-level message types, handled by the session layer and never resent on a ResendRequest.
+pub const ADMIN_MSG_TYPES: [&str; 7] = ["0", "1", "2", "3", "4", "5", "A"];
+
+pub fn is_admin_msg_type(msg_type: &str) -> bool {
+    ADMIN_MSG_TYPES.contains(&msg_type)
+}
+
 pub fn parse_field<T: std::str::FromStr>(fields: &HashMap<String, String>, tag: &str) -> Result<T, String> {
     fields.get(tag)
         .ok_or_else(|| {
@@ -1,35 +1,111 @@
 use crate::fix::fix_message::FixMessage;
 use std::collections::HashMap;
+use crate::fix::messages::business_message_reject::BusinessMessageReject;
 use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::heartbeat::Heartbeat;
+use crate::fix::messages::logon::Logon;
+use crate::fix::messages::logout::Logout;
 use crate::fix::messages::new_order::NewOrder;
+use crate::fix::messages::order_cancel_reject::OrderCancelReject;
 use crate::fix::messages::order_cancel_request::OrderCancelRequest;
+use crate::fix::messages::order_mass_cancel_report::OrderMassCancelReport;
+use crate::fix::messages::order_mass_cancel_request::OrderMassCancelRequest;
 use crate::fix::messages::order_status_request::OrderStatusRequest;
+use crate::fix::messages::reject::Reject;
+use crate::fix::messages::resend_request::ResendRequest;
+use crate::fix::messages::sequence_reset::SequenceReset;
+use crate::fix::messages::test_request::TestRequest;
 
+/// Separates fields in the text form messages take over HTTP and WebSocket and in tests.
 pub const DELIMITER: char = '|';
+/// Separates fields on the FIX wire.
+pub const SOH: char = '\x01';
 pub const ASSIGNMENT: char = '=';
+/// Characters no field value may hold. A line break would split the line a message is journaled
+/// or stored on, and either delimiter would split the value once the message is read back in
+/// its text form.
+const FORBIDDEN_IN_VALUES: [char; 4] = ['\r', '\n', SOH, DELIMITER];
 
 pub struct FixMessageParser;
 
 impl FixMessageParser {
     pub fn parse_message(message: &str) -> FixMessage {
         println!("Parsing message: {}", message);
+        Self::parse_fields_into_message(Self::parse_fields(message))
+    }
+
+    pub fn parse_fields(message: &str) -> HashMap<String, String> {
         let mut fields = HashMap::new();
-        let mut msg_type = "";
 
-        for pair in message.split(DELIMITER) {
+        for pair in message.split(Self::separator(message)) {
             if let Some((key, value)) = pair.split_once(ASSIGNMENT) {
                 fields.insert(key.to_string(), value.to_string());
-                if key == "35" {
-                    msg_type = value;
-                }
             }
         }
 
-        match msg_type {
+        fields
+    }
+
+    /// Whether `value` can be written out and read back as it is.
+    pub fn is_safe_value(value: &str) -> bool {
+        !value.contains(FORBIDDEN_IN_VALUES)
+    }
+
+    /// The field separator `message` uses: SOH as sent by FIX engines, otherwise `DELIMITER`.
+    pub fn separator(message: &str) -> char {
+        if message.contains(SOH) { SOH } else { DELIMITER }
+    }
+
+    /// Checks that BodyLength (9) counts the characters between itself and CheckSum (10), and
+    /// that CheckSum is the sum of every byte before it, modulo 256.
+    pub fn check_envelope(message: &str) -> Result<(), String> {
+        let separator = Self::separator(message);
+        let begin_string_end = message.find(separator).ok_or("Missing BeginString")?;
+        let body_length_field = &message[begin_string_end + 1..];
+        let body_length_end = body_length_field.find(separator).ok_or("Missing BodyLength")?;
+        let body_length: usize = body_length_field[..body_length_end]
+            .strip_prefix("9=")
+            .and_then(|value| value.parse().ok())
+            .ok_or("Missing or invalid BodyLength")?;
+        let body_start = begin_string_end + 1 + body_length_end + 1;
+
+        let trailer = format!("{}10=", separator);
+        let body_end = message.rfind(&trailer).ok_or("Missing CheckSum")? + 1;
+        if body_end < body_start || body_end - body_start != body_length {
+            return Err(format!("BodyLength {} does not match the {} characters received", body_length, body_end.saturating_sub(body_start)));
+        }
+
+        let checksum: u32 = message[body_end + 3..].trim_end_matches(separator).parse().map_err(|_| "Invalid CheckSum")?;
+        let expected = message[..body_end].bytes().fold(0u32, |sum, byte| sum + byte as u32) % 256;
+        if checksum != expected {
+            return Err(format!("CheckSum {:03} does not match the computed {:03}", checksum, expected));
+        }
+        Ok(())
+    }
+
+    pub fn parse_fields_into_message(fields: HashMap<String, String>) -> FixMessage {
+        if let Some(tag) = fields.iter().find(|(_, value)| !Self::is_safe_value(value)).map(|(tag, _)| tag) {
+            eprintln!("Refusing message: field {} holds a line break or field delimiter", tag);
+            return FixMessage::Unknown;
+        }
+        let msg_type = fields.get("35").cloned().unwrap_or_default();
+
+        match msg_type.as_str() {
             "D" => NewOrder::new(fields).map_or(FixMessage::Unknown, FixMessage::NewOrder),
             "8" => ExecutionReport::new(fields).map_or(FixMessage::Unknown, FixMessage::ExecutionReport),
             "F" => OrderCancelRequest::new(fields).map_or(FixMessage::Unknown, FixMessage::OrderCancelRequest),
+            "9" => OrderCancelReject::new(fields).map_or(FixMessage::Unknown, FixMessage::OrderCancelReject),
             "H" => OrderStatusRequest::new(fields).map_or(FixMessage::Unknown, FixMessage::OrderStatusRequest),
+            "q" => OrderMassCancelRequest::new(fields).map_or(FixMessage::Unknown, FixMessage::OrderMassCancelRequest),
+            "r" => OrderMassCancelReport::new(fields).map_or(FixMessage::Unknown, FixMessage::OrderMassCancelReport),
+            "A" => Logon::new(fields).map_or(FixMessage::Unknown, FixMessage::Logon),
+            "5" => Logout::new(fields).map_or(FixMessage::Unknown, FixMessage::Logout),
+            "0" => Heartbeat::new(fields).map_or(FixMessage::Unknown, FixMessage::Heartbeat),
+            "1" => TestRequest::new(fields).map_or(FixMessage::Unknown, FixMessage::TestRequest),
+            "2" => ResendRequest::new(fields).map_or(FixMessage::Unknown, FixMessage::ResendRequest),
+            "4" => SequenceReset::new(fields).map_or(FixMessage::Unknown, FixMessage::SequenceReset),
+            "3" => Reject::new(fields).map_or(FixMessage::Unknown, FixMessage::Reject),
+            "j" => BusinessMessageReject::new(fields).map_or(FixMessage::Unknown, FixMessage::BusinessMessageReject),
             _ => {
                 eprintln!("Unknown message type: {}", msg_type);
                 FixMessage::Unknown
@@ -79,7 +155,7 @@ mod tests {
                 assert_eq!(execution_report.symbol, "XYZ");
                 assert_eq!(execution_report.side, '1');
                 assert_eq!(execution_report.order_qty, 100.0);
-                assert_eq!(execution_report.price, 50.5);
+                assert_eq!(execution_report.price, Some(50.5));
             },
             _ => panic!("Expected ExecutionReport message"),
         }
@@ -116,6 +192,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_order_mass_cancel_request() {
+        let message = "35=q|11=M1|530=7|1=ACC1|54=2|60=20231027-15:48:00.123|";
+        let parsed_message = FixMessageParser::parse_message(message);
+
+        match parsed_message {
+            FixMessage::OrderMassCancelRequest(request) => {
+                assert_eq!(request.cl_ord_id, "M1");
+                assert_eq!(request.mass_cancel_request_type, '7');
+                assert_eq!(request.account.as_deref(), Some("ACC1"));
+                assert_eq!(request.side, Some('2'));
+                assert_eq!(request.symbol, None);
+            },
+            _ => panic!("Expected OrderMassCancelRequest message"),
+        }
+    }
+
+    #[test]
+    fn test_parse_resend_request() {
+        let message = "8=FIX.4.2|35=2|49=CLIENT1|56=EXECUTOR|34=6|52=20231027-15:48:00.123|7=2|16=0|";
+        let parsed_message = FixMessageParser::parse_message(message);
+
+        match parsed_message {
+            FixMessage::ResendRequest(resend_request) => {
+                assert_eq!(resend_request.begin_seq_no, 2);
+                assert_eq!(resend_request.end_seq_no, 0);
+            },
+            _ => panic!("Expected ResendRequest message"),
+        }
+    }
+
+    #[test]
+    fn test_parse_sequence_reset_gap_fill() {
+        let message = "8=FIX.4.2|35=4|49=CLIENT1|56=EXECUTOR|34=3|43=Y|52=20231027-15:48:00.123|123=Y|36=6|";
+        let parsed_message = FixMessageParser::parse_message(message);
+
+        match parsed_message {
+            FixMessage::SequenceReset(sequence_reset) => {
+                assert!(sequence_reset.is_gap_fill());
+                assert_eq!(sequence_reset.new_seq_no, 6);
+            },
+            _ => panic!("Expected SequenceReset message"),
+        }
+    }
+
+    #[test]
+    fn test_check_envelope() {
+        let message = "8=FIX.4.2\x019=5\x0135=0\x0110=161\x01";
+        assert_eq!(FixMessageParser::check_envelope(message), Ok(()));
+        assert_eq!(FixMessageParser::parse_fields(message).get("35"), Some(&"0".to_string()));
+
+        let garbled = message.replace("9=5", "9=6");
+        assert_eq!(FixMessageParser::check_envelope(&garbled).unwrap_err(), "BodyLength 6 does not match the 5 characters received");
+        let garbled = message.replace("10=161", "10=162");
+        assert_eq!(FixMessageParser::check_envelope(&garbled).unwrap_err(), "CheckSum 162 does not match the computed 161");
+    }
+
     #[test]
     fn test_parse_unknown_message() {
         let message = "35=Z|11=12345|";
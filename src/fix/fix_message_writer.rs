@@ -0,0 +1,157 @@
+use crate::fix::fix_message_parser::{FixMessageParser, ASSIGNMENT, DELIMITER, SOH};
+use crate::fix::messages::standard_header::StandardHeader;
+use std::fmt::Display;
+
+/// Tags owned by the standard header and trailer, written by `encode` rather than the body.
+const ENVELOPE_TAGS: [&str; 10] = ["8", "9", "35", "49", "56", "34", "52", "43", "122", "10"];
+
+/// Builds the body of an outbound FIX message and encodes it behind a `StandardHeader`,
+/// filling in BodyLength (9) and CheckSum (10), or without a header in the '|' text form.
+#[derive(Debug, Clone)]
+pub struct FixMessageWriter {
+    msg_type: String,
+    fields: Vec<(String, String)>,
+}
+
+impl FixMessageWriter {
+    pub fn new(msg_type: &str) -> Self {
+        FixMessageWriter {
+            msg_type: msg_type.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Rebuilds a writer from a previously encoded message, keeping the body fields in their
+    /// original order and dropping the header and trailer.
+    pub fn from_message(message: &str) -> Option<Self> {
+        let mut msg_type = None;
+        let mut fields = Vec::new();
+
+        for pair in message.split(FixMessageParser::separator(message)) {
+            if let Some((key, value)) = pair.split_once(ASSIGNMENT) {
+                if key == "35" {
+                    msg_type = Some(value.to_string());
+                } else if !ENVELOPE_TAGS.contains(&key) {
+                    fields.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        msg_type.map(|msg_type| FixMessageWriter { msg_type, fields })
+    }
+
+    pub fn field<T: Display>(mut self, tag: &str, value: T) -> Self {
+        self.fields.push((tag.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn optional_field<T: Display>(self, tag: &str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.field(tag, value),
+            None => self,
+        }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.msg_type
+    }
+
+    /// Encodes MsgType and the body without a header or trailer, the form messages take over
+    /// HTTP and WebSocket where there is no session to number them.
+    pub fn encode_body(&self) -> String {
+        let mut message = String::new();
+        push_field(&mut message, "35", &self.msg_type, DELIMITER);
+        for (tag, value) in &self.fields {
+            push_field(&mut message, tag, value, DELIMITER);
+        }
+        message
+    }
+
+    /// Encodes the message as it goes on the wire of a FIX session, separated by SOH.
+    pub fn encode(&self, header: &StandardHeader) -> String {
+        let mut body = String::new();
+        push_field(&mut body, "35", &self.msg_type, SOH);
+        push_field(&mut body, "49", &header.sender_comp_id, SOH);
+        push_field(&mut body, "56", &header.target_comp_id, SOH);
+        push_field(&mut body, "34", &header.msg_seq_num.to_string(), SOH);
+        if let Some(poss_dup_flag) = header.poss_dup_flag {
+            push_field(&mut body, "43", &poss_dup_flag.to_string(), SOH);
+        }
+        push_field(&mut body, "52", &header.sending_time, SOH);
+        if let Some(orig_sending_time) = &header.orig_sending_time {
+            push_field(&mut body, "122", orig_sending_time, SOH);
+        }
+        for (tag, value) in &self.fields {
+            push_field(&mut body, tag, value, SOH);
+        }
+
+        let mut message = String::new();
+        push_field(&mut message, "8", &header.begin_string, SOH);
+        push_field(&mut message, "9", &body.len().to_string(), SOH);
+        message.push_str(&body);
+
+        let checksum = message.bytes().fold(0u32, |sum, byte| sum + byte as u32) % 256;
+        push_field(&mut message, "10", &format!("{:03}", checksum), SOH);
+        message
+    }
+}
+
+fn push_field(message: &mut String, tag: &str, value: &str, separator: char) {
+    message.push_str(tag);
+    message.push(ASSIGNMENT);
+    message.push_str(value);
+    message.push(separator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+
+    fn header(msg_type: &str) -> StandardHeader {
+        StandardHeader {
+            begin_string: "FIX.4.2".to_string(),
+            msg_type: msg_type.to_string(),
+            sender_comp_id: "SERVER".to_string(),
+            target_comp_id: "CLIENT".to_string(),
+            msg_seq_num: 7,
+            sending_time: "20231027-15:48:00.123".to_string(),
+            poss_dup_flag: None,
+            orig_sending_time: None,
+        }
+    }
+
+    #[test]
+    fn test_encode_computes_body_length_and_checksum() {
+        let message = FixMessageWriter::new("0").encode(&header("0"));
+
+        assert_eq!(message, "8=FIX.4.2|9=55|35=0|49=SERVER|56=CLIENT|34=7|52=20231027-15:48:00.123|10=105|".replace('|', "\x01"));
+    }
+
+    #[test]
+    fn test_encode_round_trips_through_parser() {
+        let message = FixMessageWriter::new("2")
+            .field("7", 3)
+            .field("16", 0)
+            .encode(&header("2"));
+        let fields = FixMessageParser::parse_fields(&message);
+
+        assert_eq!(fields.get("35"), Some(&"2".to_string()));
+        assert_eq!(fields.get("34"), Some(&"7".to_string()));
+        assert_eq!(fields.get("7"), Some(&"3".to_string()));
+        assert_eq!(fields.get("16"), Some(&"0".to_string()));
+    }
+
+    #[test]
+    fn test_from_message_keeps_body_order_and_drops_envelope() {
+        let message = "8=FIX.4.2|9=40|35=D|49=CLIENT|56=SERVER|34=2|52=20231027-15:48:00.123|11=1|55=XYZ|54=1|10=000|";
+        let writer = FixMessageWriter::from_message(message).unwrap();
+
+        assert_eq!(writer.msg_type(), "D");
+        assert_eq!(writer.fields, vec![
+            ("11".to_string(), "1".to_string()),
+            ("55".to_string(), "XYZ".to_string()),
+            ("54".to_string(), "1".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct BusinessMessageReject {
+    pub ref_seq_num: Option<u64>,               // Tag 45: MsgSeqNum of the rejected message
+    pub ref_msg_type: String,                   // Tag 372: MsgType of the rejected message
+    pub business_reject_ref_id: Option<String>, // Tag 379: Business-level ID of the rejected message, e.g. ClOrdID
+    pub business_reject_reason: u32,            // Tag 380: Code identifying the reject reason
+    pub text: Option<String>,                   // Tag 58: Human readable reason
+}
+
+impl BusinessMessageReject {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"j".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(BusinessMessageReject {
+            ref_seq_num: parse_field_optional::<u64>(&fix_msg, "45")?,
+            ref_msg_type: parse_field(&fix_msg, "372")?,
+            business_reject_ref_id: parse_field_optional(&fix_msg, "379")?,
+            business_reject_reason: parse_field::<u32>(&fix_msg, "380")?,
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("j")
+            .optional_field("45", self.ref_seq_num)
+            .field("372", &self.ref_msg_type)
+            .optional_field("379", self.business_reject_ref_id.as_ref())
+            .field("380", self.business_reject_reason)
+            .optional_field("58", self.text.as_ref())
+    }
+}
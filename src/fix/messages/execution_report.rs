@@ -1,7 +1,8 @@
 use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExecutionReport {
     pub cl_ord_id: String,        // Tag 11: Client's order ID
     pub order_id: String,         // Tag 37: Unique identifier of the order assigned by the broker
@@ -15,7 +16,18 @@ pub struct ExecutionReport {
     pub symbol: String,           // Tag 55: Ticker symbol
     pub transact_time: String,    // Tag 60: Execution transaction time
     pub order_qty: f64,           // Tag 38: Quantity of the order
-    pub price: f64,               // Tag 44: Price of the order
+    pub price: Option<f64>,       // Tag 44: Price of the order, absent for market orders
+    pub ord_type: Option<char>,         // Tag 40: Order type, which changes when a stop triggers
+    pub stop_px: Option<f64>,           // Tag 99: Stop price of a stop or stop limit order
+    pub max_floor: Option<f64>,         // Tag 111: Most of an iceberg order's quantity shown at once
+    pub display_qty: Option<f64>,       // Tag 1138: Quantity an iceberg order shows, as introduced in FIX 5.0
+    pub exec_trans_type: Option<char>,  // Tag 20: Transaction type ('0' for New), required by FIX 4.2
+    pub account: Option<String>,        // Tag 1: Account of the order
+    pub orig_cl_ord_id: Option<String>, // Tag 41: Previous ClOrdID after a cancel or replace
+    pub ord_rej_reason: Option<u32>,    // Tag 103: Why the order was rejected
+    pub last_qty: Option<f64>,          // Tag 32: Quantity traded in this execution
+    pub last_px: Option<f64>,           // Tag 31: Price of this execution
+    pub text: Option<String>,           // Tag 58: Human readable explanation
 }
 
 impl ExecutionReport {
@@ -37,7 +49,46 @@ impl ExecutionReport {
             symbol: parse_field(&fix_msg, "55")?,
             transact_time: parse_field(&fix_msg, "60")?,
             order_qty: parse_field::<f64>(&fix_msg, "38")?,
-            price: parse_field::<f64>(&fix_msg, "44")?,
+            price: parse_field_optional::<f64>(&fix_msg, "44")?,
+            ord_type: parse_field_optional::<char>(&fix_msg, "40")?,
+            stop_px: parse_field_optional::<f64>(&fix_msg, "99")?,
+            max_floor: parse_field_optional::<f64>(&fix_msg, "111")?,
+            display_qty: parse_field_optional::<f64>(&fix_msg, "1138")?,
+            exec_trans_type: parse_field_optional::<char>(&fix_msg, "20")?,
+            account: parse_field_optional(&fix_msg, "1")?,
+            orig_cl_ord_id: parse_field_optional(&fix_msg, "41")?,
+            ord_rej_reason: parse_field_optional::<u32>(&fix_msg, "103")?,
+            last_qty: parse_field_optional::<f64>(&fix_msg, "32")?,
+            last_px: parse_field_optional::<f64>(&fix_msg, "31")?,
+            text: parse_field_optional(&fix_msg, "58")?,
         })
     }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("8")
+            .field("37", &self.order_id)
+            .field("11", &self.cl_ord_id)
+            .optional_field("41", self.orig_cl_ord_id.as_ref())
+            .field("17", &self.exec_id)
+            .optional_field("20", self.exec_trans_type)
+            .field("150", self.exec_type)
+            .field("39", self.ord_status)
+            .optional_field("103", self.ord_rej_reason)
+            .optional_field("1", self.account.as_ref())
+            .field("55", &self.symbol)
+            .field("54", self.side)
+            .field("38", self.order_qty)
+            .optional_field("111", self.max_floor)
+            .optional_field("40", self.ord_type)
+            .optional_field("44", self.price)
+            .optional_field("99", self.stop_px)
+            .optional_field("32", self.last_qty)
+            .optional_field("31", self.last_px)
+            .field("151", self.leaves_qty)
+            .field("14", self.cum_qty)
+            .optional_field("1138", self.display_qty)
+            .optional_field("6", self.avg_px)
+            .field("60", &self.transact_time)
+            .optional_field("58", self.text.as_ref())
+    }
 }
\ No newline at end of file
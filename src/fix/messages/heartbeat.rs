@@ -0,0 +1,25 @@
+use crate::fix::fix_message::parse_field_optional;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    pub test_req_id: Option<String>,  // Tag 112: Echoed TestReqID when answering a TestRequest
+}
+
+impl Heartbeat {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"0".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(Heartbeat {
+            test_req_id: parse_field_optional(&fix_msg, "112")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("0")
+            .optional_field("112", self.test_req_id.as_ref())
+    }
+}
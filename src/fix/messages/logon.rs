@@ -0,0 +1,37 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Logon {
+    pub encrypt_method: u32,                // Tag 98: Encryption method ('0' for None)
+    pub heart_bt_int: u64,                  // Tag 108: Heartbeat interval in seconds
+    pub reset_seq_num_flag: Option<char>,   // Tag 141: 'Y' to reset both sides' sequence numbers
+    pub username: Option<String>,           // Tag 553: Username used to authenticate the logon
+    pub password: Option<String>,           // Tag 554: Password used to authenticate the logon
+}
+
+impl Logon {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"A".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(Logon {
+            encrypt_method: parse_field::<u32>(&fix_msg, "98")?,
+            heart_bt_int: parse_field::<u64>(&fix_msg, "108")?,
+            reset_seq_num_flag: parse_field_optional::<char>(&fix_msg, "141")?,
+            username: parse_field_optional(&fix_msg, "553")?,
+            password: parse_field_optional(&fix_msg, "554")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("A")
+            .field("98", self.encrypt_method)
+            .field("108", self.heart_bt_int)
+            .optional_field("141", self.reset_seq_num_flag)
+            .optional_field("553", self.username.as_ref())
+            .optional_field("554", self.password.as_ref())
+    }
+}
@@ -0,0 +1,25 @@
+use crate::fix::fix_message::parse_field_optional;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Logout {
+    pub text: Option<String>,     // Tag 58: Reason for the logout
+}
+
+impl Logout {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"5".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(Logout {
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("5")
+            .optional_field("58", self.text.as_ref())
+    }
+}
@@ -1,4 +1,16 @@
 pub mod new_order;
 pub mod order_cancel_request;
 pub mod execution_report;
-pub mod order_status_request;
\ No newline at end of file
+pub mod order_status_request;
+pub mod standard_header;
+pub mod logon;
+pub mod logout;
+pub mod heartbeat;
+pub mod test_request;
+pub mod resend_request;
+pub mod sequence_reset;
+pub mod reject;
+pub mod business_message_reject;
+pub mod order_cancel_reject;
+pub mod order_mass_cancel_request;
+pub mod order_mass_cancel_report;
\ No newline at end of file
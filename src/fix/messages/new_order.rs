@@ -1,7 +1,8 @@
 use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NewOrder {
     pub cl_ord_id: String,              // Tag 11: Unique ID for the order from the client
     pub side: char,                     // Tag 54: Side of the order (e.g., '1' for Buy, '2' for Sell)
@@ -9,11 +10,17 @@ pub struct NewOrder {
     pub order_qty: f64,                 // Tag 38: Quantity of the order
     pub ord_type: char,                 // Tag 40: Order type (e.g., '1' for Market, '2' for Limit)
     pub price: Option<f64>,             // Tag 44: Price for limit orders, optional for market orders
+    pub stop_px: Option<f64>,           // Tag 99: Trigger price for stop and stop limit orders
+    pub peg_offset_value: Option<f64>,  // Tag 211: Distance a trailing stop keeps from the last price
+    pub max_floor: Option<f64>,         // Tag 111: Most of an iceberg order's quantity shown at once
     pub symbol: String,                 // Tag 55: Ticker symbol for the instrument
     pub time_in_force: Option<char>,    // Tag 59: Duration of the order (e.g., '0' for Day)
     pub account: Option<String>,        // Tag 1: Optional account identifier
+    pub self_match_prevention_id: Option<String>, // Tag 2362: Orders sharing it are kept from trading with each other
     pub currency: Option<String>,       // Tag 15: Currency of the order
     pub exec_inst: Option<String>,      // Tag 18: Execution instructions, if applicable
+    pub expire_time: Option<String>,    // Tag 126: UTC time a Good Till Date order expires
+    pub expire_date: Option<String>,    // Tag 432: Local market date a Good Till Date order expires, YYYYMMDD
 }
 
 impl NewOrder {
@@ -29,11 +36,38 @@ impl NewOrder {
             order_qty: parse_field::<f64>(&fix_msg, "38")?,
             ord_type: parse_field::<char>(&fix_msg, "40")?,
             price: parse_field_optional::<f64>(&fix_msg, "44")?,
+            stop_px: parse_field_optional::<f64>(&fix_msg, "99")?,
+            peg_offset_value: parse_field_optional::<f64>(&fix_msg, "211")?,
+            max_floor: parse_field_optional::<f64>(&fix_msg, "111")?,
             symbol: parse_field(&fix_msg, "55")?,
             time_in_force: parse_field_optional::<char>(&fix_msg, "59")?,
             account: parse_field_optional(&fix_msg, "1")?,
+            self_match_prevention_id: parse_field_optional(&fix_msg, "2362")?,
             currency: parse_field_optional(&fix_msg, "15")?,
             exec_inst: parse_field_optional(&fix_msg, "18")?,
+            expire_time: parse_field_optional(&fix_msg, "126")?,
+            expire_date: parse_field_optional(&fix_msg, "432")?,
         })
     }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("D")
+            .field("11", &self.cl_ord_id)
+            .optional_field("1", self.account.as_ref())
+            .optional_field("2362", self.self_match_prevention_id.as_ref())
+            .field("55", &self.symbol)
+            .field("54", self.side)
+            .field("60", &self.transact_time)
+            .field("38", self.order_qty)
+            .optional_field("111", self.max_floor)
+            .field("40", self.ord_type)
+            .optional_field("44", self.price)
+            .optional_field("99", self.stop_px)
+            .optional_field("211", self.peg_offset_value)
+            .optional_field("59", self.time_in_force)
+            .optional_field("15", self.currency.as_ref())
+            .optional_field("18", self.exec_inst.as_ref())
+            .optional_field("126", self.expire_time.as_ref())
+            .optional_field("432", self.expire_date.as_ref())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,43 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct OrderCancelReject {
+    pub order_id: String,               // Tag 37: OrderID of the order, or "NONE" if unknown
+    pub cl_ord_id: String,              // Tag 11: ClOrdID of the rejected cancel request
+    pub orig_cl_ord_id: String,         // Tag 41: ClOrdID of the order the request referred to
+    pub ord_status: char,               // Tag 39: Current status of the order
+    pub cxl_rej_response_to: char,      // Tag 434: '1' for OrderCancelRequest, '2' for OrderCancelReplaceRequest
+    pub cxl_rej_reason: Option<u32>,    // Tag 102: Code identifying the reject reason
+    pub text: Option<String>,           // Tag 58: Human readable reason
+}
+
+impl OrderCancelReject {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"9".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(OrderCancelReject {
+            order_id: parse_field(&fix_msg, "37")?,
+            cl_ord_id: parse_field(&fix_msg, "11")?,
+            orig_cl_ord_id: parse_field(&fix_msg, "41")?,
+            ord_status: parse_field::<char>(&fix_msg, "39")?,
+            cxl_rej_response_to: parse_field::<char>(&fix_msg, "434")?,
+            cxl_rej_reason: parse_field_optional::<u32>(&fix_msg, "102")?,
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("9")
+            .field("37", &self.order_id)
+            .field("11", &self.cl_ord_id)
+            .field("41", &self.orig_cl_ord_id)
+            .field("39", self.ord_status)
+            .field("434", self.cxl_rej_response_to)
+            .optional_field("102", self.cxl_rej_reason)
+            .optional_field("58", self.text.as_ref())
+    }
+}
@@ -1,7 +1,8 @@
 use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrderCancelRequest {
     pub orig_cl_ord_id: String,   // Tag 41: Original client order ID
     pub cl_ord_id: String,        // Tag 11: Client's order ID
@@ -9,6 +10,7 @@ pub struct OrderCancelRequest {
     pub symbol: String,           // Tag 55: Ticker symbol
     pub transact_time: String,    // Tag 60: Transaction time
     pub order_qty: Option<f64>,   // Tag 38: Order quantity
+    pub account: Option<String>,  // Tag 1: Account of the original order
 }
 
 impl OrderCancelRequest {
@@ -24,6 +26,18 @@ impl OrderCancelRequest {
             symbol: parse_field(&fix_msg, "55")?,
             transact_time: parse_field(&fix_msg, "60")?,
             order_qty: parse_field_optional::<f64>(&fix_msg, "38")?,
+            account: parse_field_optional(&fix_msg, "1")?,
         })
     }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("F")
+            .field("41", &self.orig_cl_ord_id)
+            .field("11", &self.cl_ord_id)
+            .optional_field("1", self.account.as_ref())
+            .field("54", self.side)
+            .field("55", &self.symbol)
+            .field("60", &self.transact_time)
+            .optional_field("38", self.order_qty)
+    }
 }
\ No newline at end of file
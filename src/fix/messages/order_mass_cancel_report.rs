@@ -0,0 +1,55 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct OrderMassCancelReport {
+    pub cl_ord_id: String,                          // Tag 11: ClOrdID of the mass cancel request
+    pub order_id: String,                           // Tag 37: ID the recipient gave the request
+    pub mass_cancel_request_type: char,             // Tag 530: Scope of the request
+    pub mass_cancel_response: char,                 // Tag 531: '0' if rejected, otherwise the scope carried out
+    pub mass_cancel_reject_reason: Option<u32>,     // Tag 532: Why the request was rejected
+    pub total_affected_orders: Option<u32>,         // Tag 533: How many orders were canceled
+    pub symbol: Option<String>,                     // Tag 55: Security of the request
+    pub side: Option<char>,                         // Tag 54: Side of the request
+    pub account: Option<String>,                    // Tag 1: Account of the request
+    pub transact_time: Option<String>,              // Tag 60: Time the request was carried out
+    pub text: Option<String>,                       // Tag 58: Human readable explanation
+}
+
+impl OrderMassCancelReport {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"r".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(OrderMassCancelReport {
+            cl_ord_id: parse_field(&fix_msg, "11")?,
+            order_id: parse_field(&fix_msg, "37")?,
+            mass_cancel_request_type: parse_field::<char>(&fix_msg, "530")?,
+            mass_cancel_response: parse_field::<char>(&fix_msg, "531")?,
+            mass_cancel_reject_reason: parse_field_optional::<u32>(&fix_msg, "532")?,
+            total_affected_orders: parse_field_optional::<u32>(&fix_msg, "533")?,
+            symbol: parse_field_optional(&fix_msg, "55")?,
+            side: parse_field_optional::<char>(&fix_msg, "54")?,
+            account: parse_field_optional(&fix_msg, "1")?,
+            transact_time: parse_field_optional(&fix_msg, "60")?,
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("r")
+            .field("11", &self.cl_ord_id)
+            .field("37", &self.order_id)
+            .field("530", self.mass_cancel_request_type)
+            .field("531", self.mass_cancel_response)
+            .optional_field("532", self.mass_cancel_reject_reason)
+            .optional_field("533", self.total_affected_orders)
+            .optional_field("1", self.account.as_ref())
+            .optional_field("55", self.symbol.as_ref())
+            .optional_field("54", self.side)
+            .optional_field("60", self.transact_time.as_ref())
+            .optional_field("58", self.text.as_ref())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct OrderMassCancelRequest {
+    pub cl_ord_id: String,                  // Tag 11: Client's ID for the request
+    pub mass_cancel_request_type: char,     // Tag 530: Scope, e.g. '1' for one security, '7' for all orders
+    pub symbol: Option<String>,             // Tag 55: Security to cancel orders in, for request type '1'
+    pub side: Option<char>,                 // Tag 54: Only cancel orders on this side
+    pub account: Option<String>,            // Tag 1: Only cancel orders of this account
+    pub transact_time: String,              // Tag 60: Time the request was initiated
+    pub text: Option<String>,               // Tag 58: Free text, such as why the orders are pulled
+}
+
+impl OrderMassCancelRequest {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"q".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(OrderMassCancelRequest {
+            cl_ord_id: parse_field(&fix_msg, "11")?,
+            mass_cancel_request_type: parse_field::<char>(&fix_msg, "530")?,
+            symbol: parse_field_optional(&fix_msg, "55")?,
+            side: parse_field_optional::<char>(&fix_msg, "54")?,
+            account: parse_field_optional(&fix_msg, "1")?,
+            transact_time: parse_field(&fix_msg, "60")?,
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("q")
+            .field("11", &self.cl_ord_id)
+            .field("530", self.mass_cancel_request_type)
+            .optional_field("1", self.account.as_ref())
+            .optional_field("55", self.symbol.as_ref())
+            .optional_field("54", self.side)
+            .field("60", &self.transact_time)
+            .optional_field("58", self.text.as_ref())
+    }
+}
@@ -1,12 +1,14 @@
-use crate::fix::fix_message::parse_field;
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrderStatusRequest {
     pub cl_ord_id: String,        // Tag 11: Client's order ID
     pub symbol: String,           // Tag 55: Ticker symbol
     pub side: char,               // Tag 54: Side of the order
     pub transact_time: String,    // Tag 60: Transaction time
+    pub order_id: Option<String>, // Tag 37: OrderID assigned by the OMS, if known
 }
 
 impl OrderStatusRequest {
@@ -20,6 +22,16 @@ impl OrderStatusRequest {
             symbol: parse_field(&fix_msg, "55")?,
             side: parse_field::<char>(&fix_msg, "54")?,
             transact_time: parse_field(&fix_msg, "60")?,
+            order_id: parse_field_optional(&fix_msg, "37")?,
         })
     }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("H")
+            .optional_field("37", self.order_id.as_ref())
+            .field("11", &self.cl_ord_id)
+            .field("55", &self.symbol)
+            .field("54", self.side)
+            .field("60", &self.transact_time)
+    }
 }
\ No newline at end of file
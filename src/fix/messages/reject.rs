@@ -0,0 +1,37 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct Reject {
+    pub ref_seq_num: u64,                       // Tag 45: MsgSeqNum of the rejected message
+    pub ref_tag_id: Option<u32>,                // Tag 371: Tag number that caused the reject
+    pub ref_msg_type: Option<String>,           // Tag 372: MsgType of the rejected message
+    pub session_reject_reason: Option<u32>,     // Tag 373: Code identifying the reject reason
+    pub text: Option<String>,                   // Tag 58: Human readable reason
+}
+
+impl Reject {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"3".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(Reject {
+            ref_seq_num: parse_field::<u64>(&fix_msg, "45")?,
+            ref_tag_id: parse_field_optional::<u32>(&fix_msg, "371")?,
+            ref_msg_type: parse_field_optional(&fix_msg, "372")?,
+            session_reject_reason: parse_field_optional::<u32>(&fix_msg, "373")?,
+            text: parse_field_optional(&fix_msg, "58")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("3")
+            .field("45", self.ref_seq_num)
+            .optional_field("371", self.ref_tag_id)
+            .optional_field("372", self.ref_msg_type.as_ref())
+            .optional_field("373", self.session_reject_reason)
+            .optional_field("58", self.text.as_ref())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::fix::fix_message::parse_field;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct ResendRequest {
+    pub begin_seq_no: u64,        // Tag 7: First sequence number to resend
+    pub end_seq_no: u64,          // Tag 16: Last sequence number to resend ('0' for infinity)
+}
+
+impl ResendRequest {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"2".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(ResendRequest {
+            begin_seq_no: parse_field::<u64>(&fix_msg, "7")?,
+            end_seq_no: parse_field::<u64>(&fix_msg, "16")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("2")
+            .field("7", self.begin_seq_no)
+            .field("16", self.end_seq_no)
+    }
+}
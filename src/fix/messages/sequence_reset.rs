@@ -0,0 +1,32 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct SequenceReset {
+    pub new_seq_no: u64,                // Tag 36: Next sequence number to be expected
+    pub gap_fill_flag: Option<char>,    // Tag 123: 'Y' for Gap Fill mode, otherwise Reset mode
+}
+
+impl SequenceReset {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"4".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(SequenceReset {
+            new_seq_no: parse_field::<u64>(&fix_msg, "36")?,
+            gap_fill_flag: parse_field_optional::<char>(&fix_msg, "123")?,
+        })
+    }
+
+    pub fn is_gap_fill(&self) -> bool {
+        self.gap_fill_flag == Some('Y')
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("4")
+            .optional_field("123", self.gap_fill_flag)
+            .field("36", self.new_seq_no)
+    }
+}
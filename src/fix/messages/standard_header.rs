@@ -0,0 +1,33 @@
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct StandardHeader {
+    pub begin_string: String,               // Tag 8: FIX protocol version (e.g., "FIX.4.2")
+    pub msg_type: String,                   // Tag 35: Message type
+    pub sender_comp_id: String,             // Tag 49: Firm sending the message
+    pub target_comp_id: String,             // Tag 56: Firm receiving the message
+    pub msg_seq_num: u64,                   // Tag 34: Message sequence number
+    pub sending_time: String,               // Tag 52: Time the message was sent
+    pub poss_dup_flag: Option<char>,        // Tag 43: 'Y' if the message may be a duplicate
+    pub orig_sending_time: Option<String>,  // Tag 122: Original sending time of a resent message
+}
+
+impl StandardHeader {
+    pub fn new(fix_msg: &HashMap<String, String>) -> Result<Self, String> {
+        Ok(StandardHeader {
+            begin_string: parse_field(fix_msg, "8")?,
+            msg_type: parse_field(fix_msg, "35")?,
+            sender_comp_id: parse_field(fix_msg, "49")?,
+            target_comp_id: parse_field(fix_msg, "56")?,
+            msg_seq_num: parse_field::<u64>(fix_msg, "34")?,
+            sending_time: parse_field(fix_msg, "52")?,
+            poss_dup_flag: parse_field_optional::<char>(fix_msg, "43")?,
+            orig_sending_time: parse_field_optional(fix_msg, "122")?,
+        })
+    }
+
+    pub fn is_poss_dup(&self) -> bool {
+        self.poss_dup_flag == Some('Y')
+    }
+}
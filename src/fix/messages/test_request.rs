@@ -0,0 +1,25 @@
+use crate::fix::fix_message::parse_field;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub test_req_id: String,      // Tag 112: Identifier to be echoed back in the Heartbeat
+}
+
+impl TestRequest {
+    pub fn new(fix_msg: HashMap<String, String>) -> Result<Self, String> {
+        if fix_msg.get("35") != Some(&"1".to_string()) {
+            return Err("Invalid message type".to_string());
+        }
+
+        Ok(TestRequest {
+            test_req_id: parse_field(&fix_msg, "112")?,
+        })
+    }
+
+    pub fn to_writer(&self) -> FixMessageWriter {
+        FixMessageWriter::new("1")
+            .field("112", &self.test_req_id)
+    }
+}
@@ -1,4 +1,6 @@
+pub mod data_dictionary;
 pub mod fix_file_reader;
 pub mod fix_message_parser;
+pub mod fix_message_writer;
 pub mod fix_message;
 pub mod messages;
\ No newline at end of file
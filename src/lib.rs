@@ -0,0 +1,5 @@
+pub mod auth;
+pub mod config;
+pub mod fix;
+pub mod oms;
+pub mod session;
@@ -1,90 +1,549 @@
-mod ws_client;
-mod fix;
-mod oms;
-
-use oms::order_management_system::OrderManagementSystem;
-use std::fmt::Debug;
-use crate::fix::fix_message::FixMessage;
-use crate::fix::fix_message_parser::FixMessageParser;
-
-use axum::{routing::{get, post}, Router, extract::ws::{WebSocket, WebSocketUpgrade}, response::IntoResponse};
-use crossbeam::channel::{unbounded, Receiver, Sender};
-use futures_util::{SinkExt, StreamExt};
-use std::net::SocketAddr;
+use fixparser::auth::access_control::{AccessControl, AccessDenied, Caller, Refused};
+use fixparser::oms::audit_trail::{self, AuditEvent};
+use fixparser::oms::gtc_store::GtcStore;
+use fixparser::oms::id_generator::IdStore;
+use fixparser::oms::instruments::{Instrument, InstrumentMaster, TradingStatus};
+use fixparser::oms::journal::Journal;
+use fixparser::oms::oms_dispatcher::{OmsDispatcher, OmsShards};
+use fixparser::oms::oms_request::{OmsRequest, ReplyTo};
+use fixparser::oms::order_management_system::{OrderManagementSystem, MASS_CANCEL_ALL};
+use fixparser::oms::positions::{Position, Positions};
+use fixparser::oms::risk::{RiskChecks, SharedExposure};
+use fixparser::oms::snapshot::SnapshotStore;
+use fixparser::fix::fix_message::FixMessage;
+use fixparser::fix::messages::order_mass_cancel_request::OrderMassCancelRequest;
+use fixparser::config::server_config::{OmsConfig, ServerConfig, DEFAULT_CONFIG_PATH};
+use fixparser::session::cancel_on_disconnect::CancelOnDisconnect;
+use fixparser::session::session_clock::{SystemClock, UTC_TIMESTAMP_FORMAT};
+use fixparser::session::session_manager::SessionManager;
+use fixparser::session::session_schedule::SessionSchedule;
+
+use chrono::Duration;
+use axum::{routing::{get, post}, Router, extract::{Path, Query, ws::{WebSocket, WebSocketUpgrade}}, http::{header, HeaderMap, StatusCode}, response::IntoResponse, Json};
+use futures_util::stream::{self, SelectAll, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
 use axum::extract::ws::Message;
-use utoipa::OpenApi;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
 const FIX_TAG: &str = "fix";
+const POSITIONS_TAG: &str = "positions";
+const INSTRUMENTS_TAG: &str = "instruments";
+const ADMIN_TAG: &str = "admin";
+const AUDIT_TAG: &str = "audit";
+
+/// How long open connections get to finish once shutdown starts.
+const SHUTDOWN_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
 
 #[derive(OpenApi)]
 #[openapi(
     tags(
-            (name = FIX_TAG, description = "Parses FIX protocol messages.")
+            (name = FIX_TAG, description = "Parses FIX protocol messages."),
+            (name = POSITIONS_TAG, description = "Positions and P&L built from the OMS's fills."),
+            (name = INSTRUMENTS_TAG, description = "Reference data orders are checked against."),
+            (name = ADMIN_TAG, description = "Operator controls such as the kill switch."),
+            (name = AUDIT_TAG, description = "Order lifecycle records for compliance.")
     ),
-    paths(post_fix_msg),
+    paths(post_fix_msg, get_positions, reset_positions, get_instruments, get_instrument, kill_account, restore_account, get_killed_accounts, get_audit_trail),
+    components(schemas(Position, Instrument, TradingStatus)),
+    modifiers(&SecurityAddon),
 )]
 struct ApiDoc;
 
+/// Operators present their token as a bearer token; counterparties their Username and
+/// Password through basic authentication.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("operator", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("counterparty", SecurityScheme::Http(Http::new(HttpAuthScheme::Basic)));
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/fix",
     tag = FIX_TAG,
-    request_body(content = String, description = "Raw FIX message"),
+    request_body(content = String, description = "Raw FIX message, carrying SenderCompID (49) and, when password authentication is on, Username (553) and Password (554)"),
     responses(
-        (status = 200, description = "FIX message received")
+        (status = 200, description = "Messages the OMS sent back, e.g. ExecutionReports, one per line"),
+        (status = 401, description = "Counterparty failed authentication; the body is a BusinessMessageReject"),
+        (status = 403, description = "Counterparty is not entitled to send the message; the body is a BusinessMessageReject"),
+        (status = 503, description = "The OMS queue is full; retry later")
     )
 )]
-async fn post_fix_msg(body: String, sender: Sender<FixMessage>) -> String {
-    let parsed_message = FixMessageParser::parse_message(&body);
+async fn post_fix_msg(body: String, sender: OmsDispatcher, access_control: AccessControl) -> (StatusCode, String) {
+    match access_control.authorize(&body) {
+        Ok((message, comp_id)) => request_replies(message, &comp_id, sender).await,
+        Err(refused) => access_denied(refused),
+    }
+}
 
-    if sender.send(parsed_message).is_err() {
-        return "Failed to send message to channel".to_string();
+/// Sends a message from `comp_id` to the OMS and answers with everything it sends back.
+async fn request_replies(message: FixMessage, comp_id: &str, sender: OmsDispatcher) -> (StatusCode, String) {
+    let (reply_sender, reply) = oneshot::channel();
+    match sender.try_send(from_counterparty(message, comp_id, ReplyTo::Request(reply_sender))) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => return (StatusCode::SERVICE_UNAVAILABLE, "OMS queue is full, try again later".to_string()),
+        Err(TrySendError::Closed(_)) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to send message to channel".to_string()),
+    }
+    match reply.await {
+        Ok(replies) if replies.is_empty() => (StatusCode::OK, "FIX message received and sent to channel".to_string()),
+        Ok(replies) => (StatusCode::OK, replies.iter().filter_map(encode).collect::<Vec<_>>().join("\n")),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "OMS dropped the message without replying".to_string()),
     }
-    "FIX message received and sent to channel".to_string()
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, sender: Sender<FixMessage>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| ws_fix_msg(socket, sender))
+/// A request from a counterparty on HTTP or WebSocket, identified by its SenderCompID if it sent
+/// one. Without one, e.g. from an operator, a mass cancel reaches every counterparty's orders.
+fn from_counterparty(message: FixMessage, comp_id: &str, reply_to: ReplyTo) -> OmsRequest {
+    let request = OmsRequest::new(message, reply_to);
+    if comp_id.is_empty() { request } else { request.with_session(comp_id) }
 }
 
-async fn ws_fix_msg(mut socket: WebSocket, sender: Sender<FixMessage>) {
-    while let Some(Ok(message)) = socket.next().await {
-        if let Message::Text(text) = message {
-            let parsed_message = FixMessageParser::parse_message(&text);
+/// Encodes a reply for HTTP or WebSocket, where messages travel without a session header.
+fn encode(message: &FixMessage) -> Option<String> {
+    message.to_writer().map(|writer| writer.encode_body())
+}
 
-            if sender.send(parsed_message).is_err() {
-                eprintln!("Failed to send message to channel");
-            }
+/// Answers a refused message with its BusinessMessageReject, as a FIX session would.
+fn access_denied(refused: Refused) -> (StatusCode, String) {
+    let status = match refused.denied {
+        AccessDenied::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+        AccessDenied::NotEntitled(_) => StatusCode::FORBIDDEN,
+    };
+    (status, refused.reject.to_writer().encode_body())
+}
+
+/// Refuses a request to an endpoint other than the FIX one.
+fn refused(denied: AccessDenied) -> (StatusCode, String) {
+    match denied {
+        AccessDenied::Unauthenticated(text) => (StatusCode::UNAUTHORIZED, text),
+        AccessDenied::NotEntitled(text) => (StatusCode::FORBIDDEN, text),
+    }
+}
+
+fn authorization(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok())
+}
+
+#[derive(Deserialize, IntoParams)]
+struct PositionsQuery {
+    /// Only return positions of this Account (1)
+    account: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/positions",
+    tag = POSITIONS_TAG,
+    params(PositionsQuery),
+    security(("operator" = []), ("counterparty" = [])),
+    responses(
+        (status = 200, description = "Net position, average cost and P&L per account and symbol; a counterparty only sees the accounts it may trade", body = Vec<Position>),
+        (status = 401, description = "No valid operator token or counterparty credentials")
+    )
+)]
+async fn get_positions(Query(query): Query<PositionsQuery>, headers: HeaderMap, shards: Vec<Arc<Mutex<Positions>>>, access_control: AccessControl) -> Result<Json<Vec<Position>>, (StatusCode, String)> {
+    let caller = access_control.identify(authorization(&headers)).map_err(refused)?;
+    let entitlements = access_control.entitlements();
+    let visible = |position: &Position| match &caller {
+        Caller::Operator(_) => true,
+        Caller::Counterparty(comp_id) => entitlements.may_trade_account(comp_id, position.account.as_deref()),
+    };
+    // Each symbol trades on one shard, so no position is split between them
+    let mut positions: Vec<Position> = shards
+        .iter()
+        .flat_map(|positions| positions.lock().unwrap().positions(query.account.as_deref()))
+        .filter(visible)
+        .collect();
+    positions.sort_by(|a, b| (&a.account, &a.symbol).cmp(&(&b.account, &b.symbol)));
+    Ok(Json(positions))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/positions/reset",
+    tag = POSITIONS_TAG,
+    security(("operator" = [])),
+    responses(
+        (status = 200, description = "Realised P&L zeroed and open positions re-based to their marks, as at the start of day"),
+        (status = 401, description = "No valid operator token"),
+        (status = 403, description = "The caller is not an operator")
+    )
+)]
+async fn reset_positions(headers: HeaderMap, shards: Vec<Arc<Mutex<Positions>>>, access_control: AccessControl) -> (StatusCode, String) {
+    let operator = match access_control.authorize_operator(authorization(&headers)) {
+        Ok(operator) => operator,
+        Err(denied) => return refused(denied),
+    };
+    for positions in &shards {
+        positions.lock().unwrap().start_of_day();
+    }
+    println!("Positions reset for the start of day by {}", operator);
+    (StatusCode::OK, "Positions reset for the start of day".to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/instruments",
+    tag = INSTRUMENTS_TAG,
+    responses(
+        (status = 200, description = "Every instrument in the master, by symbol; empty when no master is configured", body = Vec<Instrument>)
+    )
+)]
+async fn get_instruments(instruments: Option<Arc<InstrumentMaster>>) -> Json<Vec<Instrument>> {
+    Json(instruments.iter().flat_map(|instruments| instruments.instruments().cloned()).collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/instruments/{symbol}",
+    tag = INSTRUMENTS_TAG,
+    params(("symbol" = String, Path, description = "Symbol (55) of the instrument")),
+    responses(
+        (status = 200, description = "Reference data of the instrument", body = Instrument),
+        (status = 404, description = "No such instrument")
+    )
+)]
+async fn get_instrument(Path(symbol): Path<String>, instruments: Option<Arc<InstrumentMaster>>) -> Result<Json<Instrument>, (StatusCode, String)> {
+    instruments
+        .as_ref()
+        .and_then(|instruments| instruments.get(&symbol).cloned())
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unknown symbol {}", symbol)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/kill-switch/{account}",
+    tag = ADMIN_TAG,
+    params(("account" = String, Path, description = "Account (1) to stop trading")),
+    security(("operator" = [])),
+    responses(
+        (status = 200, description = "The OrderMassCancelReport for the account's working orders, which are all canceled; new orders are refused until the account is restored"),
+        (status = 401, description = "No valid operator token"),
+        (status = 403, description = "The caller is not an operator"),
+        (status = 503, description = "The OMS queue is full; the account is blocked but its orders may still be working")
+    )
+)]
+async fn kill_account(Path(account): Path<String>, headers: HeaderMap, sender: OmsDispatcher, access_control: AccessControl) -> (StatusCode, String) {
+    let operator = match access_control.authorize_operator(authorization(&headers)) {
+        Ok(operator) => operator,
+        Err(denied) => return refused(denied),
+    };
+    // Block first, so no order slips in behind the mass cancel
+    access_control.entitlements().block_account(&account);
+    println!("Kill switch engaged for account {} by {}", account, operator);
+
+    let now = chrono::Utc::now();
+    let request = OrderMassCancelRequest {
+        cl_ord_id: format!("KILL-{}-{}", account, now.timestamp_millis()),
+        mass_cancel_request_type: MASS_CANCEL_ALL,
+        symbol: None,
+        side: None,
+        account: Some(account.clone()),
+        transact_time: now.format(UTC_TIMESTAMP_FORMAT).to_string(),
+        text: Some("Kill switch".to_string()),
+    };
+    request_replies(FixMessage::OrderMassCancelRequest(request), "", sender).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/kill-switch/{account}",
+    tag = ADMIN_TAG,
+    params(("account" = String, Path, description = "Account (1) to let trade again")),
+    security(("operator" = [])),
+    responses(
+        (status = 200, description = "The account may enter orders again"),
+        (status = 401, description = "No valid operator token"),
+        (status = 403, description = "The caller is not an operator"),
+        (status = 404, description = "The account was not blocked")
+    )
+)]
+async fn restore_account(Path(account): Path<String>, headers: HeaderMap, access_control: AccessControl) -> (StatusCode, String) {
+    let operator = match access_control.authorize_operator(authorization(&headers)) {
+        Ok(operator) => operator,
+        Err(denied) => return refused(denied),
+    };
+    if !access_control.entitlements().unblock_account(&account) {
+        return (StatusCode::NOT_FOUND, format!("Account {} is not blocked", account));
+    }
+    println!("Kill switch released for account {} by {}", account, operator);
+    (StatusCode::OK, format!("Account {} may trade again", account))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/kill-switch",
+    tag = ADMIN_TAG,
+    security(("operator" = [])),
+    responses(
+        (status = 200, description = "Accounts blocked by the kill switch. The list is held in memory and empties on restart", body = Vec<String>),
+        (status = 401, description = "No valid operator token"),
+        (status = 403, description = "The caller is not an operator")
+    )
+)]
+async fn get_killed_accounts(headers: HeaderMap, access_control: AccessControl) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    access_control.authorize_operator(authorization(&headers)).map_err(refused)?;
+    Ok(Json(access_control.entitlements().blocked_accounts()))
+}
+
+#[derive(Deserialize)]
+struct WsQuery {
+    /// Seconds after the socket closes before the orders entered over it are canceled. Unset,
+    /// orders outlive the socket.
+    cancel_on_disconnect_secs: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct AuditQuery {
+    /// `csv` or `jsonl`; CSV by default
+    format: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = AUDIT_TAG,
+    params(AuditQuery),
+    security(("operator" = [])),
+    responses(
+        (status = 200, description = "Every order event in the journal, from receipt through acceptance, routing, fills and restatements to cancellation or expiry, oldest first. Events link the ClOrdIDs a cancel replaced and the mass cancel behind a cancel"),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "No valid operator token"),
+        (status = 403, description = "The caller is not an operator"),
+        (status = 404, description = "No journal is configured to read the trail from"),
+        (status = 500, description = "A journal could not be read")
+    )
+)]
+async fn get_audit_trail(Query(query): Query<AuditQuery>, headers: HeaderMap, config: Arc<OmsConfig>, access_control: AccessControl) -> impl IntoResponse {
+    if let Err(denied) = access_control.authorize_operator(authorization(&headers)) {
+        let (status, text) = refused(denied);
+        return (status, [(header::CONTENT_TYPE, "text/plain")], text);
+    }
+    let (content_type, encode): (_, fn(&[AuditEvent]) -> String) = match query.format.as_deref() {
+        None | Some("csv") => ("text/csv", audit_trail::to_csv),
+        Some("jsonl") => ("application/x-ndjson", audit_trail::to_jsonl),
+        Some(format) => return (StatusCode::BAD_REQUEST, [(header::CONTENT_TYPE, "text/plain")], format!("Unknown format {}, expected csv or jsonl", format)),
+    };
+    if config.journal_path.is_none() {
+        return (StatusCode::NOT_FOUND, [(header::CONTENT_TYPE, "text/plain")], "No journal is configured".to_string());
+    }
+    // Reading whole journals blocks, so it stays off the server's workers
+    match tokio::task::spawn_blocking(move || read_audit_trail(&config)).await {
+        Ok(Ok(events)) => (StatusCode::OK, [(header::CONTENT_TYPE, content_type)], encode(&events)),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, [(header::CONTENT_TYPE, "text/plain")], e),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, [(header::CONTENT_TYPE, "text/plain")], format!("Failed to read the audit trail: {}", e)),
+    }
+}
+
+/// The audit trail of every shard's journal. Each shard journals its own symbols; their trails
+/// interleave by time.
+fn read_audit_trail(config: &OmsConfig) -> Result<Vec<AuditEvent>, String> {
+    let mut events: Vec<AuditEvent> = Vec::new();
+    if let Some(path) = &config.journal_path {
+        for shard in 0..config.shards {
+            let path = config.shard_path(path, shard);
+            let entries = Journal::read(&path).map_err(|e| format!("Failed to read journal {}: {}", path.display(), e))?;
+            events.extend(audit_trail::audit_trail(entries));
+        }
+    }
+    events.sort_by_key(|event| event.event_time);
+    Ok(events)
+}
+
+async fn ws_handler(ws: WebSocketUpgrade, Query(query): Query<WsQuery>, sender: OmsDispatcher, access_control: AccessControl, cancel_on_disconnect: CancelOnDisconnect) -> impl IntoResponse {
+    let grace = query.cancel_on_disconnect_secs.map(std::time::Duration::from_secs);
+    ws.on_upgrade(move |socket| async move {
+        let connection = cancel_on_disconnect.next_socket();
+        cancel_on_disconnect.connected(&connection);
+        ws_fix_msg(socket, &sender, access_control, &cancel_on_disconnect, &connection).await;
+        cancel_on_disconnect.disconnected(&connection, grace, &sender);
+    })
+}
 
-            if socket.send(Message::Text("FIX message received and sent to channel".to_string())).await.is_err() {
-                break;
+/// Forwards each text frame to the OMS and streams whatever the OMS sends back for this
+/// socket's messages, until the socket closes. Each message names its own SenderCompID, so the
+/// socket may carry several counterparties, or none; its orders are tracked as `connection`'s.
+/// Each counterparty's replies come back on a channel of their own, so a report is known to be
+/// about that counterparty's order even where another one reuses its ClOrdID.
+async fn ws_fix_msg(mut socket: WebSocket, sender: &OmsDispatcher, access_control: AccessControl, cancel_on_disconnect: &CancelOnDisconnect, connection: &str) {
+    let mut reply_senders: HashMap<String, mpsc::UnboundedSender<FixMessage>> = HashMap::new();
+    let mut replies = SelectAll::new();
+    loop {
+        tokio::select! {
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(_)) => continue,
+                    _ => break,
+                };
+                match access_control.authorize(&text) {
+                    Ok((parsed_message, comp_id)) => {
+                        let session = Some(comp_id.clone()).filter(|comp_id| !comp_id.is_empty());
+                        cancel_on_disconnect.entered(connection, session.as_deref(), &parsed_message);
+                        let reply_sender = reply_senders.entry(comp_id.clone()).or_insert_with(|| {
+                            let (reply_sender, receiver) = mpsc::unbounded_channel();
+                            replies.push(stream::unfold((receiver, session), |(mut receiver, session)| async move {
+                                let reply = receiver.recv().await?;
+                                Some(((session.clone(), reply), (receiver, session)))
+                            }).boxed());
+                            reply_sender
+                        });
+                        // Waiting for room in the queue stops reading the socket, pushing back on the client
+                        let request = from_counterparty(parsed_message, &comp_id, ReplyTo::Connection(reply_sender.clone()));
+                        if sender.send(request).await.is_err() {
+                            eprintln!("Failed to send message to channel");
+                        }
+                    }
+                    Err(refused) => {
+                        if socket.send(Message::Text(access_denied(refused).1)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            Some((session, reply)) = replies.next() => {
+                cancel_on_disconnect.reported(connection, session.as_deref(), &reply);
+                if let Some(encoded) = encode(&reply) {
+                    if socket.send(Message::Text(encoded)).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
     }
 }
 
+/// Builds OMS shard `shard`, restoring it from its own stores. Its risk checks count each
+/// account's orders on every shard through `exposure`.
+fn build_oms(config: &ServerConfig, shard: usize, instruments: Option<&Arc<InstrumentMaster>>, exposure: &SharedExposure) -> Result<OrderManagementSystem, String> {
+    let risk = RiskChecks::new(&config.risk).with_shared_exposure(exposure.clone(), shard);
+    let mut oms = OrderManagementSystem::new().with_risk_checks(risk.clone());
+    let ids = &config.oms.ids;
+    match config.oms.shards {
+        1 => oms = oms.with_id_format(&ids.prefix, ids.width),
+        _ => oms = oms.with_id_format(&format!("{}S{}-", ids.prefix, shard), ids.width),
+    }
+    if let Some(path) = &ids.store_path {
+        oms = oms.with_id_store(IdStore::new(config.oms.shard_path(path, shard)))?;
+    }
+    if let Some(instruments) = instruments {
+        oms = oms.with_instruments(instruments.clone());
+    }
+    if let Some(prevention) = config.oms.self_trade_prevention {
+        oms = oms.with_self_trade_prevention(prevention);
+    }
+    if let Some(schedule) = &config.oms.schedule {
+        oms = oms.with_schedule(SessionSchedule::new(schedule)?);
+    }
+    if let Some(path) = &config.oms.gtc_store_path {
+        oms = oms.with_gtc_store(GtcStore::new(config.oms.shard_path(path, shard)))?;
+    }
+    if let Some(path) = &config.oms.snapshot_path {
+        let store = SnapshotStore::new(config.oms.shard_path(path, shard));
+        oms = oms.with_snapshots(store, Duration::seconds(config.oms.snapshot_interval_secs as i64));
+    }
+    if let Some(path) = &config.oms.journal_path {
+        let path = config.oms.shard_path(path, shard);
+        let journal = Journal::open(&path).map_err(|e| format!("Failed to open journal {}: {}", path.display(), e))?;
+        oms = oms.with_journal(journal)?;
+    }
+    risk.publish_working(&oms);
+    Ok(oms)
+}
+
 #[tokio::main]
 async fn main() {
-    let (sender, receiver): (Sender<FixMessage>, Receiver<FixMessage>) = unbounded();
+    let config_path = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let config = ServerConfig::load(&config_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
-    let oms = OrderManagementSystem::new(receiver);
-    tokio::spawn(async move {
-        oms.listen_for_orders();
+    let instruments = config.oms.instruments_path.as_ref().map(|path| {
+        let instruments = InstrumentMaster::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        println!("Loaded {} instruments", instruments.len());
+        Arc::new(instruments)
+    });
+    let exposure = SharedExposure::new();
+    let systems = (0..config.oms.shards).map(|shard| build_oms(&config, shard, instruments.as_ref(), &exposure)).collect::<Result<Vec<_>, _>>().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let positions: Vec<_> = systems.iter().map(OrderManagementSystem::positions).collect();
+    let (oms_shards, sender) = OmsShards::start(systems, config.oms.queue_capacity).unwrap_or_else(|e| {
+        eprintln!("Failed to start the OMS: {}", e);
+        std::process::exit(1);
+    });
+
+    let access_control = AccessControl::from_config(&config);
+    let session_manager = SessionManager::new(&config.sessions, &access_control, Arc::new(SystemClock)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
     });
+    session_manager.start(sender.clone());
+    let cancel_on_disconnect = session_manager.cancel_on_disconnect();
 
     let post_sender = sender.clone();
     let ws_sender = sender.clone();
+    let post_access_control = access_control.clone();
+    let ws_access_control = access_control.clone();
+    let kill_sender = sender.clone();
+    let kill_access_control = access_control.clone();
+    let restore_access_control = access_control.clone();
+    let killed_access_control = access_control.clone();
+    let reset_positions_handle = positions.clone();
+    let positions_access_control = access_control.clone();
+    let reset_access_control = access_control.clone();
+    let instrument_handle = instruments.clone();
+    let audit_config = Arc::new(config.oms.clone());
+    let audit_access_control = access_control.clone();
     let app = Router::new()
-        .route("/api/v1/fix", post(move |body| post_fix_msg(body, post_sender)))
-        .route("/ws", get(move |ws| ws_handler(ws, ws_sender)))
+        .route("/api/v1/fix", post(move |body| post_fix_msg(body, post_sender, post_access_control)))
+        .route("/api/v1/positions", get(move |query, headers| get_positions(query, headers, positions, positions_access_control)))
+        .route("/api/v1/positions/reset", post(move |headers| reset_positions(headers, reset_positions_handle, reset_access_control)))
+        .route("/api/v1/instruments", get(move || get_instruments(instruments)))
+        .route("/api/v1/audit", get(move |query, headers| get_audit_trail(query, headers, audit_config, audit_access_control)))
+        .route("/api/v1/instruments/:symbol", get(move |symbol| get_instrument(symbol, instrument_handle)))
+        .route("/api/v1/admin/kill-switch", get(move |headers| get_killed_accounts(headers, killed_access_control)))
+        .route("/api/v1/admin/kill-switch/:account", post(move |account, headers| kill_account(account, headers, kill_sender, kill_access_control))
+            .delete(move |account, headers| restore_account(account, headers, restore_access_control)))
+        .route("/ws", get(move |ws, query| ws_handler(ws, query, ws_sender, ws_access_control, cancel_on_disconnect)))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8081));
+    let addr = config.server.http_address;
     println!("listening on {}", addr);
 
+    let server_handle = axum_server::Handle::new();
+    let shutdown_handle = server_handle.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("Shutting down");
+            shutdown_handle.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+        }
+    });
+
     axum_server::Server::bind(addr)
+        .handle(server_handle)
         .serve(app.into_make_service())
         .await
         .unwrap();
+
+    // The OMS answers whatever is still queued before it stops
+    if tokio::task::spawn_blocking(move || oms_shards.stop()).await.is_err() {
+        eprintln!("Failed to wait for the OMS to stop");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use crate::oms::order::{BUY, QTY_EPSILON};
+use std::cmp::Ordering;
+
+/// An order taking part in an opening or closing auction. Market orders carry no limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuctionOrder {
+    pub order_id: String,
+    pub side: char,
+    pub limit: Option<f64>,
+    pub qty: f64,
+}
+
+/// Outcome of an auction: every execution happens at the single uncrossing price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuctionResult {
+    pub price: f64,
+    /// Quantity executed per order, in allocation order.
+    pub executions: Vec<(String, f64)>,
+}
+
+/// Finds the price at which the most quantity trades, breaking ties by the smallest imbalance,
+/// then by closeness to `reference_price`, then by the lower price. That quantity goes to the
+/// eligible orders on each side: market orders first, then by limit price, then by arrival.
+/// `orders` must be in arrival order. An auction of market orders only needs a reference price.
+pub fn uncross(orders: &[AuctionOrder], reference_price: Option<f64>) -> Option<AuctionResult> {
+    let mut candidates: Vec<f64> = orders.iter().filter_map(|order| order.limit).collect();
+    if candidates.is_empty() {
+        candidates.extend(reference_price);
+    }
+
+    let volumes = |price: f64| {
+        let eligible = |side_is_buy: bool| orders
+            .iter()
+            .filter(|order| (order.side == BUY) == side_is_buy && is_eligible(order, price))
+            .map(|order| order.qty)
+            .sum::<f64>();
+        (eligible(true), eligible(false))
+    };
+    let distance = |price: f64| reference_price.map_or(0.0, |reference| (price - reference).abs());
+
+    let (price, executable) = candidates
+        .into_iter()
+        .map(|price| {
+            let (buy, sell) = volumes(price);
+            (price, buy.min(sell), (buy - sell).abs())
+        })
+        .max_by(|a, b| {
+            a.1.total_cmp(&b.1)
+                .then(b.2.total_cmp(&a.2))
+                .then(distance(b.0).total_cmp(&distance(a.0)))
+                .then(b.0.total_cmp(&a.0))
+        })
+        .map(|(price, executable, _)| (price, executable))?;
+    if executable <= QTY_EPSILON {
+        return None;
+    }
+
+    let mut executions = allocate(orders, true, price, executable);
+    executions.extend(allocate(orders, false, price, executable));
+    Some(AuctionResult { price, executions })
+}
+
+fn is_eligible(order: &AuctionOrder, price: f64) -> bool {
+    match order.limit {
+        None => true,
+        Some(limit) if order.side == BUY => limit >= price,
+        Some(limit) => limit <= price,
+    }
+}
+
+fn allocate(orders: &[AuctionOrder], buys: bool, price: f64, executable: f64) -> Vec<(String, f64)> {
+    let mut eligible: Vec<&AuctionOrder> = orders
+        .iter()
+        .filter(|order| (order.side == BUY) == buys && is_eligible(order, price))
+        .collect();
+    // Stable sort, so arrival order decides between equal limits
+    eligible.sort_by(|a, b| match (a.limit, b.limit) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(a), Some(b)) if buys => b.total_cmp(&a),
+        (Some(a), Some(b)) => a.total_cmp(&b),
+    });
+
+    let mut remaining = executable;
+    let mut executions = Vec::new();
+    for order in eligible {
+        if remaining <= QTY_EPSILON {
+            break;
+        }
+        let qty = order.qty.min(remaining);
+        remaining -= qty;
+        executions.push((order.order_id.clone(), qty));
+    }
+    executions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oms::order::SELL;
+
+    fn order(order_id: &str, side: char, limit: Option<f64>, qty: f64) -> AuctionOrder {
+        AuctionOrder { order_id: order_id.to_string(), side, limit, qty }
+    }
+
+    #[test]
+    fn test_uncross_maximises_volume_at_one_price() {
+        let orders = vec![
+            order("B1", BUY, Some(101.0), 100.0),
+            order("B2", BUY, Some(100.0), 50.0),
+            order("S1", SELL, Some(99.0), 80.0),
+            order("S2", SELL, Some(100.0), 100.0),
+        ];
+
+        let result = uncross(&orders, None).unwrap();
+
+        assert_eq!(result.price, 100.0);
+        let executed: f64 = result.executions.iter().map(|(_, qty)| qty).sum();
+        assert_eq!(executed, 300.0);
+        assert_eq!(result.executions[..2], [("B1".to_string(), 100.0), ("B2".to_string(), 50.0)]);
+        assert_eq!(result.executions[2..], [("S1".to_string(), 80.0), ("S2".to_string(), 70.0)]);
+    }
+
+    #[test]
+    fn test_market_orders_go_first_and_need_a_reference_price() {
+        let orders = vec![
+            order("B1", BUY, Some(100.0), 30.0),
+            order("B2", BUY, None, 30.0),
+            order("S1", SELL, None, 40.0),
+        ];
+
+        let result = uncross(&orders, Some(99.0)).unwrap();
+        assert_eq!(result.price, 100.0);
+        assert_eq!(result.executions, vec![("B2".to_string(), 30.0), ("B1".to_string(), 10.0), ("S1".to_string(), 40.0)]);
+
+        let market_only = [order("B1", BUY, None, 10.0), order("S1", SELL, None, 10.0)];
+        assert!(uncross(&market_only, None).is_none());
+        assert_eq!(uncross(&market_only, Some(42.0)).unwrap().price, 42.0);
+        assert!(uncross(&orders[..2], Some(99.0)).is_none());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::fix::fix_message::FixMessage;
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::oms::journal::{JournalEntry, JournalEvent};
+use crate::oms::order::{AT_THE_CLOSE, AT_THE_OPENING, STOP, STOP_LIMIT};
+use crate::oms::order_management_system::{
+    EXEC_TYPE_CANCELED, EXEC_TYPE_EXPIRED, EXEC_TYPE_NEW, EXEC_TYPE_REJECTED, EXEC_TYPE_RESTATED, EXEC_TYPE_TRADE, EXEC_TYPE_TRIGGERED, NO_ORDER_ID,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Columns of the CSV export, in the order `AuditEvent::csv_row` writes them.
+const CSV_HEADER: &str = "sequence,event_time,event_type,order_id,cl_ord_id,orig_cl_ord_id,replaced_by_cl_ord_id,parent_cl_ord_id,submitter,destination,account,symbol,side,order_qty,price,ord_status,exec_id,last_qty,last_px,cum_qty,leaves_qty,text";
+
+/// Where the OMS routes an order it accepts or triggers: it matches orders itself, so an order
+/// goes to its symbol's book, waits for its auction or waits with the stops for its trigger.
+pub const BOOK: &str = "BOOK";
+pub const OPENING_AUCTION: &str = "OPENING_AUCTION";
+pub const CLOSING_AUCTION: &str = "CLOSING_AUCTION";
+pub const STOPS: &str = "STOPS";
+
+/// A step in an order's lifecycle, after the order events of CAT and the record keeping of
+/// MiFID II.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditEventType {
+    OrderReceived,
+    OrderAccepted,
+    /// The order went to its destination within the OMS, on acceptance or when it triggered.
+    OrderRouted,
+    OrderRejected,
+    OrderTriggered,
+    Fill,
+    /// The order's quantity was reduced, e.g. by self-trade prevention.
+    OrderModified,
+    CancelRequested,
+    CancelRejected,
+    MassCancelRequested,
+    OrderCanceled,
+    OrderExpired,
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", name.as_str().unwrap_or_default())
+    }
+}
+
+/// One record of the audit trail. OrderID ties every event of an order together. A cancel gives
+/// the order the cancel's ClOrdID, and the two are linked both ways: the new ClOrdID back
+/// through OrigClOrdID, the original forwards through the ClOrdID that replaced it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEvent {
+    /// Journal offset the event was recorded at, never decreasing within a journal. A route
+    /// shares the offset of the report it follows.
+    pub sequence: u64,
+    /// When the OMS received the message or produced the report, to the nanosecond.
+    pub event_time: DateTime<Utc>,
+    pub event_type: AuditEventType,
+    pub order_id: Option<String>,
+    pub cl_ord_id: String,
+    pub orig_cl_ord_id: Option<String>,
+    /// ClOrdID that took over from this event's ClOrdID through a later cancel.
+    pub replaced_by_cl_ord_id: Option<String>,
+    /// ClOrdID of the request that caused this event on the order, e.g. the mass cancel that
+    /// canceled it.
+    pub parent_cl_ord_id: Option<String>,
+    /// CompID of the counterparty that sent the order or request.
+    pub submitter: Option<String>,
+    /// Where a route event sent the order.
+    pub destination: Option<&'static str>,
+    pub account: Option<String>,
+    pub symbol: Option<String>,
+    pub side: Option<char>,
+    pub order_qty: Option<f64>,
+    pub price: Option<f64>,
+    pub ord_status: Option<char>,
+    pub exec_id: Option<String>,
+    pub last_qty: Option<f64>,
+    pub last_px: Option<f64>,
+    pub cum_qty: Option<f64>,
+    pub leaves_qty: Option<f64>,
+    pub text: Option<String>,
+}
+
+impl AuditEvent {
+    fn new(entry: &JournalEntry, event_type: AuditEventType, cl_ord_id: &str) -> Self {
+        AuditEvent {
+            sequence: entry.offset,
+            event_time: entry.time,
+            event_type,
+            order_id: None,
+            cl_ord_id: cl_ord_id.to_string(),
+            orig_cl_ord_id: None,
+            replaced_by_cl_ord_id: None,
+            parent_cl_ord_id: None,
+            submitter: None,
+            destination: None,
+            account: None,
+            symbol: None,
+            side: None,
+            order_qty: None,
+            price: None,
+            ord_status: None,
+            exec_id: None,
+            last_qty: None,
+            last_px: None,
+            cum_qty: None,
+            leaves_qty: None,
+            text: None,
+        }
+    }
+
+    fn csv_row(&self) -> String {
+        let text = |value: &Option<String>| value.as_deref().map(csv_field).unwrap_or_default();
+        let number = |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let flag = |value: Option<char>| value.map(String::from).unwrap_or_default();
+        [
+            self.sequence.to_string(),
+            self.event_time.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            self.event_type.to_string(),
+            text(&self.order_id),
+            csv_field(&self.cl_ord_id),
+            text(&self.orig_cl_ord_id),
+            text(&self.replaced_by_cl_ord_id),
+            text(&self.parent_cl_ord_id),
+            text(&self.submitter),
+            self.destination.unwrap_or_default().to_string(),
+            text(&self.account),
+            text(&self.symbol),
+            flag(self.side),
+            number(self.order_qty),
+            number(self.price),
+            flag(self.ord_status),
+            text(&self.exec_id),
+            number(self.last_qty),
+            number(self.last_px),
+            number(self.cum_qty),
+            number(self.leaves_qty),
+            text(&self.text),
+        ]
+        .join(",")
+    }
+}
+
+/// The inbound message the reports that follow it in the journal were made for, until the next
+/// inbound message or timer tick.
+#[derive(Default)]
+struct Cause {
+    submitter: Option<String>,
+    /// ClOrdID of a mass cancel, the parent of the cancels it makes.
+    mass_cancel: Option<String>,
+    /// TimeInForce (59) of a new order, which decides where its acceptance routes it.
+    time_in_force: Option<char>,
+}
+
+/// Reads an order audit trail out of journal entries: every order and cancel request the OMS
+/// received, and every report on an order's life it sent, at the time it happened.
+pub fn audit_trail(entries: impl IntoIterator<Item = JournalEntry>) -> Vec<AuditEvent> {
+    // Reports carry no SenderCompID. An order's first report answers the message that entered
+    // it, and later ones find the submitter by OrderID, as ClOrdIDs are only unique per submitter
+    let mut submitters: HashMap<String, Option<String>> = HashMap::new();
+    let mut cause = Cause::default();
+    let mut events = Vec::new();
+    for entry in entries {
+        match &entry.event {
+            JournalEvent::Inbound(message) => {
+                let fields = FixMessageParser::parse_fields(message);
+                let submitter = fields.get("49").cloned();
+                let message = FixMessageParser::parse_fields_into_message(fields);
+                cause = Cause {
+                    submitter: submitter.clone(),
+                    mass_cancel: match &message {
+                        FixMessage::OrderMassCancelRequest(request) => Some(request.cl_ord_id.clone()),
+                        _ => None,
+                    },
+                    time_in_force: match &message {
+                        FixMessage::NewOrder(order) => order.time_in_force,
+                        _ => None,
+                    },
+                };
+                events.extend(received(&entry, &message).map(|event| AuditEvent { submitter, ..event }));
+            }
+            JournalEvent::Outbound(message) => {
+                let message = FixMessageParser::parse_fields_into_message(FixMessageParser::parse_fields(message));
+                for event in sent(&entry, &message, &cause) {
+                    let submitter = match event.order_id.as_deref().filter(|order_id| *order_id != NO_ORDER_ID) {
+                        Some(order_id) => submitters.entry(order_id.to_string()).or_insert_with(|| cause.submitter.clone()).clone(),
+                        None => cause.submitter.clone(),
+                    };
+                    events.push(AuditEvent { submitter, ..event });
+                }
+            }
+            JournalEvent::Timer | JournalEvent::Ids(_) => cause = Cause::default(),
+        }
+    }
+    link_replacements(&mut events);
+    events
+}
+
+/// Points every event of a ClOrdID at the ClOrdID a cancel replaced it with, so an order can
+/// be followed forwards as well as back through OrigClOrdID.
+fn link_replacements(events: &mut [AuditEvent]) {
+    let replacements: HashMap<(Option<String>, String), String> = events
+        .iter()
+        .filter(|event| event.event_type == AuditEventType::OrderCanceled)
+        .filter_map(|event| {
+            let orig_cl_ord_id = event.orig_cl_ord_id.clone().filter(|orig_cl_ord_id| *orig_cl_ord_id != event.cl_ord_id)?;
+            Some(((event.submitter.clone(), orig_cl_ord_id), event.cl_ord_id.clone()))
+        })
+        .collect();
+    for event in events {
+        event.replaced_by_cl_ord_id = replacements.get(&(event.submitter.clone(), event.cl_ord_id.clone())).cloned();
+    }
+}
+
+fn received(entry: &JournalEntry, message: &FixMessage) -> Option<AuditEvent> {
+    match message {
+        FixMessage::NewOrder(order) => Some(AuditEvent {
+            account: order.account.clone(),
+            symbol: Some(order.symbol.clone()),
+            side: Some(order.side),
+            order_qty: Some(order.order_qty),
+            price: order.price,
+            ..AuditEvent::new(entry, AuditEventType::OrderReceived, &order.cl_ord_id)
+        }),
+        FixMessage::OrderCancelRequest(request) => Some(AuditEvent {
+            orig_cl_ord_id: Some(request.orig_cl_ord_id.clone()),
+            account: request.account.clone(),
+            symbol: Some(request.symbol.clone()),
+            side: Some(request.side),
+            ..AuditEvent::new(entry, AuditEventType::CancelRequested, &request.cl_ord_id)
+        }),
+        FixMessage::OrderMassCancelRequest(request) => Some(AuditEvent {
+            account: request.account.clone(),
+            symbol: request.symbol.clone(),
+            side: request.side,
+            text: request.text.clone(),
+            ..AuditEvent::new(entry, AuditEventType::MassCancelRequested, &request.cl_ord_id)
+        }),
+        _ => None,
+    }
+}
+
+/// The events a report records: a step in the order's life, followed by where it went if it
+/// was accepted or triggered.
+fn sent(entry: &JournalEntry, message: &FixMessage, cause: &Cause) -> Vec<AuditEvent> {
+    match message {
+        FixMessage::ExecutionReport(report) => {
+            let event_type = match report.exec_type {
+                EXEC_TYPE_NEW => AuditEventType::OrderAccepted,
+                EXEC_TYPE_REJECTED => AuditEventType::OrderRejected,
+                EXEC_TYPE_TRIGGERED => AuditEventType::OrderTriggered,
+                EXEC_TYPE_TRADE => AuditEventType::Fill,
+                EXEC_TYPE_RESTATED => AuditEventType::OrderModified,
+                EXEC_TYPE_CANCELED => AuditEventType::OrderCanceled,
+                EXEC_TYPE_EXPIRED => AuditEventType::OrderExpired,
+                // Status reports change nothing
+                _ => return Vec::new(),
+            };
+            let parent_cl_ord_id = cause.mass_cancel.clone().filter(|_| event_type == AuditEventType::OrderCanceled);
+            let event = AuditEvent {
+                order_id: Some(report.order_id.clone()),
+                orig_cl_ord_id: report.orig_cl_ord_id.clone(),
+                account: report.account.clone(),
+                symbol: Some(report.symbol.clone()),
+                side: Some(report.side),
+                order_qty: Some(report.order_qty),
+                price: report.price,
+                ord_status: Some(report.ord_status),
+                exec_id: Some(report.exec_id.clone()),
+                last_qty: report.last_qty,
+                last_px: report.last_px,
+                cum_qty: Some(report.cum_qty),
+                leaves_qty: Some(report.leaves_qty),
+                text: report.text.clone(),
+                parent_cl_ord_id,
+                ..AuditEvent::new(entry, event_type, &report.cl_ord_id)
+            };
+            let destination = match (event_type, report.ord_type, cause.time_in_force) {
+                (AuditEventType::OrderAccepted, Some(STOP | STOP_LIMIT), _) => Some(STOPS),
+                (AuditEventType::OrderAccepted, _, Some(AT_THE_OPENING)) => Some(OPENING_AUCTION),
+                (AuditEventType::OrderAccepted, _, Some(AT_THE_CLOSE)) => Some(CLOSING_AUCTION),
+                (AuditEventType::OrderAccepted | AuditEventType::OrderTriggered, _, _) => Some(BOOK),
+                _ => None,
+            };
+            let route = destination.map(|destination| AuditEvent {
+                event_type: AuditEventType::OrderRouted,
+                destination: Some(destination),
+                exec_id: None,
+                text: None,
+                ..event.clone()
+            });
+            [event].into_iter().chain(route).collect()
+        }
+        FixMessage::OrderCancelReject(reject) => vec![AuditEvent {
+            order_id: Some(reject.order_id.clone()),
+            orig_cl_ord_id: Some(reject.orig_cl_ord_id.clone()),
+            ord_status: Some(reject.ord_status),
+            text: reject.text.clone(),
+            ..AuditEvent::new(entry, AuditEventType::CancelRejected, &reject.cl_ord_id)
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// The trail as CSV with a header line, one event per line.
+pub fn to_csv(events: &[AuditEvent]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for event in events {
+        csv.push_str(&event.csv_row());
+        csv.push('\n');
+    }
+    csv
+}
+
+/// The trail as JSON Lines, one event object per line.
+pub fn to_jsonl(events: &[AuditEvent]) -> String {
+    events
+        .iter()
+        .filter_map(|event| serde_json::to_string(event).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Quotes a CSV field if it holds a comma, quote or line break, doubling any quotes.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oms::journal::Journal;
+    use crate::oms::order_management_system::OrderManagementSystem;
+
+    #[test]
+    fn test_trail_follows_orders_from_receipt_to_fill_and_cancel() {
+        let path = std::env::temp_dir().join(format!("fix_audit_{}", std::process::id())).join("journal");
+        let _ = std::fs::remove_file(&path);
+        let now = Utc::now();
+        let mut oms = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        let message = |fields: &str| FixMessageParser::parse_message(&format!("{}60=20230101-12:00:00|55=XYZ|", fields));
+        oms.handle_from(message("35=D|11=S1|54=2|38=100|40=2|44=50|1=ACC1|"), Some("CLIENT1"), now);
+        oms.handle_from(message("35=D|11=B1|54=1|38=40|40=2|44=50|"), Some("CLIENT2"), now);
+        oms.handle_from(message("35=F|11=S1-CXL|41=S1|54=2|"), Some("CLIENT1"), now);
+
+        let events = audit_trail(Journal::read(&path).unwrap());
+        let steps: Vec<_> = events.iter().map(|event| (event.event_type, event.cl_ord_id.as_str(), event.submitter.as_deref())).collect();
+        assert_eq!(steps, vec![
+            (AuditEventType::OrderReceived, "S1", Some("CLIENT1")),
+            (AuditEventType::OrderAccepted, "S1", Some("CLIENT1")),
+            (AuditEventType::OrderRouted, "S1", Some("CLIENT1")),
+            (AuditEventType::OrderReceived, "B1", Some("CLIENT2")),
+            (AuditEventType::OrderAccepted, "B1", Some("CLIENT2")),
+            (AuditEventType::OrderRouted, "B1", Some("CLIENT2")),
+            (AuditEventType::Fill, "B1", Some("CLIENT2")),
+            (AuditEventType::Fill, "S1", Some("CLIENT1")),
+            (AuditEventType::CancelRequested, "S1-CXL", Some("CLIENT1")),
+            (AuditEventType::OrderCanceled, "S1-CXL", Some("CLIENT1")),
+        ]);
+        let canceled = events.last().unwrap();
+        assert_eq!((canceled.orig_cl_ord_id.as_deref(), canceled.order_id.as_deref(), canceled.cum_qty), (Some("S1"), events[1].order_id.as_deref(), Some(40.0)));
+        assert_eq!(events[2].destination, Some(BOOK));
+        // Every event of S1 leads on to the ClOrdID that replaced it
+        let replaced_by: Vec<_> = events.iter().filter(|event| event.cl_ord_id == "S1").map(|event| event.replaced_by_cl_ord_id.as_deref()).collect();
+        assert_eq!(replaced_by, vec![Some("S1-CXL"); 4]);
+
+        let csv = to_csv(&events);
+        assert_eq!(csv.lines().next(), Some(CSV_HEADER));
+        assert!(csv.lines().nth(1).unwrap().starts_with(&format!("1,{},ORDER_RECEIVED,,S1,,S1-CXL,,CLIENT1,,ACC1,XYZ,2,100,50,", now.to_rfc3339_opts(SecondsFormat::Nanos, true))));
+        let jsonl = to_jsonl(&events);
+        let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!((first["event_type"].as_str(), first["side"].as_str()), (Some("ORDER_RECEIVED"), Some("2")));
+        assert_eq!(jsonl.lines().count(), events.len());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_trail_links_routes_mass_cancels_and_counterparties_sharing_a_cl_ord_id() {
+        let path = std::env::temp_dir().join(format!("fix_audit_links_{}", std::process::id())).join("journal");
+        let _ = std::fs::remove_file(&path);
+        let now = Utc::now();
+        let mut oms = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        let message = |fields: &str| FixMessageParser::parse_message(&format!("{}60=20230101-12:00:00|55=XYZ|", fields));
+        oms.handle_from(message("35=D|11=C1|54=1|38=10|40=2|44=50|"), Some("CLIENT1"), now);
+        oms.handle_from(message("35=D|11=C1|54=1|38=10|40=2|44=50|59=7|"), Some("CLIENT2"), now);
+        oms.handle_from(message("35=D|11=C2|54=1|38=10|40=3|99=60|"), Some("CLIENT2"), now);
+        oms.handle_from(message("35=q|11=M1|530=7|"), Some("CLIENT2"), now);
+
+        let events = audit_trail(Journal::read(&path).unwrap());
+        let routes: Vec<_> = events
+            .iter()
+            .filter(|event| event.event_type == AuditEventType::OrderRouted)
+            .map(|event| (event.cl_ord_id.as_str(), event.submitter.as_deref(), event.destination))
+            .collect();
+        assert_eq!(routes, vec![("C1", Some("CLIENT1"), Some(BOOK)), ("C1", Some("CLIENT2"), Some(CLOSING_AUCTION)), ("C2", Some("CLIENT2"), Some(STOPS))]);
+
+        // The mass cancel is the parent of CLIENT2's cancels and leaves CLIENT1's order alone
+        let canceled: Vec<_> = events
+            .iter()
+            .filter(|event| event.event_type == AuditEventType::OrderCanceled)
+            .map(|event| (event.cl_ord_id.as_str(), event.submitter.as_deref(), event.parent_cl_ord_id.as_deref()))
+            .collect();
+        assert_eq!(canceled, vec![("C1", Some("CLIENT2"), Some("M1")), ("C2", Some("CLIENT2"), Some("M1"))]);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("Canceled by mass cancel M1"), "Canceled by mass cancel M1");
+        assert_eq!(csv_field("Price 50, qty 10"), "\"Price 50, qty 10\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,178 @@
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use crate::fix::fix_message::{parse_field, parse_field_optional};
+use crate::fix::messages::new_order::NewOrder;
+use crate::oms::order::{OrdStatus, Order, OrderEvent, GOOD_TILL_CANCEL};
+use crate::session::session_clock::UTC_TIMESTAMP_FORMAT;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Keeps working Good Till Cancel orders in a file so they are back on the book after a
+/// restart. Each line is the order as a NewOrderSingle body, with its current OrdType and
+/// StopPx, plus its OrderID (37), OrdStatus (39), CumQty (14), LeavesQty (151) and AvgPx (6).
+pub struct GtcStore {
+    path: PathBuf,
+}
+
+impl GtcStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        GtcStore { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Reads back the orders saved last, in the order they were saved.
+    pub fn load(&self, time: DateTime<Utc>) -> io::Result<Vec<Order>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| from_record(line, time).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+            .collect()
+    }
+
+    /// Replaces the file with the working GTC orders among `orders`. An order with a value that
+    /// would not read back, which the parser keeps out of the OMS, is left out rather than
+    /// spoiling the file for the next start.
+    pub fn save<'a>(&self, orders: impl IntoIterator<Item = &'a Order>) -> io::Result<()> {
+        let records: String = orders
+            .into_iter()
+            .filter(|order| order.time_in_force() == GOOD_TILL_CANCEL && !order.ord_status.is_terminal())
+            .filter(|order| {
+                let storable = text_values(order).all(FixMessageParser::is_safe_value);
+                if !storable {
+                    eprintln!("Not storing GTC order {}: a value holds a line break or field delimiter", order.order_id.escape_debug());
+                }
+                storable
+            })
+            .map(|order| to_record(order) + "\n")
+            .collect();
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, records)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+/// The values of `order` that come from its originator rather than the OMS.
+fn text_values(order: &Order) -> impl Iterator<Item = &str> {
+    [Some(&order.order_id), Some(&order.cl_ord_id), order.account.as_ref(), order.session.as_ref()]
+        .into_iter()
+        .chain([order.self_match_prevention_id.as_ref(), Some(&order.symbol), order.currency.as_ref()])
+        .flatten()
+        .map(String::as_str)
+}
+
+fn to_record(order: &Order) -> String {
+    let entered = order.events.first().map_or(Utc::now(), |event| event.time);
+    FixMessageWriter::new("D")
+        .field("37", &order.order_id)
+        .field("11", &order.cl_ord_id)
+        .optional_field("1", order.account.as_ref())
+        .optional_field("49", order.session.as_ref())
+        .optional_field("2362", order.self_match_prevention_id.as_ref())
+        .field("55", &order.symbol)
+        .field("54", order.side)
+        .field("40", order.ord_type)
+        .optional_field("44", order.price)
+        .optional_field("99", order.stop_px)
+        .optional_field("211", order.peg_offset_value)
+        .field("38", order.order_qty)
+        .optional_field("111", order.max_floor)
+        .optional_field("59", order.time_in_force)
+        .optional_field("15", order.currency.as_ref())
+        .field("60", entered.format(UTC_TIMESTAMP_FORMAT))
+        .field("39", order.ord_status.as_char())
+        .field("14", order.cum_qty)
+        .field("151", order.leaves_qty)
+        .field("6", order.avg_px)
+        .encode_body()
+}
+
+fn from_record(record: &str, time: DateTime<Utc>) -> Result<Order, String> {
+    let fields = FixMessageParser::parse_fields(record);
+    let new_order = NewOrder::new(fields.clone())?;
+    let mut order = Order::new(parse_field(&fields, "37")?, &new_order, time);
+
+    order.ord_status = match parse_field::<char>(&fields, "39")? {
+        '0' => OrdStatus::New,
+        '1' => OrdStatus::PartiallyFilled,
+        other => return Err(format!("Order {} has OrdStatus {} and is not working", order.order_id, other)),
+    };
+    order.cum_qty = parse_field(&fields, "14")?;
+    order.leaves_qty = parse_field(&fields, "151")?;
+    order.avg_px = parse_field(&fields, "6")?;
+    order.session = parse_field_optional(&fields, "49")?;
+    order.events.push(OrderEvent {
+        time,
+        ord_status: order.ord_status,
+        last_qty: None,
+        last_px: None,
+        text: Some("Restored after restart".to_string()),
+    });
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message::FixMessage;
+
+    fn order(order_id: &str, time_in_force: char) -> Order {
+        let message = format!("35=D|11=C{}|1=ACC1|54=2|60=20230101-12:00:00|38=100|40=2|44=50.5|55=XYZ|59={}|", order_id, time_in_force);
+        let mut order = match FixMessageParser::parse_message(&message) {
+            FixMessage::NewOrder(new_order) => Order::new(order_id.to_string(), &new_order, Utc::now()),
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        };
+        order.transition(OrdStatus::New, Utc::now(), None).unwrap();
+        order
+    }
+
+    #[test]
+    fn test_working_gtc_orders_survive_reload() {
+        let path = std::env::temp_dir().join(format!("fix_gtc_store_{}", std::process::id())).join("gtc");
+        let store = GtcStore::new(&path);
+
+        let mut partially_filled = order("ORD1", GOOD_TILL_CANCEL);
+        partially_filled.fill(40.0, 50.0, Utc::now()).unwrap();
+        let mut canceled = order("ORD2", GOOD_TILL_CANCEL);
+        canceled.transition(OrdStatus::Canceled, Utc::now(), None).unwrap();
+        let day = order("ORD3", '0');
+        store.save([&partially_filled, &canceled, &day]).unwrap();
+
+        let orders = store.load(Utc::now()).unwrap();
+        assert_eq!(orders.len(), 1);
+        let restored = &orders[0];
+        assert_eq!(restored.order_id, "ORD1");
+        assert_eq!(restored.cl_ord_id, "CORD1");
+        assert_eq!(restored.account.as_deref(), Some("ACC1"));
+        assert_eq!(restored.ord_status, OrdStatus::PartiallyFilled);
+        assert_eq!((restored.cum_qty, restored.leaves_qty, restored.avg_px), (40.0, 60.0, 50.0));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_an_order_with_a_line_break_in_a_value_does_not_spoil_the_reload() {
+        let path = std::env::temp_dir().join(format!("fix_gtc_store_values_{}", std::process::id())).join("gtc");
+        let store = GtcStore::new(&path);
+        let message = "35=D|11=C1|54=2|60=20230101-12:00:00|38=100|40=2|44=50.5|55=XYZ\n|59=1|";
+        assert!(matches!(FixMessageParser::parse_message(message), FixMessage::Unknown), "the parser refuses it");
+
+        let kept = order("ORD1", GOOD_TILL_CANCEL);
+        let mut split_symbol = order("ORD2", GOOD_TILL_CANCEL);
+        split_symbol.symbol = "XYZ\n|54=1".to_string();
+        let mut split_account = order("ORD3", GOOD_TILL_CANCEL);
+        split_account.account = Some("ACC\r\n1".to_string());
+        store.save([&kept, &split_symbol, &split_account]).unwrap();
+
+        let orders = store.load(Utc::now()).unwrap();
+        assert_eq!(orders.iter().map(|order| (order.order_id.as_str(), order.symbol.as_str())).collect::<Vec<_>>(), vec![("ORD1", "XYZ")]);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
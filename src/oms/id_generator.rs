@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Issues IDs such as OrderIDs or ExecIDs: a prefix followed by a number that goes up by one
+/// with every ID, zero-padded to `width` digits so that IDs also sort in the order issued.
+#[derive(Debug, Clone)]
+pub struct IdGenerator {
+    prefix: String,
+    width: usize,
+    next: u64,
+}
+
+impl IdGenerator {
+    pub fn new(prefix: &str, width: usize) -> Self {
+        IdGenerator { prefix: prefix.to_string(), width, next: 1 }
+    }
+
+    pub fn next_id(&mut self) -> String {
+        let id = format!("{}{:0width$}", self.prefix, self.next, width = self.width);
+        self.next += 1;
+        id
+    }
+
+    /// Number the next ID will carry.
+    pub fn next_number(&self) -> u64 {
+        self.next
+    }
+
+    /// Carries on from `next`, as restored from a snapshot.
+    pub fn reset(&mut self, next: u64) {
+        self.next = next;
+    }
+
+    /// Carries on from `next` unless already past it, so that no ID is issued twice.
+    pub fn resume(&mut self, next: u64) {
+        self.next = self.next.max(next);
+    }
+
+    /// Number within an ID this generator issued.
+    pub fn number(&self, id: &str) -> Option<u64> {
+        id.strip_prefix(self.prefix.as_str()).and_then(|number| number.parse().ok())
+    }
+}
+
+/// Numbers the next OrderID and ExecID will carry, as kept by an `IdStore`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NextIds {
+    pub next_order_id: u64,
+    pub next_exec_id: u64,
+}
+
+/// Keeps the next OrderID and ExecID numbers in a file, so that a restart carries on after the
+/// last IDs issued even without a journal. The file only ever moves forward.
+pub struct IdStore {
+    path: PathBuf,
+    saved: NextIds,
+}
+
+impl IdStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        IdStore { path: path.as_ref().to_path_buf(), saved: NextIds::default() }
+    }
+
+    /// Numbers last saved or loaded.
+    pub fn saved(&self) -> NextIds {
+        self.saved
+    }
+
+    /// Reads the numbers saved by an earlier run; none if the file does not exist yet.
+    pub fn load(&mut self) -> io::Result<Option<NextIds>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&self.path)?;
+        self.saved = serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(self.saved))
+    }
+
+    /// Writes `next` unless the file is already as far on, replacing the file whole so that a
+    /// crash leaves either the old numbers or the new ones.
+    pub fn save(&mut self, next: NextIds) -> io::Result<()> {
+        let next = NextIds {
+            next_order_id: next.next_order_id.max(self.saved.next_order_id),
+            next_exec_id: next.next_exec_id.max(self.saved.next_exec_id),
+        };
+        if next == self.saved {
+            return Ok(());
+        }
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_string(&next)?)?;
+        fs::rename(temp_path, &self.path)?;
+        self.saved = next;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_padded_and_parsed_back() {
+        let mut ids = IdGenerator::new("S1-ORD", 6);
+        assert_eq!(ids.next_id(), "S1-ORD000001");
+        assert_eq!(ids.next_id(), "S1-ORD000002");
+        assert_eq!(ids.number("S1-ORD000002"), Some(2));
+        assert_eq!(ids.number("S2-ORD000002"), None);
+
+        ids.resume(1);
+        assert_eq!(ids.next_number(), 3);
+        ids.resume(10);
+        assert_eq!(ids.next_id(), "S1-ORD000010");
+    }
+
+    #[test]
+    fn test_store_survives_a_restart_and_never_goes_back() {
+        let path = std::env::temp_dir().join(format!("fix_ids_{}", std::process::id())).join("ids.json");
+        let _ = fs::remove_file(&path);
+
+        let mut store = IdStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+        store.save(NextIds { next_order_id: 5, next_exec_id: 9 }).unwrap();
+        store.save(NextIds { next_order_id: 3, next_exec_id: 12 }).unwrap();
+
+        let mut restarted = IdStore::new(&path);
+        assert_eq!(restarted.load().unwrap(), Some(NextIds { next_order_id: 5, next_exec_id: 12 }));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::fix::messages::new_order::NewOrder;
+use crate::oms::order::MIN_LOT_SIZE;
+use crate::oms::order_management_system::{EXCHANGE_CLOSED, INCORRECT_QUANTITY, INVALID_PRICE_INCREMENT, OTHER, PRICE_EXCEEDS_BAND, UNKNOWN_SYMBOL};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use utoipa::ToSchema;
+
+/// Columns of an instrument master CSV file, which starts with them as its header line.
+const CSV_COLUMNS: [&str; 9] = ["symbol", "security_id", "security_id_source", "currency", "tick_size", "lot_size", "min_price", "max_price", "status"];
+
+/// Whether an instrument may be traded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TradingStatus {
+    #[default]
+    Trading,
+    /// Orders are rejected until trading resumes.
+    Halted,
+}
+
+/// Reference data of one instrument. Omitted limits are not enforced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Instrument {
+    /// Symbol (55) orders name the instrument by.
+    pub symbol: String,
+    /// SecurityID (48), such as an ISIN.
+    pub security_id: Option<String>,
+    /// SecurityIDSource (22) of the SecurityID, e.g. 4 for ISIN.
+    pub security_id_source: Option<String>,
+    /// Currency (15) the instrument trades in; orders stating another are rejected.
+    pub currency: Option<String>,
+    /// Prices must be a multiple of this.
+    pub tick_size: Option<f64>,
+    /// Quantities must be a multiple of this.
+    pub lot_size: Option<f64>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    #[serde(default)]
+    pub status: TradingStatus,
+}
+
+impl Instrument {
+    /// Checks a NewOrderSingle against the instrument, returning the OrdRejReason and text on
+    /// failure.
+    pub fn check(&self, new_order: &NewOrder) -> Result<(), (u32, String)> {
+        if self.status == TradingStatus::Halted {
+            return Err((EXCHANGE_CLOSED, format!("Trading in {} is halted", self.symbol)));
+        }
+        if let (Some(currency), Some(expected)) = (&new_order.currency, &self.currency) {
+            if currency != expected {
+                return Err((OTHER, format!("{} trades in {}, not {}", self.symbol, expected, currency)));
+            }
+        }
+        if let Some(lot_size) = self.lot_size {
+            for (name, qty) in [("OrderQty", Some(new_order.order_qty)), ("MaxFloor", new_order.max_floor)] {
+                if let Some(qty) = qty.filter(|qty| !is_multiple(*qty, lot_size)) {
+                    return Err((INCORRECT_QUANTITY, format!("{} {} is not a multiple of the lot size {}", name, qty, lot_size)));
+                }
+            }
+        }
+        for (name, price) in [("Price", new_order.price), ("StopPx", new_order.stop_px)] {
+            let price = match price {
+                Some(price) => price,
+                None => continue,
+            };
+            if let Some(tick_size) = self.tick_size.filter(|tick_size| !is_multiple(price, *tick_size)) {
+                return Err((INVALID_PRICE_INCREMENT, format!("{} {} is not a multiple of the tick size {}", name, price, tick_size)));
+            }
+            if let Some(min_price) = self.min_price.filter(|min_price| price < *min_price) {
+                return Err((PRICE_EXCEEDS_BAND, format!("{} {} is below the minimum of {}", name, price, min_price)));
+            }
+            if let Some(max_price) = self.max_price.filter(|max_price| price > *max_price) {
+                return Err((PRICE_EXCEEDS_BAND, format!("{} {} is above the maximum of {}", name, price, max_price)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Instruments that may be traded, by symbol. Orders for any other symbol are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentMaster {
+    instruments: BTreeMap<String, Instrument>,
+}
+
+impl InstrumentMaster {
+    pub fn new(instruments: impl IntoIterator<Item = Instrument>) -> Result<Self, String> {
+        let mut master = BTreeMap::new();
+        for instrument in instruments {
+            if let Some(size) = [instrument.tick_size, instrument.lot_size].into_iter().flatten().find(|size| *size <= 0.0) {
+                return Err(format!("Instrument {} has a tick or lot size of {}", instrument.symbol, size));
+            }
+            if let Some(lot_size) = instrument.lot_size.filter(|lot_size| *lot_size < MIN_LOT_SIZE) {
+                return Err(format!("Instrument {} has a lot size of {}, below the smallest of {}", instrument.symbol, lot_size, MIN_LOT_SIZE));
+            }
+            if let Some(duplicate) = master.insert(instrument.symbol.clone(), instrument) {
+                return Err(format!("Duplicate instrument {}", duplicate.symbol));
+            }
+        }
+        Ok(InstrumentMaster { instruments: master })
+    }
+
+    /// Loads a `.json` file holding an array of instruments, or a `.csv` file with a header line
+    /// naming its columns. CSV fields cannot be quoted, and empty fields are left unset.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read instruments {}: {}", path.display(), e))?;
+        let instruments = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&contents).map_err(|e| e.to_string()),
+            Some("csv") => parse_csv(&contents),
+            _ => Err("expected a .csv or .json file".to_string()),
+        };
+        instruments
+            .and_then(InstrumentMaster::new)
+            .map_err(|e| format!("Invalid instruments {}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(symbol)
+    }
+
+    /// Every instrument, by symbol.
+    pub fn instruments(&self) -> impl Iterator<Item = &Instrument> {
+        self.instruments.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.instruments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instruments.is_empty()
+    }
+
+    /// Checks that a NewOrderSingle names a known instrument and fits its reference data.
+    pub fn check(&self, new_order: &NewOrder) -> Result<(), (u32, String)> {
+        match self.instruments.get(&new_order.symbol) {
+            Some(instrument) => instrument.check(new_order),
+            None => Err((UNKNOWN_SYMBOL, format!("Unknown symbol {}", new_order.symbol))),
+        }
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<Instrument>, String> {
+    let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<&str> = match lines.next() {
+        Some((_, header)) => header.split(',').map(str::trim).collect(),
+        None => return Ok(Vec::new()),
+    };
+    if let Some(column) = header.iter().find(|column| !CSV_COLUMNS.contains(column)) {
+        return Err(format!("unknown column {}", column));
+    }
+    lines
+        .map(|(index, line)| {
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            if values.len() != header.len() {
+                return Err(format!("line {} has {} fields, expected {}", index + 1, values.len(), header.len()));
+            }
+            let fields: HashMap<&str, &str> = header.iter().copied().zip(values).filter(|(_, value)| !value.is_empty()).collect();
+            instrument(&fields).map_err(|e| format!("line {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+fn instrument(fields: &HashMap<&str, &str>) -> Result<Instrument, String> {
+    let text = |column: &str| fields.get(column).map(|value| value.to_string());
+    let number = |column: &str| {
+        fields
+            .get(column)
+            .map(|value| value.parse::<f64>().map_err(|_| format!("invalid {} {}", column, value)))
+            .transpose()
+    };
+    let status = match fields.get("status") {
+        None | Some(&"trading") => TradingStatus::Trading,
+        Some(&"halted") => TradingStatus::Halted,
+        Some(other) => return Err(format!("invalid status {}", other)),
+    };
+    Ok(Instrument {
+        symbol: text("symbol").ok_or("missing symbol")?,
+        security_id: text("security_id"),
+        security_id_source: text("security_id_source"),
+        currency: text("currency"),
+        tick_size: number("tick_size")?,
+        lot_size: number("lot_size")?,
+        min_price: number("min_price")?,
+        max_price: number("max_price")?,
+        status,
+    })
+}
+
+/// Whether `value` is a whole number of `step`s, allowing for binary floating point.
+fn is_multiple(value: f64, step: f64) -> bool {
+    let steps = value / step;
+    (steps - steps.round()).abs() < 1e-9 * steps.abs().max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message::FixMessage;
+    use crate::fix::fix_message_parser::FixMessageParser;
+
+    fn new_order(fields: &str) -> NewOrder {
+        match FixMessageParser::parse_message(&format!("35=D|11=C1|54=1|60=20230101-12:00:00|40=2|{}", fields)) {
+            FixMessage::NewOrder(order) => order,
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_csv_master_checks_ticks_lots_and_limits() {
+        let master = InstrumentMaster::new(parse_csv("\
+            symbol,security_id,security_id_source,currency,tick_size,lot_size,min_price,max_price,status
+            VOD.L,GB00BH4HKS39,4,GBP,0.02,100,10,500,
+            BARC.L,,,GBP,,,,,halted
+        ").unwrap()).unwrap();
+        assert_eq!(master.len(), 2);
+        assert_eq!(master.get("VOD.L").unwrap().security_id.as_deref(), Some("GB00BH4HKS39"));
+
+        assert_eq!(master.check(&new_order("55=VOD.L|38=300|44=70.14|")), Ok(()));
+        let rejection = |fields: &str| master.check(&new_order(fields)).unwrap_err();
+        assert_eq!(rejection("55=VOD.L|38=300|44=70.13|"), (INVALID_PRICE_INCREMENT, "Price 70.13 is not a multiple of the tick size 0.02".to_string()));
+        assert_eq!(rejection("55=VOD.L|38=250|44=70.14|").0, INCORRECT_QUANTITY);
+        assert_eq!(rejection("55=VOD.L|38=300|44=600|").0, PRICE_EXCEEDS_BAND);
+        assert_eq!(rejection("55=VOD.L|38=300|44=70|15=USD|").1, "VOD.L trades in GBP, not USD");
+        assert_eq!(rejection("55=BARC.L|38=300|44=2|").1, "Trading in BARC.L is halted");
+        assert_eq!(rejection("55=XYZ|38=300|44=70|"), (UNKNOWN_SYMBOL, "Unknown symbol XYZ".to_string()));
+
+        assert!(parse_csv("symbol,colour\nVOD.L,red").is_err());
+        assert!(InstrumentMaster::new(parse_csv("symbol\nVOD.L\nVOD.L").unwrap()).is_err());
+    }
+}
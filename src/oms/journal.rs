@@ -0,0 +1,166 @@
+use crate::oms::id_generator::NextIds;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const INBOUND: &str = "IN";
+const OUTBOUND: &str = "OUT";
+const TIMER: &str = "TIMER";
+const IDS: &str = "IDS";
+
+/// Entries keep times to the nanosecond so that a replay sees exactly the times the OMS saw.
+const TIME_FORMAT: &str = "%Y%m%d-%H:%M:%S%.9f";
+
+/// Something the OMS journals. Messages are kept as their encoded body, e.g. `35=D|11=...|`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalEvent {
+    /// A message the OMS received.
+    Inbound(String),
+    /// A timer tick that started the OMS's clock, ran an auction or expired orders.
+    Timer,
+    /// A message the OMS produced, kept for the record and not replayed.
+    Outbound(String),
+    /// ID numbering moved on past the journal, to where the ID store left off.
+    Ids(NextIds),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// Position of the entry in the journal, counting from 1.
+    pub offset: u64,
+    pub time: DateTime<Utc>,
+    pub event: JournalEvent,
+}
+
+/// Append-only record of everything that changed the OMS, one entry per line as
+/// `<offset> <time> IN|OUT|TIMER|IDS [message or ID numbers]`. Replaying the inbound messages and timer ticks at
+/// their recorded times rebuilds the OMS as it was.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    next_offset: u64,
+}
+
+impl Journal {
+    /// Opens the journal for appending after its last complete entry, creating it if needed.
+    /// A line cut short by a crash is dropped.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let contents = fs::read_to_string(&path)?;
+        let complete_len = contents.rfind('\n').map_or(0, |index| index + 1);
+        if complete_len < contents.len() {
+            eprintln!("Dropping an incomplete entry at the end of journal {}", path.display());
+            file.set_len(complete_len as u64)?;
+        }
+        let next_offset = parse_entries(&contents[..complete_len])?.last().map_or(1, |entry| entry.offset + 1);
+        Ok(Journal { path, file, next_offset })
+    }
+
+    /// Reads every complete entry of the journal at `path`, oldest first.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<JournalEntry>> {
+        let contents = fs::read_to_string(path)?;
+        let complete_len = contents.rfind('\n').map_or(0, |index| index + 1);
+        parse_entries(&contents[..complete_len])
+    }
+
+    pub fn entries(&self) -> io::Result<Vec<JournalEntry>> {
+        Journal::read(&self.path)
+    }
+
+    /// Offset the next appended entry will get.
+    pub fn next_offset(&self) -> u64 {
+        self.next_offset
+    }
+
+    /// Appends `events` as having happened at `time` and flushes them to disk before returning.
+    pub fn append(&mut self, time: DateTime<Utc>, events: Vec<JournalEvent>) -> io::Result<()> {
+        let mut lines = String::new();
+        let timestamp = time.format(TIME_FORMAT);
+        for (offset, event) in (self.next_offset..).zip(&events) {
+            let line = match event {
+                JournalEvent::Inbound(message) => format!("{} {} {} {}", offset, timestamp, INBOUND, message),
+                JournalEvent::Timer => format!("{} {} {}", offset, timestamp, TIMER),
+                JournalEvent::Outbound(message) => format!("{} {} {} {}", offset, timestamp, OUTBOUND, message),
+                JournalEvent::Ids(next) => format!("{} {} {} {} {}", offset, timestamp, IDS, next.next_order_id, next.next_exec_id),
+            };
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+        self.file.write_all(lines.as_bytes())?;
+        self.file.sync_data()?;
+        self.next_offset += events.len() as u64;
+        Ok(())
+    }
+}
+
+fn parse_entries(contents: &str) -> io::Result<Vec<JournalEntry>> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_entry(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Result<JournalEntry, String> {
+    let mut parts = line.splitn(4, ' ');
+    let invalid = || format!("Invalid journal entry: {}", line);
+    let offset = parts.next().and_then(|offset| offset.parse().ok()).ok_or_else(invalid)?;
+    let time = parts.next().and_then(|time| parse_time(time).ok()).ok_or_else(invalid)?;
+    let event = match (parts.next(), parts.next()) {
+        (Some(INBOUND), Some(message)) => JournalEvent::Inbound(message.to_string()),
+        (Some(OUTBOUND), Some(message)) => JournalEvent::Outbound(message.to_string()),
+        (Some(TIMER), None) => JournalEvent::Timer,
+        (Some(IDS), Some(numbers)) => match numbers.split_once(' ').map(|(order, exec)| (order.parse(), exec.parse())) {
+            Some((Ok(next_order_id), Ok(next_exec_id))) => JournalEvent::Ids(NextIds { next_order_id, next_exec_id }),
+            _ => return Err(invalid()),
+        },
+        _ => return Err(invalid()),
+    };
+    Ok(JournalEntry { offset, time, event })
+}
+
+/// Parses a point in time given as a FIX UTCTimestamp, with or without fractional seconds.
+pub fn parse_time(time: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(time, "%Y%m%d-%H:%M:%S%.f")
+        .map(|time| time.and_utc())
+        .map_err(|_| format!("Invalid time {}, expected YYYYMMDD-HH:MM:SS", time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_survive_reopen_and_a_torn_write() {
+        let path = std::env::temp_dir().join(format!("fix_journal_{}", std::process::id())).join("journal");
+        let time = parse_time("20240102-09:30:00.250").unwrap();
+
+        let mut journal = Journal::open(&path).unwrap();
+        journal.append(time, vec![JournalEvent::Timer, JournalEvent::Inbound("35=D|11=C1|".to_string())]).unwrap();
+        journal.append(time, vec![JournalEvent::Outbound("35=8|58=Order accepted|".to_string())]).unwrap();
+        drop(journal);
+
+        // A crash part way through writing an entry leaves it without its newline
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"4 20240102-09:30").unwrap();
+        let mut journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.next_offset(), 4);
+        journal.append(time, vec![JournalEvent::Timer]).unwrap();
+        let ids = NextIds { next_order_id: 12, next_exec_id: 40 };
+        journal.append(time, vec![JournalEvent::Ids(ids)]).unwrap();
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], JournalEntry { offset: 1, time, event: JournalEvent::Timer });
+        assert_eq!(entries[2].event, JournalEvent::Outbound("35=8|58=Order accepted|".to_string()));
+        assert_eq!(entries[3].offset, 4);
+        assert_eq!(entries[4].event, JournalEvent::Ids(ids));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::fix::fix_message::FixMessage;
+use crate::fix::messages::business_message_reject::BusinessMessageReject;
+use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::new_order::NewOrder;
+use crate::fix::messages::order_cancel_reject::OrderCancelReject;
+use crate::fix::messages::order_cancel_request::OrderCancelRequest;
+use crate::fix::messages::order_mass_cancel_report::OrderMassCancelReport;
+use crate::fix::messages::order_mass_cancel_request::OrderMassCancelRequest;
+use crate::fix::messages::order_status_request::OrderStatusRequest;
+use crate::oms::instruments::InstrumentMaster;
+use crate::oms::oms_request::ReplyTo;
+use crate::oms::order_management_system::{validate, OrderManagementSystem};
+use crate::oms::risk::RiskChecks;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+
+pub const LOGGING: &str = "logging";
+pub const INSTRUMENTS: &str = "instruments";
+pub const RISK: &str = "risk";
+pub const MATCHING: &str = "matching";
+pub const ROUTING: &str = "routing";
+pub const SHARD_INDEX: &str = "shard_index";
+
+/// What a handler made of an inbound message.
+#[derive(Debug)]
+pub enum Disposition {
+    /// Pass the message on to the next handler.
+    Continue,
+    /// The message is dealt with; these are the replies to its originator.
+    Done(Vec<FixMessage>),
+}
+
+/// One link in the chain of handlers the OMS runs each message through. Every callback
+/// defaults to passing the message on, so a handler only implements the messages it cares
+/// about. Handlers see the OMS mutably and can enter, reject or cancel orders through it, but
+/// must not feed it further messages: the chain is taken out of the OMS while it runs.
+pub trait FixMessageHandler: Send {
+    /// Name the handler is registered under, unique within a registry.
+    fn name(&self) -> &str;
+
+    fn on_new_order(&mut self, _new_order: &NewOrder, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_cancel_request(&mut self, _request: &OrderCancelRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_status_request(&mut self, _request: &OrderStatusRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_mass_cancel_request(&mut self, _request: &OrderMassCancelRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_execution_report(&mut self, _report: &ExecutionReport, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_order_cancel_reject(&mut self, _reject: &OrderCancelReject, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_mass_cancel_report(&mut self, _report: &OrderMassCancelReport, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_business_message_reject(&mut self, _reject: &BusinessMessageReject, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    /// Logon, Logout, Heartbeat and the other session-level messages.
+    fn on_session_message(&mut self, _message: &FixMessage, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    fn on_unknown(&mut self, _oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Continue
+    }
+
+    /// Called with the replies to a message once the chain is done, before they are sent to
+    /// `reply_to`.
+    fn on_replies(&mut self, _replies: &[FixMessage], _reply_to: Option<&ReplyTo>, _oms: &OrderManagementSystem) {}
+
+    /// Called for each report produced for an order other than the requester's, such as a
+    /// fill against a resting order, keyed by its OrderID.
+    fn on_unsolicited(&mut self, _order_id: &str, _message: &FixMessage, _oms: &OrderManagementSystem) {}
+}
+
+/// The ordered chain of handlers the OMS runs messages through. An inbound message goes to each
+/// handler in turn until one is done with it; replies and unsolicited reports go to all of them.
+#[derive(Default)]
+pub struct HandlerRegistry {
+    handlers: Vec<Box<dyn FixMessageHandler>>,
+}
+
+impl HandlerRegistry {
+    pub fn new() -> Self {
+        HandlerRegistry::default()
+    }
+
+    /// Logging, risk checks without limits, matching and routing, which is how the OMS runs
+    /// unless told otherwise.
+    pub fn standard() -> Self {
+        HandlerRegistry::new()
+            .with(LoggingHandler)
+            .with(RiskHandler::new(RiskChecks::default()))
+            .with(MatchingHandler)
+            .with(RoutingHandler::new())
+    }
+
+    pub fn with<H: FixMessageHandler + 'static>(mut self, handler: H) -> Self {
+        self.push(handler);
+        self
+    }
+
+    /// Adds `handler` at the end of the chain.
+    pub fn push<H: FixMessageHandler + 'static>(&mut self, handler: H) {
+        self.handlers.push(Box::new(handler));
+    }
+
+    /// Adds `handler` just ahead of the handler named `name`.
+    pub fn insert_before<H: FixMessageHandler + 'static>(&mut self, name: &str, handler: H) -> Result<(), String> {
+        let index = self.position(name).ok_or_else(|| format!("No handler named {}", name))?;
+        self.handlers.insert(index, Box::new(handler));
+        Ok(())
+    }
+
+    /// Replaces the handler registered under the same name, or adds `handler` at the end if
+    /// there is none.
+    pub fn set<H: FixMessageHandler + 'static>(&mut self, handler: H) {
+        match self.position(handler.name()) {
+            Some(index) => self.handlers[index] = Box::new(handler),
+            None => self.push(handler),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn FixMessageHandler>> {
+        self.position(name).map(|index| self.handlers.remove(index))
+    }
+
+    /// Names of the handlers in the order they run.
+    pub fn names(&self) -> Vec<&str> {
+        self.handlers.iter().map(|handler| handler.name()).collect()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.handlers.iter().position(|handler| handler.name() == name)
+    }
+
+    /// Runs `message` down the chain and returns the replies of the handler that dealt with it,
+    /// or nothing if none did.
+    pub fn dispatch(&mut self, message: &FixMessage, oms: &mut OrderManagementSystem) -> Vec<FixMessage> {
+        for handler in &mut self.handlers {
+            let disposition = match message {
+                FixMessage::NewOrder(new_order) => handler.on_new_order(new_order, oms),
+                FixMessage::OrderCancelRequest(request) => handler.on_cancel_request(request, oms),
+                FixMessage::OrderStatusRequest(request) => handler.on_status_request(request, oms),
+                FixMessage::OrderMassCancelRequest(request) => handler.on_mass_cancel_request(request, oms),
+                FixMessage::OrderMassCancelReport(report) => handler.on_mass_cancel_report(report, oms),
+                FixMessage::ExecutionReport(report) => handler.on_execution_report(report, oms),
+                FixMessage::OrderCancelReject(reject) => handler.on_order_cancel_reject(reject, oms),
+                FixMessage::BusinessMessageReject(reject) => handler.on_business_message_reject(reject, oms),
+                FixMessage::Logon(_) | FixMessage::Logout(_) | FixMessage::Heartbeat(_) | FixMessage::TestRequest(_)
+                | FixMessage::ResendRequest(_) | FixMessage::SequenceReset(_) | FixMessage::Reject(_) => handler.on_session_message(message, oms),
+                FixMessage::Unknown => handler.on_unknown(oms),
+            };
+            if let Disposition::Done(replies) = disposition {
+                return replies;
+            }
+        }
+        Vec::new()
+    }
+
+    pub fn on_replies(&mut self, replies: &[FixMessage], reply_to: Option<&ReplyTo>, oms: &OrderManagementSystem) {
+        for handler in &mut self.handlers {
+            handler.on_replies(replies, reply_to, oms);
+        }
+    }
+
+    pub fn on_unsolicited(&mut self, order_id: &str, message: &FixMessage, oms: &OrderManagementSystem) {
+        for handler in &mut self.handlers {
+            handler.on_unsolicited(order_id, message, oms);
+        }
+    }
+}
+
+/// Logs every inbound message and passes it on.
+pub struct LoggingHandler;
+
+impl FixMessageHandler for LoggingHandler {
+    fn name(&self) -> &str {
+        LOGGING
+    }
+
+    fn on_new_order(&mut self, new_order: &NewOrder, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Processing new order: {:?}", new_order);
+        Disposition::Continue
+    }
+
+    fn on_cancel_request(&mut self, request: &OrderCancelRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Processing cancel request: {:?}", request);
+        Disposition::Continue
+    }
+
+    fn on_status_request(&mut self, request: &OrderStatusRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Processing status request: {:?}", request);
+        Disposition::Continue
+    }
+
+    fn on_mass_cancel_request(&mut self, request: &OrderMassCancelRequest, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Processing mass cancel request: {:?}", request);
+        Disposition::Continue
+    }
+
+    fn on_execution_report(&mut self, report: &ExecutionReport, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Received ExecutionReport: {:?}", report);
+        Disposition::Continue
+    }
+
+    fn on_order_cancel_reject(&mut self, reject: &OrderCancelReject, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Received OrderCancelReject: {:?}", reject);
+        Disposition::Continue
+    }
+
+    fn on_mass_cancel_report(&mut self, report: &OrderMassCancelReport, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Received OrderMassCancelReport: {:?}", report);
+        Disposition::Continue
+    }
+
+    fn on_business_message_reject(&mut self, reject: &BusinessMessageReject, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Received BusinessMessageReject: {:?}", reject);
+        Disposition::Continue
+    }
+
+    fn on_session_message(&mut self, message: &FixMessage, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Ignoring session-level message: {:?}", message);
+        Disposition::Continue
+    }
+
+    fn on_unknown(&mut self, _oms: &mut OrderManagementSystem) -> Disposition {
+        println!("Received an Unknown message type");
+        Disposition::Continue
+    }
+}
+
+/// Rejects new orders for unknown or halted instruments, and those off their tick or lot size or
+/// outside their price limits.
+pub struct InstrumentHandler {
+    instruments: Arc<InstrumentMaster>,
+}
+
+impl InstrumentHandler {
+    pub fn new(instruments: Arc<InstrumentMaster>) -> Self {
+        InstrumentHandler { instruments }
+    }
+}
+
+impl FixMessageHandler for InstrumentHandler {
+    fn name(&self) -> &str {
+        INSTRUMENTS
+    }
+
+    fn on_new_order(&mut self, new_order: &NewOrder, oms: &mut OrderManagementSystem) -> Disposition {
+        // Duplicate and malformed orders are left for the matching handler to reject as such
+        if oms.orders().contains_cl_ord_id(oms.session(), &new_order.cl_ord_id) || validate(new_order).is_err() {
+            return Disposition::Continue;
+        }
+        match self.instruments.check(new_order) {
+            Ok(()) => Disposition::Continue,
+            Err((ord_rej_reason, text)) => Disposition::Done(oms.reject_order(new_order, ord_rej_reason, text)),
+        }
+    }
+}
+
+/// Rejects new orders that fail the pre-trade risk checks before they reach the book.
+pub struct RiskHandler {
+    risk: RiskChecks,
+    /// Account of the order last counted towards a shared exposure, until it is published again.
+    checked: Option<Option<String>>,
+}
+
+impl RiskHandler {
+    pub fn new(risk: RiskChecks) -> Self {
+        RiskHandler { risk, checked: None }
+    }
+
+    fn publish(&self, account: Option<&str>, oms: &OrderManagementSystem) {
+        self.risk.publish(account, oms.account_exposure(account));
+    }
+}
+
+impl FixMessageHandler for RiskHandler {
+    fn name(&self) -> &str {
+        RISK
+    }
+
+    fn on_new_order(&mut self, new_order: &NewOrder, oms: &mut OrderManagementSystem) -> Disposition {
+        // Duplicate and malformed orders are left for the matching handler to reject as such
+        if oms.orders().contains_cl_ord_id(oms.session(), &new_order.cl_ord_id) || validate(new_order).is_err() {
+            return Disposition::Continue;
+        }
+        let order = oms.build_order(String::new(), new_order);
+        let last_px = oms.last_price(&order.symbol);
+        let exposure = oms.account_exposure(order.account.as_deref());
+        match self.risk.check(&order, last_px, exposure) {
+            Ok(()) => {
+                self.checked = self.risk.shares_exposure().then_some(order.account);
+                Disposition::Continue
+            }
+            Err((ord_rej_reason, text)) => Disposition::Done(oms.reject_order(new_order, ord_rej_reason, text)),
+        }
+    }
+
+    /// Publishes what the accounts the replies touched now have working, in place of the order
+    /// counted when it was checked, which may since have been rejected or filled.
+    fn on_replies(&mut self, replies: &[FixMessage], _reply_to: Option<&ReplyTo>, oms: &OrderManagementSystem) {
+        if !self.risk.shares_exposure() {
+            return;
+        }
+        let mut accounts: HashSet<Option<&str>> = self.checked.iter().map(Option::as_deref).collect();
+        for reply in replies {
+            if let FixMessage::ExecutionReport(report) = reply {
+                accounts.extend(oms.orders().get(&report.order_id).map(|order| order.account.as_deref()));
+            }
+        }
+        for account in accounts {
+            self.publish(account, oms);
+        }
+        self.checked = None;
+    }
+
+    fn on_unsolicited(&mut self, order_id: &str, _message: &FixMessage, oms: &OrderManagementSystem) {
+        if let Some(order) = oms.orders().get(order_id).filter(|_| self.risk.shares_exposure()) {
+            self.publish(order.account.as_deref(), oms);
+        }
+    }
+}
+
+/// Enters new orders into the book and answers cancel, mass cancel and status requests, ending
+/// the chain for those messages.
+pub struct MatchingHandler;
+
+impl FixMessageHandler for MatchingHandler {
+    fn name(&self) -> &str {
+        MATCHING
+    }
+
+    fn on_new_order(&mut self, new_order: &NewOrder, oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Done(oms.enter_order(new_order))
+    }
+
+    fn on_cancel_request(&mut self, request: &OrderCancelRequest, oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Done(vec![oms.cancel_order(request)])
+    }
+
+    fn on_status_request(&mut self, request: &OrderStatusRequest, oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Done(vec![FixMessage::ExecutionReport(oms.order_status(request))])
+    }
+
+    fn on_mass_cancel_request(&mut self, request: &OrderMassCancelRequest, oms: &mut OrderManagementSystem) -> Disposition {
+        Disposition::Done(vec![FixMessage::OrderMassCancelReport(oms.mass_cancel(request))])
+    }
+}
+
+/// Remembers which connection submitted each working order and forwards that order's
+/// unsolicited reports, such as fills against it while it rests, to that connection.
+pub struct RoutingHandler {
+    routes: HashMap<String, UnboundedSender<FixMessage>>,
+}
+
+impl RoutingHandler {
+    pub fn new() -> Self {
+        RoutingHandler { routes: HashMap::new() }
+    }
+}
+
+impl Default for RoutingHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FixMessageHandler for RoutingHandler {
+    fn name(&self) -> &str {
+        ROUTING
+    }
+
+    fn on_replies(&mut self, replies: &[FixMessage], reply_to: Option<&ReplyTo>, oms: &OrderManagementSystem) {
+        let sender = match reply_to {
+            Some(ReplyTo::Connection(sender)) => sender,
+            _ => return,
+        };
+        for reply in replies {
+            if let FixMessage::ExecutionReport(report) = reply {
+                let working = oms.orders().get(&report.order_id).is_some_and(|order| !order.ord_status.is_terminal());
+                if working {
+                    self.routes.entry(report.order_id.clone()).or_insert_with(|| sender.clone());
+                }
+            }
+        }
+    }
+
+    fn on_unsolicited(&mut self, order_id: &str, message: &FixMessage, oms: &OrderManagementSystem) {
+        if let Some(sender) = self.routes.get(order_id) {
+            if sender.send(message.clone()).is_err() {
+                self.routes.remove(order_id);
+            }
+        }
+        if oms.orders().get(order_id).is_none_or(|order| order.ord_status.is_terminal()) {
+            self.routes.remove(order_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use crate::oms::order::OrdStatus;
+    use crate::oms::order_management_system::{EXEC_TYPE_NEW, EXEC_TYPE_REJECTED, OTHER};
+
+    /// Rejects every order in one symbol.
+    struct SymbolBlock {
+        symbol: String,
+    }
+
+    impl FixMessageHandler for SymbolBlock {
+        fn name(&self) -> &str {
+            "symbol-block"
+        }
+
+        fn on_new_order(&mut self, new_order: &NewOrder, oms: &mut OrderManagementSystem) -> Disposition {
+            if new_order.symbol == self.symbol {
+                return Disposition::Done(oms.reject_order(new_order, OTHER, format!("{} is blocked", self.symbol)));
+            }
+            Disposition::Continue
+        }
+    }
+
+    fn new_order(cl_ord_id: &str, symbol: &str) -> FixMessage {
+        FixMessageParser::parse_message(&format!("35=D|11={}|54=1|60=20230101-12:00:00|38=10|40=2|44=50|55={}|", cl_ord_id, symbol))
+    }
+
+    fn exec_type(mut replies: Vec<FixMessage>) -> char {
+        match replies.remove(0) {
+            FixMessage::ExecutionReport(report) => report.exec_type,
+            other => panic!("Expected an ExecutionReport, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_custom_handler_runs_ahead_of_matching() {
+        let mut handlers = HandlerRegistry::standard();
+        handlers.insert_before(MATCHING, SymbolBlock { symbol: "BAD".to_string() }).unwrap();
+        assert_eq!(handlers.names(), vec![LOGGING, RISK, "symbol-block", MATCHING, ROUTING]);
+        let mut oms = OrderManagementSystem::new().with_handlers(handlers);
+
+        assert_eq!(exec_type(oms.handle(new_order("C1", "BAD"))), EXEC_TYPE_REJECTED);
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C1").unwrap().ord_status, OrdStatus::Rejected);
+        assert_eq!(exec_type(oms.handle(new_order("C2", "XYZ"))), EXEC_TYPE_NEW);
+
+        // Without the matching handler nothing enters the book
+        assert!(oms.handlers_mut().remove(MATCHING).is_some());
+        assert!(oms.handle(new_order("C3", "XYZ")).is_empty());
+        assert!(oms.orders().get_by_cl_ord_id(None, "C3").is_none());
+    }
+}
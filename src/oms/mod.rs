@@ -1 +1,16 @@
-pub mod order_management_system;
\ No newline at end of file
+pub mod auction;
+pub mod audit_trail;
+pub mod gtc_store;
+pub mod id_generator;
+pub mod instruments;
+pub mod journal;
+pub mod message_handler;
+pub mod oms_dispatcher;
+pub mod oms_request;
+pub mod order;
+pub mod order_book;
+pub mod order_management_system;
+pub mod order_store;
+pub mod positions;
+pub mod risk;
+pub mod snapshot;
@@ -0,0 +1,477 @@
+use crate::fix::fix_message::FixMessage;
+use crate::oms::message_handler::{FixMessageHandler, SHARD_INDEX};
+use crate::oms::oms_request::{OmsRequest, ReplyTo};
+use crate::oms::order_management_system::{OrderManagementSystem, MASS_CANCEL_SECURITY};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tokio::sync::mpsc::{self, error::{SendError, TrySendError}, Sender};
+use tokio::sync::oneshot;
+
+/// Shard owning `symbol` out of `shards`. The hash is stable across builds and restarts, so a
+/// symbol stays with the shard whose journal holds its orders.
+pub fn shard_of_symbol(symbol: &str, shards: usize) -> usize {
+    crc32fast::hash(symbol.as_bytes()) as usize % shards
+}
+
+/// Shard holding each live ClOrdID, by the CompID that used it.
+type ClOrdIdShards = Arc<Mutex<HashMap<(String, String), usize>>>;
+
+/// Routes messages to OMS shards, each owning the books and orders of its share of the symbols,
+/// so an order matches exactly as it would in a single OMS. A message about a working order
+/// goes wherever its counterparty's ClOrdID went before, which keeps every ClOrdID's messages
+/// in order on one shard and lets that shard reject a duplicate of a working order's ClOrdID
+/// even in another symbol. Once an order is done its ClOrdIDs are forgotten, so reusing one
+/// is only caught on the shard of the order's symbol. A mass cancel beyond one security goes
+/// to every shard, and its requester gets a single report back. Other messages without a
+/// symbol go to the first shard.
+#[derive(Clone)]
+pub struct OmsDispatcher {
+    shards: Vec<Sender<OmsRequest>>,
+    cl_ord_ids: ClOrdIdShards,
+}
+
+impl OmsDispatcher {
+    pub fn new(shards: Vec<Sender<OmsRequest>>) -> Self {
+        OmsDispatcher { shards, cl_ord_ids: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Shard the request belongs to.
+    pub fn shard_of(&self, request: &OmsRequest) -> usize {
+        self.route(request).0
+    }
+
+    /// Shard the request belongs to, along with the ClOrdID it introduces, if any.
+    fn route(&self, request: &OmsRequest) -> (usize, Option<(String, String)>) {
+        let shards = self.shards.len();
+        if shards == 1 {
+            return (0, None);
+        }
+        let key = |cl_ord_id: &str| (request.session.clone().unwrap_or_default(), cl_ord_id.to_string());
+        let cl_ord_ids = self.cl_ord_ids.lock().unwrap();
+        match &request.message {
+            FixMessage::NewOrder(new_order) => {
+                let key = key(&new_order.cl_ord_id);
+                match cl_ord_ids.get(&key) {
+                    Some(&shard) => (shard, None),
+                    None => (shard_of_symbol(&new_order.symbol, shards), Some(key)),
+                }
+            }
+            FixMessage::OrderCancelRequest(request) => {
+                let shard = cl_ord_ids.get(&key(&request.orig_cl_ord_id)).copied().unwrap_or_else(|| shard_of_symbol(&request.symbol, shards));
+                let key = key(&request.cl_ord_id);
+                let introduced = (!cl_ord_ids.contains_key(&key)).then_some(key);
+                (shard, introduced)
+            }
+            FixMessage::OrderStatusRequest(request) => {
+                (cl_ord_ids.get(&key(&request.cl_ord_id)).copied().unwrap_or_else(|| shard_of_symbol(&request.symbol, shards)), None)
+            }
+            FixMessage::OrderMassCancelRequest(request) => match (request.mass_cancel_request_type, &request.symbol) {
+                (MASS_CANCEL_SECURITY, Some(symbol)) => (shard_of_symbol(symbol, shards), None),
+                _ => (0, None),
+            },
+            _ => (0, None),
+        }
+    }
+
+    /// Records the shard of a ClOrdID the request introduces. It has to be in place before the
+    /// request is queued, as the shard may be done with the order before the send returns; a
+    /// failed send takes it back out with `forget`.
+    fn remember(&self, request: &OmsRequest) -> (usize, Option<(String, String)>) {
+        let (shard, introduced) = self.route(request);
+        if let Some(key) = &introduced {
+            self.cl_ord_ids.lock().unwrap().entry(key.clone()).or_insert(shard);
+        }
+        (shard, introduced)
+    }
+
+    fn forget(&self, shard: usize, introduced: Option<(String, String)>) {
+        if let Some(key) = introduced {
+            let mut cl_ord_ids = self.cl_ord_ids.lock().unwrap();
+            if cl_ord_ids.get(&key) == Some(&shard) {
+                cl_ord_ids.remove(&key);
+            }
+        }
+    }
+
+    /// Whether the message needs every shard: a mass cancel beyond one security.
+    fn is_for_every_shard(&self, message: &FixMessage) -> bool {
+        match message {
+            FixMessage::OrderMassCancelRequest(request) => self.shards.len() > 1 && request.mass_cancel_request_type != MASS_CANCEL_SECURITY,
+            _ => false,
+        }
+    }
+
+    /// Queues the request on its shard, waiting for room if the queue is full. A request for
+    /// every shard only fails if no shard took it; otherwise the report back says how many
+    /// shards it missed.
+    pub async fn send(&self, request: OmsRequest) -> Result<(), SendError<OmsRequest>> {
+        if !self.is_for_every_shard(&request.message) {
+            let (shard, introduced) = self.remember(&request);
+            let sent = self.shards[shard].send(request).await;
+            if sent.is_err() {
+                self.forget(shard, introduced);
+            }
+            return sent;
+        }
+        let (requests, gather) = fan_out(request, self.shards.len());
+        let mut failure = None;
+        let mut reached = 0;
+        for (shard, request) in self.shards.iter().zip(requests) {
+            match shard.send(request).await {
+                Ok(()) => reached += 1,
+                Err(e) => failure = Some(e),
+            }
+        }
+        match failure {
+            Some(e) if reached == 0 => Err(e),
+            _ => {
+                tokio::spawn(gather);
+                Ok(())
+            }
+        }
+    }
+
+    /// Queues the request on its shard, failing straight away if the queue is full. A request
+    /// for every shard is offered to each of them and only fails if none had room; otherwise
+    /// the report back says how many shards it missed.
+    #[allow(clippy::result_large_err)] // Hands the request back on failure, as the channel does
+    pub fn try_send(&self, request: OmsRequest) -> Result<(), TrySendError<OmsRequest>> {
+        if !self.is_for_every_shard(&request.message) {
+            let (shard, introduced) = self.remember(&request);
+            let sent = self.shards[shard].try_send(request);
+            if sent.is_err() {
+                self.forget(shard, introduced);
+            }
+            return sent;
+        }
+        let (requests, gather) = fan_out(request, self.shards.len());
+        let mut failure = None;
+        let mut reached = 0;
+        for (shard, request) in self.shards.iter().zip(requests) {
+            match shard.try_send(request) {
+                Ok(()) => reached += 1,
+                Err(e) => failure = Some(e),
+            }
+        }
+        match failure {
+            Some(e) if reached == 0 => Err(e),
+            _ => {
+                tokio::spawn(gather);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Copies a request for each of `shards` shards, returning the copies along with a task that
+/// waits for every shard's replies and hands them, merged, to the original requester. A shard
+/// that never got its copy, or dropped it, counts as missed.
+fn fan_out(request: OmsRequest, shards: usize) -> (Vec<OmsRequest>, impl Future<Output = ()>) {
+    let (requests, replies): (Vec<_>, Vec<_>) = (0..shards)
+        .map(|_| {
+            let (reply_sender, reply) = oneshot::channel();
+            let copy = OmsRequest { message: request.message.clone(), reply_to: Some(ReplyTo::Request(reply_sender)), session: request.session.clone() };
+            (copy, reply)
+        })
+        .collect();
+    let reply_to = request.reply_to;
+    let gather = async move {
+        let mut merged = Vec::new();
+        let mut missed = 0;
+        for reply in replies {
+            match reply.await {
+                Ok(replies) => merge_replies(&mut merged, replies),
+                Err(_) => missed += 1,
+            }
+        }
+        if missed > 0 {
+            report_missed(&mut merged, missed, shards);
+        }
+        if let Some(reply_to) = reply_to {
+            reply_to.send(merged);
+        }
+    };
+    (requests, gather)
+}
+
+/// Adds one shard's replies to those of the shards before it. Mass cancel reports become one,
+/// counting the orders canceled on every shard.
+fn merge_replies(merged: &mut Vec<FixMessage>, replies: Vec<FixMessage>) {
+    for reply in replies {
+        let first = merged.iter_mut().find_map(|message| match message {
+            FixMessage::OrderMassCancelReport(report) => Some(report),
+            _ => None,
+        });
+        match (first, reply) {
+            (Some(report), FixMessage::OrderMassCancelReport(other)) => {
+                report.total_affected_orders = match (report.total_affected_orders, other.total_affected_orders) {
+                    (None, None) => None,
+                    (total, other) => Some(total.unwrap_or(0) + other.unwrap_or(0)),
+                };
+            }
+            (_, reply) => merged.push(reply),
+        }
+    }
+}
+
+/// Notes on the merged mass cancel report that some shards never acted on the request, so its
+/// count covers only part of the orders and the requester knows to send it again.
+fn report_missed(merged: &mut [FixMessage], missed: usize, shards: usize) {
+    let text = format!("{} of {} OMS shards did not take the request; send it again to cancel the rest", missed, shards);
+    eprintln!("Mass cancel reached only {} of {} OMS shards", shards - missed, shards);
+    for message in merged {
+        if let FixMessage::OrderMassCancelReport(report) = message {
+            report.text = Some(match report.text.take() {
+                Some(existing) => format!("{}; {}", existing, text),
+                None => text.clone(),
+            });
+        }
+    }
+}
+
+/// Forgets the ClOrdIDs of the shard's orders once they are done, keeping the dispatcher's map
+/// down to the working orders. Runs on the shard's OMS, after every message it answers and
+/// every report it sends unprompted.
+struct ShardIndexHandler {
+    shard: usize,
+    cl_ord_ids: ClOrdIdShards,
+}
+
+impl ShardIndexHandler {
+    /// Drops each of the ClOrdIDs that no longer names a working order of `session`.
+    fn forget_done(&self, session: Option<&str>, cl_ord_ids: &[&str], oms: &OrderManagementSystem) {
+        let mut shards = self.cl_ord_ids.lock().unwrap();
+        for cl_ord_id in cl_ord_ids {
+            let working = oms.orders().get_by_cl_ord_id(session, cl_ord_id).is_some_and(|order| !order.ord_status.is_terminal());
+            let key = (session.unwrap_or_default().to_string(), cl_ord_id.to_string());
+            if !working && shards.get(&key) == Some(&self.shard) {
+                shards.remove(&key);
+            }
+        }
+    }
+}
+
+impl FixMessageHandler for ShardIndexHandler {
+    fn name(&self) -> &str {
+        SHARD_INDEX
+    }
+
+    fn on_replies(&mut self, replies: &[FixMessage], _reply_to: Option<&ReplyTo>, oms: &OrderManagementSystem) {
+        for reply in replies {
+            match reply {
+                FixMessage::ExecutionReport(report) => {
+                    let orig_cl_ord_id = report.orig_cl_ord_id.as_deref().unwrap_or_default();
+                    self.forget_done(oms.session(), &[&report.cl_ord_id, orig_cl_ord_id], oms);
+                }
+                FixMessage::OrderCancelReject(reject) => self.forget_done(oms.session(), &[&reject.cl_ord_id], oms),
+                _ => {}
+            }
+        }
+    }
+
+    fn on_unsolicited(&mut self, order_id: &str, _message: &FixMessage, oms: &OrderManagementSystem) {
+        if let Some(order) = oms.orders().get(order_id).filter(|order| order.ord_status.is_terminal()) {
+            let orig_cl_ord_id = order.orig_cl_ord_id.as_deref().unwrap_or_default();
+            self.forget_done(order.session.as_deref(), &[&order.cl_ord_id, orig_cl_ord_id], oms);
+        }
+    }
+}
+
+/// OMS shards running on threads of their own.
+pub struct OmsShards {
+    shards: Vec<(oneshot::Sender<()>, JoinHandle<()>)>,
+}
+
+impl OmsShards {
+    /// Starts each OMS on its own thread, fed through a queue of `queue_capacity` messages, and
+    /// returns them with the dispatcher routing to them. The OMS at index `i` must be the one
+    /// rebuilt from shard `i`'s journal, holding the symbols `shard_of_symbol` gives to `i`.
+    pub fn start(systems: Vec<OrderManagementSystem>, queue_capacity: usize) -> io::Result<(OmsShards, OmsDispatcher)> {
+        let mut senders = Vec::new();
+        let mut cl_ord_ids = HashMap::new();
+        let mut shards = Vec::new();
+        for (index, oms) in systems.iter().enumerate() {
+            let working = oms.orders().cl_ord_ids().filter(|(session, cl_ord_id)| {
+                oms.orders().get_by_cl_ord_id(*session, cl_ord_id).is_some_and(|order| !order.ord_status.is_terminal())
+            });
+            cl_ord_ids.extend(working.map(|(session, cl_ord_id)| ((session.unwrap_or_default().to_string(), cl_ord_id.to_string()), index)));
+        }
+        let cl_ord_ids = Arc::new(Mutex::new(cl_ord_ids));
+        for (index, mut oms) in systems.into_iter().enumerate() {
+            oms.handlers_mut().push(ShardIndexHandler { shard: index, cl_ord_ids: cl_ord_ids.clone() });
+            let (sender, receiver) = mpsc::channel(queue_capacity);
+            let (stop, shutdown) = oneshot::channel();
+            // Matching and journal writes block, so each OMS gets its own thread and
+            // single-threaded runtime rather than a worker of the server's
+            let thread = std::thread::Builder::new().name(format!("oms-{}", index)).spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                runtime.block_on(oms.listen_for_orders(receiver, shutdown));
+            })?;
+            senders.push(sender);
+            shards.push((stop, thread));
+        }
+        let dispatcher = OmsDispatcher { shards: senders, cl_ord_ids };
+        Ok((OmsShards { shards }, dispatcher))
+    }
+
+    /// Stops every shard once it has answered what is already queued, and waits for them.
+    pub fn stop(self) {
+        let threads: Vec<_> = self.shards.into_iter().map(|(stop, thread)| {
+            let _ = stop.send(());
+            thread
+        }).collect();
+        for thread in threads {
+            if thread.join().is_err() {
+                eprintln!("An OMS shard panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use crate::oms::oms_request::ReplyTo;
+    use crate::config::server_config::RiskConfig;
+    use crate::oms::order_management_system::{DUPLICATE_ORDER, EXEC_TYPE_CANCELED, EXEC_TYPE_NEW, EXEC_TYPE_ORDER_STATUS, EXEC_TYPE_REJECTED, EXEC_TYPE_TRADE, ORDER_EXCEEDS_LIMIT};
+    use crate::oms::risk::{RiskChecks, SharedExposure};
+
+    async fn request(dispatcher: &OmsDispatcher, message: &str) -> Vec<(char, Option<u32>)> {
+        let (reply_sender, reply) = oneshot::channel();
+        let message = FixMessageParser::parse_message(message);
+        dispatcher.send(OmsRequest::new(message, ReplyTo::Request(reply_sender))).await.unwrap();
+        reply
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|reply| match reply {
+                FixMessage::ExecutionReport(report) => (report.exec_type, report.ord_rej_reason),
+                other => panic!("Expected an ExecutionReport, got {:?}", other),
+            })
+            .collect()
+    }
+
+    fn order(cl_ord_id: &str, side: char, symbol: &str) -> String {
+        format!("35=D|11={}|54={}|60=20230101-12:00:00|38=10|40=2|44=50|55={}|", cl_ord_id, side, symbol)
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_dispatched_by_symbol() {
+        let systems = (0..4).map(|_| OrderManagementSystem::new()).collect();
+        let (shards, dispatcher) = OmsShards::start(systems, 8).unwrap();
+        let symbols = ["AAA", "BBB", "CCC", "DDD", "EEE", "FFF"];
+        assert!(symbols.iter().map(|symbol| shard_of_symbol(symbol, 4)).collect::<std::collections::HashSet<_>>().len() > 1);
+
+        for (index, symbol) in symbols.iter().enumerate() {
+            assert_eq!(request(&dispatcher, &order(&format!("S{}", index), '2', symbol)).await, vec![(EXEC_TYPE_NEW, None)]);
+            let trade = request(&dispatcher, &order(&format!("B{}", index), '1', symbol)).await;
+            assert_eq!(trade.last().unwrap().0, EXEC_TYPE_TRADE, "orders in {} meet on one book", symbol);
+        }
+
+        request(&dispatcher, &order("S9", '2', "CCC")).await;
+        // The ClOrdID of a working order is taken, whatever the symbol
+        assert_eq!(request(&dispatcher, &order("S9", '2', "BBB")).await, vec![(EXEC_TYPE_REJECTED, Some(DUPLICATE_ORDER))]);
+        let cancel = request(&dispatcher, "35=F|11=X9|41=S9|54=2|55=CCC|60=20230101-12:00:00|").await;
+        assert_eq!(cancel, vec![(EXEC_TYPE_CANCELED, None)]);
+        let status = request(&dispatcher, "35=H|11=X9|54=2|55=CCC|60=20230101-12:00:00|").await;
+        assert_eq!(status[0].0, EXEC_TYPE_ORDER_STATUS);
+
+        // Every order is done, so once each shard has reported on its fills none is remembered
+        for symbol in symbols {
+            request(&dispatcher, &format!("35=H|11=NONE|54=2|55={}|60=20230101-12:00:00|", symbol)).await;
+        }
+        assert!(dispatcher.cl_ord_ids.lock().unwrap().is_empty());
+
+        drop(dispatcher);
+        shards.stop();
+    }
+
+    #[tokio::test]
+    async fn test_mass_cancel_reaches_every_shard_and_answers_once() {
+        let systems = (0..4).map(|_| OrderManagementSystem::new()).collect();
+        let (shards, dispatcher) = OmsShards::start(systems, 8).unwrap();
+        let symbols = ["AAA", "BBB", "CCC", "DDD", "EEE", "FFF"];
+        for (index, symbol) in symbols.iter().enumerate() {
+            request(&dispatcher, &order(&format!("S{}", index), '2', symbol)).await;
+        }
+
+        let (reply_sender, reply) = oneshot::channel();
+        let message = FixMessageParser::parse_message("35=q|11=M1|530=7|60=20230101-12:00:00|");
+        dispatcher.send(OmsRequest::new(message, ReplyTo::Request(reply_sender))).await.unwrap();
+        match reply.await.unwrap().as_slice() {
+            [FixMessage::OrderMassCancelReport(report)] => assert_eq!(report.total_affected_orders, Some(symbols.len() as u32)),
+            other => panic!("Expected one OrderMassCancelReport, got {:?}", other),
+        }
+
+        drop(dispatcher);
+        shards.stop();
+    }
+
+    #[tokio::test]
+    async fn test_account_limits_hold_across_shards() {
+        let config = RiskConfig { max_open_orders_per_account: Some(2), max_gross_exposure_per_account: Some(2500.0), ..RiskConfig::default() };
+        let exposure = SharedExposure::new();
+        let systems = (0..2)
+            .map(|shard| OrderManagementSystem::new().with_risk_checks(RiskChecks::new(&config).with_shared_exposure(exposure.clone(), shard)))
+            .collect();
+        let (shards, dispatcher) = OmsShards::start(systems, 8).unwrap();
+        let symbols = ["AAA", "BBB", "CCC", "DDD"];
+        let first = symbols.into_iter().find(|symbol| shard_of_symbol(symbol, 2) == 0).unwrap();
+        let second = symbols.into_iter().find(|symbol| shard_of_symbol(symbol, 2) == 1).unwrap();
+        let order = |cl_ord_id: &str, symbol: &str, order_qty: u32| {
+            format!("35=D|11={}|1=ACC1|54=1|60=20230101-12:00:00|38={}|40=2|44=50|55={}|", cl_ord_id, order_qty, symbol)
+        };
+
+        assert_eq!(request(&dispatcher, &order("A1", first, 10)).await, vec![(EXEC_TYPE_NEW, None)]);
+        assert_eq!(request(&dispatcher, &order("A2", second, 10)).await, vec![(EXEC_TYPE_NEW, None)]);
+        // Each shard holds one order, but the account holds two
+        assert_eq!(request(&dispatcher, &order("A3", first, 10)).await, vec![(EXEC_TYPE_REJECTED, Some(ORDER_EXCEEDS_LIMIT))]);
+        assert_eq!(request(&dispatcher, &order("A3", second, 10)).await, vec![(EXEC_TYPE_REJECTED, Some(ORDER_EXCEEDS_LIMIT))]);
+
+        // Canceling on one shard frees room on the other, up to the gross exposure left
+        let cancel = request(&dispatcher, &format!("35=F|11=X1|41=A1|54=1|55={}|60=20230101-12:00:00|", first)).await;
+        assert_eq!(cancel, vec![(EXEC_TYPE_CANCELED, None)]);
+        assert_eq!(request(&dispatcher, &order("A4", second, 41)).await, vec![(EXEC_TYPE_REJECTED, Some(ORDER_EXCEEDS_LIMIT))]);
+        assert_eq!(request(&dispatcher, &order("A4", first, 40)).await, vec![(EXEC_TYPE_NEW, None)]);
+
+        drop(dispatcher);
+        shards.stop();
+    }
+
+    #[tokio::test]
+    async fn test_requests_that_miss_a_full_queue() {
+        let (senders, mut receivers): (Vec<_>, Vec<_>) = (0..2).map(|_| mpsc::channel(1)).unzip();
+        let dispatcher = OmsDispatcher::new(senders);
+        let symbol = ["AAA", "BBB", "CCC", "DDD"].into_iter().find(|symbol| shard_of_symbol(symbol, 2) == 0).unwrap();
+        let unanswered = |message: &str| OmsRequest { message: FixMessageParser::parse_message(message), reply_to: None, session: None };
+        let remembered = |cl_ord_id: &str| dispatcher.cl_ord_ids.lock().unwrap().contains_key(&(String::new(), cl_ord_id.to_string()));
+
+        dispatcher.try_send(unanswered(&order("F1", '1', symbol))).unwrap();
+        assert!(matches!(dispatcher.try_send(unanswered(&order("F2", '1', symbol))), Err(TrySendError::Full(_))));
+        assert!(remembered("F1"));
+        assert!(!remembered("F2"), "an order that never reached its shard is not remembered");
+
+        // The mass cancel reaches the second shard only, and says so
+        let (reply_sender, reply) = oneshot::channel();
+        let message = FixMessageParser::parse_message("35=q|11=M1|530=7|60=20230101-12:00:00|");
+        dispatcher.try_send(OmsRequest::new(message, ReplyTo::Request(reply_sender))).unwrap();
+        let copy = receivers[1].recv().await.unwrap();
+        copy.reply_to.unwrap().send(vec![FixMessageParser::parse_message("35=r|11=M1|37=M1|530=7|531=7|533=2|")]);
+        match reply.await.unwrap().as_slice() {
+            [FixMessage::OrderMassCancelReport(report)] => {
+                assert_eq!(report.total_affected_orders, Some(2));
+                assert!(report.text.as_deref().unwrap().starts_with("1 of 2 OMS shards did not take the request"));
+            }
+            other => panic!("Expected one OrderMassCancelReport, got {:?}", other),
+        }
+        assert!(receivers[0].try_recv().is_ok());
+    }
+}
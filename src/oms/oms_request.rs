@@ -0,0 +1,56 @@
+use crate::fix::fix_message::FixMessage;
+use tokio::sync::{mpsc, oneshot};
+
+/// Where the OMS sends the messages it produces in response to a request.
+#[derive(Debug)]
+pub enum ReplyTo {
+    /// A one-off request such as an HTTP call, answered once with everything the message
+    /// produced.
+    Request(oneshot::Sender<Vec<FixMessage>>),
+    /// A long-lived WebSocket or FIX session that receives each message as it is produced.
+    Connection(mpsc::UnboundedSender<FixMessage>),
+}
+
+impl ReplyTo {
+    pub fn send(self, replies: Vec<FixMessage>) {
+        match self {
+            ReplyTo::Request(sender) => {
+                let _ = sender.send(replies);
+            }
+            ReplyTo::Connection(sender) => {
+                for reply in replies {
+                    if sender.send(reply).is_err() {
+                        eprintln!("Connection closed before the OMS could reply");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A message for the OMS, together with where its replies should go.
+#[derive(Debug)]
+pub struct OmsRequest {
+    pub message: FixMessage,
+    pub reply_to: Option<ReplyTo>,
+    /// CompID of the counterparty the message came from, over a FIX session, HTTP or a
+    /// WebSocket. Orders remember it, and it limits a mass cancel to that counterparty's orders.
+    pub session: Option<String>,
+}
+
+impl OmsRequest {
+    pub fn new(message: FixMessage, reply_to: ReplyTo) -> Self {
+        OmsRequest { message, reply_to: Some(reply_to), session: None }
+    }
+
+    /// A message whose replies nobody is waiting for.
+    pub fn without_reply(message: FixMessage) -> Self {
+        OmsRequest { message, reply_to: None, session: None }
+    }
+
+    pub fn with_session(mut self, comp_id: &str) -> Self {
+        self.session = Some(comp_id.to_string());
+        self
+    }
+}
@@ -0,0 +1,393 @@
+use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::new_order::NewOrder;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// OrdType (40) values
+pub const MARKET: char = '1';
+pub const LIMIT: char = '2';
+pub const STOP: char = '3';
+pub const STOP_LIMIT: char = '4';
+
+// Side (54) values
+pub const BUY: char = '1';
+pub const SELL: char = '2';
+
+// TimeInForce (59) values
+pub const DAY: char = '0';
+pub const GOOD_TILL_CANCEL: char = '1';
+pub const AT_THE_OPENING: char = '2';
+pub const IMMEDIATE_OR_CANCEL: char = '3';
+pub const FILL_OR_KILL: char = '4';
+pub const GOOD_TILL_DATE: char = '6';
+pub const AT_THE_CLOSE: char = '7';
+
+/// Smallest lot size the instrument master accepts.
+pub const MIN_LOT_SIZE: f64 = 1e-6;
+/// Quantities are f64, so fills of fractional lots leave rounding error behind. Quantities this
+/// close are the same, which is well below `MIN_LOT_SIZE`, so no real quantity is lost to it.
+pub const QTY_EPSILON: f64 = MIN_LOT_SIZE / 1000.0;
+
+/// OrdStatus (39) of an order as tracked by the OMS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrdStatus {
+    PendingNew,
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Replaced,
+    Rejected,
+    Expired,
+}
+
+impl OrdStatus {
+    pub fn as_char(self) -> char {
+        match self {
+            OrdStatus::PendingNew => 'A',
+            OrdStatus::New => '0',
+            OrdStatus::PartiallyFilled => '1',
+            OrdStatus::Filled => '2',
+            OrdStatus::Canceled => '4',
+            OrdStatus::Replaced => '5',
+            OrdStatus::Rejected => '8',
+            OrdStatus::Expired => 'C',
+        }
+    }
+
+    /// Terminal orders no longer work in the market and accept no further transitions.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, OrdStatus::Filled | OrdStatus::Canceled | OrdStatus::Replaced | OrdStatus::Rejected | OrdStatus::Expired)
+    }
+
+    pub fn can_transition_to(self, next: OrdStatus) -> bool {
+        use OrdStatus::*;
+        match self {
+            PendingNew => matches!(next, New | Rejected),
+            New | PartiallyFilled => matches!(next, PartiallyFilled | Filled | Canceled | Replaced | Expired),
+            Filled | Canceled | Replaced | Rejected | Expired => false,
+        }
+    }
+}
+
+impl fmt::Display for OrdStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// One entry in an order's history: the status it moved to and, for fills, what traded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub time: DateTime<Utc>,
+    pub ord_status: OrdStatus,
+    pub last_qty: Option<f64>,
+    pub last_px: Option<f64>,
+    pub text: Option<String>,
+}
+
+/// An order of record with its fill state and every status change it went through.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub order_id: String,
+    pub cl_ord_id: String,
+    /// ClOrdID the order had before its latest cancel or replace request.
+    pub orig_cl_ord_id: Option<String>,
+    pub account: Option<String>,
+    /// CompID of the counterparty that entered the order, if it came in through the server.
+    pub session: Option<String>,
+    /// SelfMatchPreventionID (2362) the order shares with those it must not trade against.
+    pub self_match_prevention_id: Option<String>,
+    pub symbol: String,
+    pub side: char,
+    pub ord_type: char,
+    pub price: Option<f64>,
+    /// Last traded price at or through which a stop order triggers.
+    pub stop_px: Option<f64>,
+    /// For a trailing stop, how far StopPx follows behind the last traded price.
+    pub peg_offset_value: Option<f64>,
+    pub order_qty: f64,
+    /// For an iceberg order, the most of its quantity the book shows at once.
+    pub max_floor: Option<f64>,
+    pub time_in_force: Option<char>,
+    /// When a Day or Good Till Date order leaves the market, as resolved by the OMS.
+    pub expire_time: Option<DateTime<Utc>>,
+    pub currency: Option<String>,
+    pub ord_status: OrdStatus,
+    pub cum_qty: f64,
+    pub leaves_qty: f64,
+    pub avg_px: f64,
+    pub events: Vec<OrderEvent>,
+}
+
+impl Order {
+    /// Creates the order in Pending New, before it has been accepted or rejected.
+    pub fn new(order_id: String, new_order: &NewOrder, time: DateTime<Utc>) -> Self {
+        Order {
+            order_id,
+            cl_ord_id: new_order.cl_ord_id.clone(),
+            orig_cl_ord_id: None,
+            account: new_order.account.clone(),
+            session: None,
+            self_match_prevention_id: new_order.self_match_prevention_id.clone(),
+            symbol: new_order.symbol.clone(),
+            side: new_order.side,
+            ord_type: new_order.ord_type,
+            price: new_order.price,
+            stop_px: new_order.stop_px,
+            peg_offset_value: new_order.peg_offset_value,
+            order_qty: new_order.order_qty,
+            max_floor: new_order.max_floor,
+            time_in_force: new_order.time_in_force,
+            expire_time: None,
+            currency: new_order.currency.clone(),
+            ord_status: OrdStatus::PendingNew,
+            cum_qty: 0.0,
+            leaves_qty: new_order.order_qty,
+            avg_px: 0.0,
+            events: vec![OrderEvent { time, ord_status: OrdStatus::PendingNew, last_qty: None, last_px: None, text: None }],
+        }
+    }
+
+    /// TimeInForce, with an absent tag meaning Day as FIX specifies.
+    pub fn time_in_force(&self) -> char {
+        self.time_in_force.unwrap_or(DAY)
+    }
+
+    /// Whether the order waits for the opening or closing auction instead of trading continuously.
+    pub fn is_auction_order(&self) -> bool {
+        matches!(self.time_in_force(), AT_THE_OPENING | AT_THE_CLOSE)
+    }
+
+    /// Whether the order waits off-book for the last traded price to reach its StopPx.
+    pub fn is_stop_order(&self) -> bool {
+        matches!(self.ord_type, STOP | STOP_LIMIT)
+    }
+
+    /// Whether a last traded price of `last_px` triggers this stop order: buy stops trigger at
+    /// or above StopPx, sell stops at or below.
+    pub fn is_triggered_by(&self, last_px: f64) -> bool {
+        match (self.is_stop_order(), self.stop_px) {
+            (true, Some(stop_px)) if self.side == BUY => last_px >= stop_px,
+            (true, Some(stop_px)) => last_px <= stop_px,
+            _ => false,
+        }
+    }
+
+    /// Moves a trailing stop's StopPx to PegOffsetValue away from `last_px`, if that tightens it
+    /// or no StopPx is set yet. Returns whether StopPx moved.
+    pub fn trail(&mut self, last_px: f64) -> bool {
+        let offset = match (self.is_stop_order(), self.peg_offset_value) {
+            (true, Some(offset)) => offset,
+            _ => return false,
+        };
+        let trailed = if self.side == BUY { last_px + offset } else { last_px - offset };
+        let tighter = match self.stop_px {
+            Some(stop_px) if self.side == BUY => trailed < stop_px,
+            Some(stop_px) => trailed > stop_px,
+            None => true,
+        };
+        if tighter {
+            self.stop_px = Some(trailed);
+        }
+        tighter
+    }
+
+    /// Converts a triggered stop into a market order, or a stop limit into a limit order.
+    pub fn trigger(&mut self, last_px: f64, time: DateTime<Utc>) -> Result<(), String> {
+        self.ord_type = match self.ord_type {
+            STOP => MARKET,
+            STOP_LIMIT => LIMIT,
+            other => return Err(format!("Order {} of OrdType {} is not a stop order", self.order_id, other)),
+        };
+        let text = format!("Stop triggered at last price {}", last_px);
+        self.record(self.ord_status, time, None, None, Some(text));
+        Ok(())
+    }
+
+    /// Moves the order to a status that does not involve a fill. Leaving the market zeroes
+    /// LeavesQty.
+    pub fn transition(&mut self, next: OrdStatus, time: DateTime<Utc>, text: Option<String>) -> Result<(), String> {
+        if matches!(next, OrdStatus::PartiallyFilled | OrdStatus::Filled) {
+            return Err(format!("Order {} can only become {} through a fill", self.order_id, next));
+        }
+        self.check_transition(next)?;
+        if next.is_terminal() {
+            self.leaves_qty = 0.0;
+        }
+        self.record(next, time, None, None, text);
+        Ok(())
+    }
+
+    /// Takes `qty` off both OrderQty and LeavesQty of a working order without a fill, as when
+    /// self-trade prevention decrements it. The order stays in its current status.
+    pub fn decrement(&mut self, qty: f64, time: DateTime<Utc>, text: String) -> Result<(), String> {
+        if self.ord_status.is_terminal() || qty <= 0.0 || qty >= self.leaves_qty - QTY_EPSILON {
+            return Err(format!("Cannot decrement order {} with {} leaves by {}", self.order_id, self.leaves_qty, qty));
+        }
+        self.order_qty -= qty;
+        self.leaves_qty -= qty;
+        self.record(self.ord_status, time, None, None, Some(text));
+        Ok(())
+    }
+
+    /// Applies an execution of `last_qty` at `last_px`, updating CumQty, LeavesQty and AvgPx. A
+    /// fill that leaves no more than rounding error fills the order.
+    pub fn fill(&mut self, last_qty: f64, last_px: f64, time: DateTime<Utc>) -> Result<(), String> {
+        if last_qty <= 0.0 || last_qty > self.leaves_qty + QTY_EPSILON {
+            return Err(format!("Fill of {} on order {} with {} leaves", last_qty, self.order_id, self.leaves_qty));
+        }
+        let leaves_qty = self.leaves_qty - last_qty;
+        let next = if leaves_qty < QTY_EPSILON { OrdStatus::Filled } else { OrdStatus::PartiallyFilled };
+        self.check_transition(next)?;
+
+        let cum_qty = self.cum_qty + last_qty;
+        self.avg_px = (self.avg_px * self.cum_qty + last_px * last_qty) / cum_qty;
+        self.cum_qty = cum_qty;
+        self.leaves_qty = if next == OrdStatus::Filled { 0.0 } else { leaves_qty };
+        self.record(next, time, Some(last_qty), Some(last_px), None);
+        Ok(())
+    }
+
+    /// Reports the order's current state as an ExecutionReport of the given ExecType (150).
+    pub fn execution_report(&self, exec_id: String, exec_type: char, transact_time: String) -> ExecutionReport {
+        ExecutionReport {
+            cl_ord_id: self.cl_ord_id.clone(),
+            order_id: self.order_id.clone(),
+            exec_id,
+            exec_type,
+            ord_status: self.ord_status.as_char(),
+            side: self.side,
+            leaves_qty: self.leaves_qty,
+            cum_qty: self.cum_qty,
+            avg_px: Some(self.avg_px),
+            symbol: self.symbol.clone(),
+            transact_time,
+            order_qty: self.order_qty,
+            price: self.price,
+            ord_type: Some(self.ord_type),
+            stop_px: self.stop_px,
+            max_floor: self.max_floor,
+            // A fresh slice, as the order shows once it rests; a resting order may show less
+            display_qty: self.max_floor.map(|max_floor| max_floor.min(self.leaves_qty)),
+            exec_trans_type: Some('0'),
+            account: self.account.clone(),
+            orig_cl_ord_id: self.orig_cl_ord_id.clone(),
+            ord_rej_reason: None,
+            last_qty: None,
+            last_px: None,
+            text: self.events.last().and_then(|event| event.text.clone()),
+        }
+    }
+
+    fn check_transition(&self, next: OrdStatus) -> Result<(), String> {
+        if self.ord_status.can_transition_to(next) {
+            Ok(())
+        } else {
+            Err(format!("Order {} cannot go from {} to {}", self.order_id, self.ord_status, next))
+        }
+    }
+
+    fn record(&mut self, ord_status: OrdStatus, time: DateTime<Utc>, last_qty: Option<f64>, last_px: Option<f64>, text: Option<String>) {
+        self.ord_status = ord_status;
+        self.events.push(OrderEvent { time, ord_status, last_qty, last_px, text });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message::FixMessage;
+    use crate::fix::fix_message_parser::FixMessageParser;
+
+    fn new_order(cl_ord_id: &str, order_qty: f64) -> NewOrder {
+        let message = format!("35=D|11={}|54=1|60=20230101-12:00:00|38={}|40=2|44=50.5|55=XYZ|", cl_ord_id, order_qty);
+        match FixMessageParser::parse_message(&message) {
+            FixMessage::NewOrder(order) => order,
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        }
+    }
+
+    fn accepted_order(order_qty: f64) -> Order {
+        let mut order = Order::new("O1".to_string(), &new_order("C1", order_qty), Utc::now());
+        order.transition(OrdStatus::New, Utc::now(), None).unwrap();
+        order
+    }
+
+    #[test]
+    fn test_partial_fills_update_quantities_and_average_price() {
+        let mut order = accepted_order(100.0);
+
+        order.fill(40.0, 10.0, Utc::now()).unwrap();
+        assert_eq!(order.ord_status, OrdStatus::PartiallyFilled);
+        order.fill(60.0, 11.0, Utc::now()).unwrap();
+
+        assert_eq!(order.ord_status, OrdStatus::Filled);
+        assert_eq!(order.cum_qty, 100.0);
+        assert_eq!(order.leaves_qty, 0.0);
+        assert!((order.avg_px - 10.6).abs() < 1e-9);
+        let history: Vec<_> = order.events.iter().map(|event| event.ord_status).collect();
+        assert_eq!(history, vec![OrdStatus::PendingNew, OrdStatus::New, OrdStatus::PartiallyFilled, OrdStatus::Filled]);
+    }
+
+    #[test]
+    fn test_fills_of_fractional_lots_fill_the_order() {
+        let mut order = accepted_order(0.3);
+
+        for _ in 0..3 {
+            order.fill(0.1, 10.0, Utc::now()).unwrap();
+        }
+
+        assert_eq!(order.ord_status, OrdStatus::Filled);
+        assert_eq!(order.leaves_qty, 0.0);
+    }
+
+    #[test]
+    fn test_cancel_zeroes_leaves_qty() {
+        let mut order = accepted_order(100.0);
+        order.fill(40.0, 10.0, Utc::now()).unwrap();
+
+        order.transition(OrdStatus::Canceled, Utc::now(), Some("Client request".to_string())).unwrap();
+
+        assert_eq!(order.cum_qty, 40.0);
+        assert_eq!(order.leaves_qty, 0.0);
+    }
+
+    #[test]
+    fn test_invalid_transitions_are_refused() {
+        let mut pending = Order::new("O1".to_string(), &new_order("C1", 100.0), Utc::now());
+        assert!(pending.fill(10.0, 10.0, Utc::now()).is_err(), "cannot fill before the order is accepted");
+        assert!(pending.transition(OrdStatus::Canceled, Utc::now(), None).is_err());
+
+        let mut order = accepted_order(100.0);
+        assert!(order.fill(150.0, 10.0, Utc::now()).is_err(), "cannot overfill");
+        order.transition(OrdStatus::Canceled, Utc::now(), None).unwrap();
+        assert_eq!(
+            order.transition(OrdStatus::New, Utc::now(), None).unwrap_err(),
+            "Order O1 cannot go from Canceled to New"
+        );
+        assert!(order.fill(10.0, 10.0, Utc::now()).is_err());
+        assert_eq!(order.events.len(), 3);
+    }
+
+    #[test]
+    fn test_trailing_sell_stop_follows_the_price_up_and_triggers() {
+        let message = "35=D|11=C1|54=2|60=20230101-12:00:00|38=100|40=4|44=95|99=95|211=5|55=XYZ|";
+        let mut order = match FixMessageParser::parse_message(message) {
+            FixMessage::NewOrder(new_order) => Order::new("O1".to_string(), &new_order, Utc::now()),
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        };
+
+        assert!(!order.trail(99.0), "a looser stop is not taken");
+        assert!(order.trail(102.0));
+        assert_eq!(order.stop_px, Some(97.0));
+        assert!(!order.is_triggered_by(98.0));
+        assert!(order.is_triggered_by(97.0));
+
+        order.trigger(97.0, Utc::now()).unwrap();
+        assert_eq!(order.ord_type, LIMIT);
+        assert!(!order.is_stop_order());
+        assert!(order.trigger(97.0, Utc::now()).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::oms::order::{BUY, QTY_EPSILON};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Book key giving prices a total order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RestingOrder {
+    order_id: String,
+    /// Quantity shown in the book, which for an iceberg order is its current slice.
+    leaves_qty: f64,
+    /// Quantity of an iceberg order held back to replenish its slice.
+    #[serde(default)]
+    hidden_qty: f64,
+    max_floor: Option<f64>,
+}
+
+/// A match between an incoming order and one resting order, at the resting order's price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub maker_order_id: String,
+    pub price: f64,
+    pub qty: f64,
+    /// What the resting order shows after the fill: the rest of its slice, a fresh slice for an
+    /// iceberg order that had more hidden, or nothing once it leaves the book.
+    pub displayed_qty: f64,
+}
+
+/// Aggregated quantity resting at one price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookLevel {
+    pub price: f64,
+    pub qty: f64,
+    pub orders: usize,
+}
+
+/// Central limit order book for one symbol. Orders match by price, then by arrival time within
+/// a price level.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, VecDeque<RestingOrder>>,
+    asks: BTreeMap<Price, VecDeque<RestingOrder>>,
+    locations: HashMap<String, (char, Price)>,
+    last_price: Option<f64>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quantity an order could take from the opposite side right now, capped at `qty`.
+    pub fn fillable_qty(&self, side: char, limit: Option<f64>, qty: f64) -> f64 {
+        let available: f64 = self.crossing(side, limit).map(|(_, qty)| qty).sum();
+        available.min(qty)
+    }
+
+    /// Resting orders an order on `side` could trade with, by price then time, each with all of
+    /// its quantity, shown or hidden.
+    pub fn crossing(&self, side: char, limit: Option<f64>) -> impl Iterator<Item = (&str, f64)> {
+        let levels: Box<dyn Iterator<Item = (&Price, &VecDeque<RestingOrder>)>> = match side {
+            BUY => Box::new(self.asks.iter()),
+            _ => Box::new(self.bids.iter().rev()),
+        };
+        levels
+            .take_while(move |(price, _)| crosses(side, limit, price.0))
+            .flat_map(|(_, queue)| queue.iter().map(|resting| (resting.order_id.as_str(), resting.leaves_qty + resting.hidden_qty)))
+    }
+
+    /// Resting order the next trade of an order on `side` would be against, if any.
+    pub fn next_match(&self, side: char, limit: Option<f64>) -> Option<&str> {
+        self.crossing(side, limit).next().map(|(order_id, _)| order_id)
+    }
+
+    /// Takes up to `qty` from the opposite side, best price first, never trading through
+    /// `limit`; a market order passes no limit. Filled resting orders leave the book. An iceberg
+    /// order whose slice fills shows a fresh one from its hidden quantity, behind the orders
+    /// already at its price.
+    pub fn match_order(&mut self, side: char, limit: Option<f64>, qty: f64) -> Vec<Fill> {
+        self.match_order_until(side, limit, qty, |_| false)
+    }
+
+    /// Matches like `match_order`, but stops short of the first resting order for which `stop`
+    /// holds, leaving it to be dealt with before matching goes on.
+    pub fn match_order_until(&mut self, side: char, limit: Option<f64>, qty: f64, stop: impl Fn(&str) -> bool) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut remaining = qty;
+
+        while remaining > QTY_EPSILON {
+            let best = match side {
+                BUY => self.asks.first_entry(),
+                _ => self.bids.last_entry(),
+            };
+            let mut level = match best {
+                Some(level) => level,
+                None => break,
+            };
+            let price = level.key().0;
+            if !crosses(side, limit, price) {
+                break;
+            }
+            self.last_price = Some(price);
+
+            let queue = level.get_mut();
+            while remaining > QTY_EPSILON {
+                let resting = match queue.front_mut() {
+                    Some(resting) if stop(&resting.order_id) => return fills,
+                    Some(resting) => resting,
+                    None => break,
+                };
+                let traded = remaining.min(resting.leaves_qty);
+                remaining -= traded;
+                resting.leaves_qty -= traded;
+                if resting.leaves_qty > QTY_EPSILON {
+                    fills.push(Fill { maker_order_id: resting.order_id.clone(), price, qty: traded, displayed_qty: resting.leaves_qty });
+                    continue;
+                }
+                let mut filled = queue.pop_front().expect("front checked above");
+                if filled.hidden_qty > QTY_EPSILON {
+                    let slice = filled.max_floor.map_or(filled.hidden_qty, |max_floor| max_floor.min(filled.hidden_qty));
+                    filled.leaves_qty = slice;
+                    filled.hidden_qty -= slice;
+                    fills.push(Fill { maker_order_id: filled.order_id.clone(), price, qty: traded, displayed_qty: slice });
+                    queue.push_back(filled);
+                } else {
+                    fills.push(Fill { maker_order_id: filled.order_id.clone(), price, qty: traded, displayed_qty: 0.0 });
+                    self.locations.remove(&filled.order_id);
+                }
+            }
+            if queue.is_empty() {
+                level.remove();
+            }
+        }
+        fills
+    }
+
+    /// Rests an order at the back of its price level.
+    pub fn add(&mut self, order_id: &str, side: char, price: f64, qty: f64) {
+        self.rest(side, price, RestingOrder { order_id: order_id.to_string(), leaves_qty: qty, hidden_qty: 0.0, max_floor: None });
+    }
+
+    /// Rests an iceberg order at the back of its price level, showing no more than `max_floor`
+    /// of `qty` at a time.
+    pub fn add_iceberg(&mut self, order_id: &str, side: char, price: f64, qty: f64, max_floor: f64) {
+        let slice = max_floor.min(qty);
+        let resting = RestingOrder { order_id: order_id.to_string(), leaves_qty: slice, hidden_qty: qty - slice, max_floor: Some(max_floor) };
+        self.rest(side, price, resting);
+    }
+
+    fn rest(&mut self, side: char, price: f64, resting: RestingOrder) {
+        let levels = match side {
+            BUY => &mut self.bids,
+            _ => &mut self.asks,
+        };
+        self.locations.insert(resting.order_id.clone(), (side, Price(price)));
+        levels.entry(Price(price)).or_default().push_back(resting);
+    }
+
+    /// Takes an order off the book, returning whether it was resting.
+    pub fn remove(&mut self, order_id: &str) -> bool {
+        let (side, price) = match self.locations.remove(order_id) {
+            Some(location) => location,
+            None => return false,
+        };
+        let levels = match side {
+            BUY => &mut self.bids,
+            _ => &mut self.asks,
+        };
+        if let Some(queue) = levels.get_mut(&price) {
+            queue.retain(|resting| resting.order_id != order_id);
+            if queue.is_empty() {
+                levels.remove(&price);
+            }
+        }
+        true
+    }
+
+    /// Takes `qty` off a resting order without trading it, from its hidden quantity first. The
+    /// order keeps its place and leaves the book once nothing is left. Returns whether it was
+    /// resting.
+    pub fn reduce(&mut self, order_id: &str, qty: f64) -> bool {
+        let (side, price) = match self.locations.get(order_id) {
+            Some(location) => *location,
+            None => return false,
+        };
+        let levels = match side {
+            BUY => &mut self.bids,
+            _ => &mut self.asks,
+        };
+        let resting = match levels.get_mut(&price).and_then(|queue| queue.iter_mut().find(|resting| resting.order_id == order_id)) {
+            Some(resting) => resting,
+            None => return false,
+        };
+        let hidden = qty.min(resting.hidden_qty);
+        resting.hidden_qty -= hidden;
+        resting.leaves_qty = (resting.leaves_qty - (qty - hidden)).max(0.0);
+        if resting.leaves_qty <= QTY_EPSILON {
+            self.remove(order_id);
+        }
+        true
+    }
+
+    pub fn contains(&self, order_id: &str) -> bool {
+        self.locations.contains_key(order_id)
+    }
+
+    /// Quantity a resting order shows, or `None` if it is not on the book.
+    pub fn displayed_qty(&self, order_id: &str) -> Option<f64> {
+        let (side, price) = self.locations.get(order_id)?;
+        let levels = match *side {
+            BUY => &self.bids,
+            _ => &self.asks,
+        };
+        levels.get(price)?.iter().find(|resting| resting.order_id == order_id).map(|resting| resting.leaves_qty)
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|price| price.0)
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|price| price.0)
+    }
+
+    /// Price levels on one side, best first, counting only what each order shows.
+    pub fn depth(&self, side: char) -> Vec<BookLevel> {
+        let level = |(price, queue): (&Price, &VecDeque<RestingOrder>)| BookLevel {
+            price: price.0,
+            qty: queue.iter().map(|resting| resting.leaves_qty).sum(),
+            orders: queue.len(),
+        };
+        match side {
+            BUY => self.bids.iter().rev().map(level).collect(),
+            _ => self.asks.iter().map(level).collect(),
+        }
+    }
+
+    /// Price of the most recent trade in this symbol, continuous or auction.
+    pub fn last_price(&self) -> Option<f64> {
+        self.last_price
+    }
+
+    pub fn record_trade(&mut self, price: f64) {
+        self.last_price = Some(price);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+/// Whether an order on `side` with `limit` may trade at `price`.
+fn crosses(side: char, limit: Option<f64>, price: f64) -> bool {
+    match (side, limit) {
+        (_, None) => true,
+        (BUY, Some(limit)) => price <= limit,
+        (_, Some(limit)) => price >= limit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oms::order::SELL;
+
+    fn fill(maker_order_id: &str, price: f64, qty: f64, displayed_qty: f64) -> Fill {
+        Fill { maker_order_id: maker_order_id.to_string(), price, qty, displayed_qty }
+    }
+
+    #[test]
+    fn test_price_then_time_priority() {
+        let mut book = OrderBook::new();
+        book.add("S1", SELL, 101.0, 50.0);
+        book.add("S2", SELL, 100.0, 50.0);
+        book.add("S3", SELL, 100.0, 50.0);
+
+        let fills = book.match_order(BUY, Some(101.0), 120.0);
+
+        assert_eq!(fills, vec![fill("S2", 100.0, 50.0, 0.0), fill("S3", 100.0, 50.0, 0.0), fill("S1", 101.0, 20.0, 30.0)]);
+        assert_eq!(book.depth(SELL), vec![BookLevel { price: 101.0, qty: 30.0, orders: 1 }]);
+        assert!(!book.contains("S2"));
+    }
+
+    #[test]
+    fn test_limit_stops_matching_and_market_sweeps() {
+        let mut book = OrderBook::new();
+        book.add("B1", BUY, 99.0, 10.0);
+        book.add("B2", BUY, 98.0, 10.0);
+
+        assert!(book.match_order(SELL, Some(99.5), 10.0).is_empty());
+        assert_eq!(book.match_order(SELL, None, 15.0), vec![fill("B1", 99.0, 10.0, 0.0), fill("B2", 98.0, 5.0, 5.0)]);
+        assert_eq!(book.best_bid(), Some(98.0));
+        assert_eq!(book.best_ask(), None);
+        assert_eq!(book.last_price(), Some(98.0));
+    }
+
+    #[test]
+    fn test_iceberg_shows_one_slice_and_replenishes_behind_the_level() {
+        let mut book = OrderBook::new();
+        book.add_iceberg("S1", SELL, 100.0, 250.0, 100.0);
+        book.add("S2", SELL, 100.0, 50.0);
+        assert_eq!(book.depth(SELL), vec![BookLevel { price: 100.0, qty: 150.0, orders: 2 }]);
+        assert_eq!(book.fillable_qty(BUY, Some(100.0), 1000.0), 300.0);
+
+        // The slice fills and the fresh one queues behind S2
+        let fills = book.match_order(BUY, Some(100.0), 160.0);
+        assert_eq!(fills, vec![fill("S1", 100.0, 100.0, 100.0), fill("S2", 100.0, 50.0, 0.0), fill("S1", 100.0, 10.0, 90.0)]);
+        assert_eq!(book.displayed_qty("S1"), Some(90.0));
+
+        // The last slice is what is left of the hidden quantity
+        assert_eq!(book.match_order(BUY, None, 200.0), vec![fill("S1", 100.0, 90.0, 50.0), fill("S1", 100.0, 50.0, 0.0)]);
+        assert!(book.is_empty());
+    }
+
+    #[test]
+    fn test_fillable_qty_leaves_the_book_untouched() {
+        let mut book = OrderBook::new();
+        book.add("S1", SELL, 100.0, 10.0);
+        book.add("S2", SELL, 101.0, 10.0);
+
+        assert_eq!(book.fillable_qty(BUY, Some(100.0), 15.0), 10.0);
+        assert_eq!(book.fillable_qty(BUY, Some(101.0), 15.0), 15.0);
+        assert_eq!(book.fillable_qty(BUY, None, 50.0), 20.0);
+        assert_eq!(book.fillable_qty(SELL, None, 50.0), 0.0);
+        assert_eq!(book.depth(SELL).len(), 2);
+    }
+
+    #[test]
+    fn test_matching_stops_short_of_an_order_and_reduce_keeps_its_place() {
+        let mut book = OrderBook::new();
+        book.add("S1", SELL, 100.0, 10.0);
+        book.add_iceberg("S2", SELL, 100.0, 30.0, 10.0);
+        book.add("S3", SELL, 100.0, 10.0);
+
+        assert_eq!(book.match_order_until(BUY, None, 50.0, |order_id| order_id == "S2"), vec![fill("S1", 100.0, 10.0, 0.0)]);
+        assert_eq!(book.next_match(BUY, None), Some("S2"));
+        assert!(book.reduce("S2", 25.0));
+        assert_eq!(book.displayed_qty("S2"), Some(5.0));
+        assert_eq!(book.crossing(BUY, None).collect::<Vec<_>>(), vec![("S2", 5.0), ("S3", 10.0)]);
+        assert!(book.reduce("S2", 5.0));
+        assert!(!book.contains("S2"));
+    }
+
+    #[test]
+    fn test_depth_and_remove() {
+        let mut book = OrderBook::new();
+        book.add("B1", BUY, 99.0, 10.0);
+        book.add("B2", BUY, 100.0, 5.0);
+        book.add("B3", BUY, 100.0, 7.0);
+
+        assert_eq!(book.depth(BUY), vec![
+            BookLevel { price: 100.0, qty: 12.0, orders: 2 },
+            BookLevel { price: 99.0, qty: 10.0, orders: 1 },
+        ]);
+
+        assert!(book.remove("B2"));
+        assert!(!book.remove("B2"));
+        assert_eq!(book.depth(BUY)[0], BookLevel { price: 100.0, qty: 7.0, orders: 1 });
+        book.remove("B3");
+        assert_eq!(book.best_bid(), Some(99.0));
+    }
+}
@@ -1,41 +1,1961 @@
-use crossbeam::channel::Receiver;
+use crate::config::server_config::{SelfTradeKey, SelfTradeMode, SelfTradePrevention};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::oneshot;
+use tokio::time::MissedTickBehavior;
 use crate::fix::{fix_message::FixMessage, messages::new_order::NewOrder};
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::order_cancel_reject::OrderCancelReject;
+use crate::fix::messages::order_cancel_request::OrderCancelRequest;
+use crate::fix::messages::order_mass_cancel_report::OrderMassCancelReport;
+use crate::fix::messages::order_mass_cancel_request::OrderMassCancelRequest;
+use crate::fix::messages::order_status_request::OrderStatusRequest;
+use crate::oms::auction::{uncross, AuctionOrder};
+use crate::oms::gtc_store::GtcStore;
+use crate::oms::id_generator::{IdGenerator, IdStore, NextIds};
+use crate::oms::journal::{Journal, JournalEntry, JournalEvent};
+use crate::oms::instruments::InstrumentMaster;
+use crate::oms::message_handler::{HandlerRegistry, InstrumentHandler, RiskHandler, MATCHING, RISK};
+use crate::oms::oms_request::OmsRequest;
+use crate::oms::order::{OrdStatus, Order, LIMIT, MARKET, STOP, STOP_LIMIT, AT_THE_CLOSE, AT_THE_OPENING, DAY, FILL_OR_KILL, GOOD_TILL_CANCEL, GOOD_TILL_DATE, IMMEDIATE_OR_CANCEL};
+use crate::oms::order_book::OrderBook;
+use crate::oms::order_store::OrderStore;
+use crate::oms::positions::Positions;
+use crate::oms::risk::{valuation_price, AccountExposure, RiskChecks};
+use crate::oms::snapshot::{OmsState, SnapshotStore};
+use crate::session::session_clock::UTC_TIMESTAMP_FORMAT;
+use crate::session::session_schedule::SessionSchedule;
+
+// ExecType (150) values
+pub const EXEC_TYPE_NEW: char = '0';
+pub const EXEC_TYPE_CANCELED: char = '4';
+pub const EXEC_TYPE_RESTATED: char = 'D';
+pub const EXEC_TYPE_REJECTED: char = '8';
+pub const EXEC_TYPE_EXPIRED: char = 'C';
+pub const EXEC_TYPE_TRADE: char = 'F';
+/// Triggered or Activated by System, as introduced in FIX 4.4.
+pub const EXEC_TYPE_TRIGGERED: char = 'L';
+pub const EXEC_TYPE_ORDER_STATUS: char = 'I';
+
+// OrdRejReason (103) values
+pub const UNKNOWN_SYMBOL: u32 = 1;
+pub const EXCHANGE_CLOSED: u32 = 2;
+pub const ORDER_EXCEEDS_LIMIT: u32 = 3;
+pub const UNKNOWN_ORDER: u32 = 5;
+pub const DUPLICATE_ORDER: u32 = 6;
+pub const INCORRECT_QUANTITY: u32 = 13;
+/// Price exceeds current price band, as introduced in FIX 5.0.
+pub const PRICE_EXCEEDS_BAND: u32 = 16;
+/// Invalid price increment, as introduced in FIX 5.0.
+pub const INVALID_PRICE_INCREMENT: u32 = 18;
+pub const OTHER: u32 = 99;
+
+// CxlRejReason (102) values
+pub const TOO_LATE_TO_CANCEL: u32 = 0;
+pub const UNKNOWN_ORDER_TO_CANCEL: u32 = 1;
+pub const DUPLICATE_CL_ORD_ID: u32 = 6;
+pub const CXL_REJ_OTHER: u32 = 99;
+
+// MassCancelRequestType (530) values
+pub const MASS_CANCEL_SECURITY: char = '1';
+pub const MASS_CANCEL_ALL: char = '7';
+
+/// MassCancelResponse (531) of a rejected request; otherwise it repeats the request type.
+pub const MASS_CANCEL_REJECTED: char = '0';
+
+// MassCancelRejectReason (532) values
+pub const MASS_CANCEL_NOT_SUPPORTED: u32 = 0;
+pub const INVALID_SECURITY: u32 = 1;
+
+/// CxlRejResponseTo (434) for an OrderCancelRequest.
+const RESPONSE_TO_CANCEL_REQUEST: char = '1';
+
+/// OrderID reported when a rejected order was never assigned one.
+pub const NO_ORDER_ID: &str = "NONE";
+
+const ORDER_ID_PREFIX: &str = "ORD";
+const EXEC_ID_PREFIX: &str = "EXEC";
+
+/// How often the OMS wakes up without a message to run auctions and expire orders.
+const TIMER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 pub struct OrderManagementSystem {
-    receiver: Receiver<FixMessage>,
+    orders: OrderStore,
+    books: HashMap<String, OrderBook>,
+    /// Chain every inbound message runs through, from logging and risk checks to matching.
+    handlers: HandlerRegistry,
+    /// Reports produced for orders other than the requester's, keyed by OrderID.
+    unsolicited: Vec<(String, FixMessage)>,
+    /// Trading hours driving the auctions and Day order expiry.
+    schedule: Option<SessionSchedule>,
+    /// Keeps orders of the same owner from trading with each other in continuous matching.
+    self_trade_prevention: Option<SelfTradePrevention>,
+    /// Opening and closing auction orders waiting for their auction, in arrival order.
+    auction_orders: Vec<String>,
+    /// Stop orders waiting off-book for the last traded price to reach them, in arrival order.
+    stop_orders: Vec<String>,
+    last_timer: Option<DateTime<Utc>>,
+    gtc_store: Option<GtcStore>,
+    /// Whether a Good Till Cancel order changed since the store was last written.
+    gtc_dirty: bool,
+    /// Shared with the HTTP API, which reads positions while the OMS fills orders.
+    positions: Arc<Mutex<Positions>>,
+    journal: Option<Journal>,
+    snapshots: Option<SnapshotStore>,
+    snapshot_interval: Duration,
+    /// When the last snapshot was written and the journal offset it covered.
+    last_snapshot: Option<(DateTime<Utc>, u64)>,
+    /// Time of the message or timer tick being processed, taken from the journal on replay.
+    now: DateTime<Utc>,
+    /// Counterparty the message being processed came from, taken from the journal on replay.
+    session: Option<String>,
+    order_ids: IdGenerator,
+    exec_ids: IdGenerator,
+    /// Keeps the next ID numbers across restarts, saved before any reply carrying a new ID goes out.
+    id_store: Option<IdStore>,
 }
 
 impl OrderManagementSystem {
-    pub fn new(receiver: Receiver<FixMessage>) -> Self {
-        OrderManagementSystem { receiver }
+    pub fn new() -> Self {
+        OrderManagementSystem {
+            orders: OrderStore::new(),
+            books: HashMap::new(),
+            handlers: HandlerRegistry::standard(),
+            unsolicited: Vec::new(),
+            schedule: None,
+            self_trade_prevention: None,
+            auction_orders: Vec::new(),
+            stop_orders: Vec::new(),
+            last_timer: None,
+            gtc_store: None,
+            gtc_dirty: false,
+            positions: Arc::new(Mutex::new(Positions::new())),
+            journal: None,
+            snapshots: None,
+            snapshot_interval: Duration::zero(),
+            last_snapshot: None,
+            now: Utc::now(),
+            session: None,
+            order_ids: IdGenerator::new(ORDER_ID_PREFIX, 0),
+            exec_ids: IdGenerator::new(EXEC_ID_PREFIX, 0),
+            id_store: None,
+        }
+    }
+
+    pub fn with_schedule(mut self, schedule: SessionSchedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    pub fn with_self_trade_prevention(mut self, prevention: SelfTradePrevention) -> Self {
+        self.self_trade_prevention = Some(prevention);
+        self
+    }
+
+    /// Assigns OrderIDs `{prefix}ORD{number}` and ExecIDs `{prefix}EXEC{number}`, the numbers
+    /// zero-padded to `width` digits. Shards need a prefix of their own to keep their IDs apart.
+    /// Must be set before the ID store, GTC store or journal.
+    pub fn with_id_format(mut self, prefix: &str, width: usize) -> Self {
+        self.order_ids = IdGenerator::new(&format!("{}{}", prefix, ORDER_ID_PREFIX), width);
+        self.exec_ids = IdGenerator::new(&format!("{}{}", prefix, EXEC_ID_PREFIX), width);
+        self
+    }
+
+    /// Carries on numbering IDs after those saved in `store`, and keeps it up to date from then
+    /// on. A journal replays the IDs it recorded regardless, and the store then moves numbering
+    /// on if it is ahead, e.g. when the journal was started afresh.
+    pub fn with_id_store(mut self, mut store: IdStore) -> Result<Self, String> {
+        if let Some(next) = store.load().map_err(|e| format!("Failed to load the ID store: {}", e))? {
+            println!("Resuming at OrderID {} and ExecID {}", next.next_order_id, next.next_exec_id);
+        }
+        self.id_store = Some(store);
+        self.resume_ids();
+        Ok(self)
+    }
+
+    /// Checks new orders against `risk` by replacing the risk handler, or adding one at the end
+    /// of the chain if it was removed.
+    pub fn with_risk_checks(mut self, risk: RiskChecks) -> Self {
+        self.handlers.set(RiskHandler::new(risk));
+        self
+    }
+
+    /// Rejects orders for symbols missing from `instruments` or not fitting their reference
+    /// data, ahead of the risk checks.
+    pub fn with_instruments(mut self, instruments: Arc<InstrumentMaster>) -> Self {
+        let handler = InstrumentHandler::new(instruments);
+        let before = [RISK, MATCHING].into_iter().find(|name| self.handlers.names().contains(name));
+        match before {
+            Some(name) => self.handlers.insert_before(name, handler).expect("handler looked up above"),
+            None => self.handlers.push(handler),
+        }
+        self
+    }
+
+    /// Runs inbound messages through `handlers` instead of the standard chain.
+    pub fn with_handlers(mut self, handlers: HandlerRegistry) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    pub fn handlers_mut(&mut self) -> &mut HandlerRegistry {
+        &mut self.handlers
+    }
+
+    /// Puts the Good Till Cancel orders saved in `store` back on their books and keeps the
+    /// store up to date from then on.
+    pub fn with_gtc_store(mut self, store: GtcStore) -> Result<Self, String> {
+        let orders = store.load(Utc::now()).map_err(|e| format!("Failed to load GTC orders: {}", e))?;
+        println!("Restoring {} GTC orders", orders.len());
+        for order in orders {
+            if let Some(number) = self.order_number(&order.order_id) {
+                self.order_ids.resume(number + 1);
+            }
+            if order.is_stop_order() {
+                self.stop_orders.push(order.order_id.clone());
+            } else if let Some(price) = order.price {
+                rest(self.books.entry(order.symbol.clone()).or_default(), &order, price);
+            }
+            self.orders.insert(order)?;
+        }
+        self.gtc_store = Some(store);
+        Ok(self)
+    }
+
+    /// Snapshots the OMS into `store` every `interval` once a journal is attached, so that a
+    /// restart only replays the journal from the latest snapshot on. Must be set before the
+    /// journal.
+    pub fn with_snapshots(mut self, store: SnapshotStore, interval: Duration) -> Self {
+        self.snapshots = Some(store);
+        self.snapshot_interval = interval;
+        self
+    }
+
+    /// Rebuilds the OMS from the latest snapshot, if any, and the journal entries after it, then
+    /// journals everything from here on. The schedule, risk checks and handlers must be set first and
+    /// match those the journal was written with.
+    pub fn with_journal(mut self, journal: Journal) -> Result<Self, String> {
+        let mut entries = journal.entries().map_err(|e| format!("Failed to read the journal: {}", e))?;
+        // The journal numbers IDs from its start; the ID store only counts once it is replayed
+        self.order_ids.reset(1);
+        self.exec_ids.reset(1);
+        let journal_end = journal.next_offset() - 1;
+        if let Some(store) = &self.snapshots {
+            let snapshot = store.load_latest(journal_end).map_err(|e| format!("Failed to read snapshots: {}", e))?;
+            if let Some((offset, state)) = snapshot {
+                println!("Restoring the snapshot at journal offset {}", offset);
+                self.restore(state);
+                entries.retain(|entry| entry.offset > offset);
+            }
+        }
+        let replayed = self.replay(entries);
+        println!("Replayed {} journal entries", replayed);
+        self.last_snapshot = Some((Utc::now(), journal_end));
+        self.journal = Some(journal);
+        // Record any move past the journal's numbering, so that the next replay makes it too
+        if let Some(next) = self.resume_ids() {
+            self.now = Utc::now();
+            self.journal(Some(JournalEvent::Ids(next)), &[], self.unsolicited.len());
+        }
+        Ok(self)
+    }
+
+    /// Copies out the state a snapshot needs to carry on from the current journal offset.
+    pub fn state(&self) -> OmsState {
+        OmsState {
+            orders: self.orders.clone(),
+            books: self.books.clone(),
+            positions: self.positions.lock().unwrap().clone(),
+            auction_orders: self.auction_orders.clone(),
+            stop_orders: self.stop_orders.clone(),
+            last_timer: self.last_timer,
+            next_order_id: self.order_ids.next_number(),
+            next_exec_id: self.exec_ids.next_number(),
+        }
+    }
+
+    fn restore(&mut self, state: OmsState) {
+        self.orders = state.orders;
+        self.books = state.books;
+        *self.positions.lock().unwrap() = state.positions;
+        self.auction_orders = state.auction_orders;
+        self.stop_orders = state.stop_orders;
+        self.last_timer = state.last_timer;
+        self.order_ids.reset(state.next_order_id);
+        self.exec_ids.reset(state.next_exec_id);
+    }
+
+    /// Feeds the journaled inbound messages and timer ticks back through the OMS at their
+    /// recorded times, returning how many entries were replayed. Reports produced on the way
+    /// were sent when the entries were first processed, so none are sent again.
+    pub fn replay(&mut self, entries: impl IntoIterator<Item = JournalEntry>) -> usize {
+        let mut replayed = 0;
+        for entry in entries {
+            match entry.event {
+                JournalEvent::Inbound(message) => {
+                    let fields = FixMessageParser::parse_fields(&message);
+                    let session = fields.get("49").cloned();
+                    self.handle_from(FixMessageParser::parse_fields_into_message(fields), session.as_deref(), entry.time);
+                }
+                JournalEvent::Timer => self.on_timer(entry.time),
+                JournalEvent::Ids(next) => {
+                    self.order_ids.resume(next.next_order_id);
+                    self.exec_ids.resume(next.next_exec_id);
+                }
+                JournalEvent::Outbound(_) => {}
+            }
+            replayed += 1;
+        }
+        self.unsolicited.clear();
+        replayed
+    }
+
+    /// CompID of the counterparty whose message is being processed, if it came from one.
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+
+    pub fn orders(&self) -> &OrderStore {
+        &self.orders
+    }
+
+    pub fn positions(&self) -> Arc<Mutex<Positions>> {
+        self.positions.clone()
     }
 
-    pub fn listen_for_orders(&self) {
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+
+    /// Takes the reports produced for orders other than the requester's since the last call.
+    pub fn take_unsolicited(&mut self) -> Vec<(String, FixMessage)> {
+        std::mem::take(&mut self.unsolicited)
+    }
+
+    /// Processes requests from `receiver` and runs the timer until every sender is gone or
+    /// `shutdown` fires. On shutdown the requests already queued are still answered.
+    pub async fn listen_for_orders(&mut self, mut receiver: Receiver<OmsRequest>, mut shutdown: oneshot::Receiver<()>) {
         println!("Order Management System is now listening for messages...");
+        let mut timer = tokio::time::interval(TIMER_INTERVAL);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                request = receiver.recv() => match request {
+                    Some(request) => self.serve(request),
+                    None => break,
+                },
+                _ = timer.tick() => self.on_timer(Utc::now()),
+                _ = &mut shutdown => {
+                    receiver.close();
+                    while let Some(request) = receiver.recv().await {
+                        self.serve(request);
+                    }
+                    self.dispatch_unsolicited();
+                    break;
+                }
+            }
+            self.dispatch_unsolicited();
+        }
+        println!("Order Management System stopped");
+    }
+
+    fn serve(&mut self, request: OmsRequest) {
+        let replies = self.handle_from(request.message, request.session.as_deref(), Utc::now());
+        let mut handlers = std::mem::take(&mut self.handlers);
+        handlers.on_replies(&replies, request.reply_to.as_ref(), self);
+        self.handlers = handlers;
+        if let Some(reply_to) = request.reply_to {
+            reply_to.send(replies);
+        }
+    }
+
+    /// Runs whatever fell due by `now`: the opening and closing auctions when the schedule
+    /// opened or closed since the previous call, then expiry of Day and Good Till Date orders.
+    pub fn on_timer(&mut self, now: DateTime<Utc>) {
+        self.now = now;
+        let unsolicited = self.unsolicited.len();
+        let (opened, closed) = match (&self.schedule, self.last_timer) {
+            (Some(schedule), Some(last)) => (
+                schedule.next_session_start(last).is_some_and(|start| start <= now),
+                schedule.next_session_end(last).is_some_and(|end| end <= now),
+            ),
+            _ => (false, false),
+        };
+        if opened {
+            self.positions.lock().unwrap().start_of_day();
+            self.run_auction(AT_THE_OPENING, now);
+        }
+        if closed {
+            self.run_auction(AT_THE_CLOSE, now);
+        }
+        let first = self.last_timer.is_none();
+        self.last_timer = Some(now);
+        self.expire_orders(now);
+        self.save_gtc_orders();
+        self.save_ids();
+        // Ticks that changed nothing are left out of the journal, which would otherwise take a
+        // line a second
+        if first || opened || closed || self.unsolicited.len() > unsolicited {
+            self.journal(Some(JournalEvent::Timer), &[], unsolicited);
+        }
+        self.take_snapshot(now);
+    }
+
+    /// Writes a snapshot once the interval has passed, provided the journal has moved on.
+    fn take_snapshot(&mut self, now: DateTime<Utc>) {
+        let (store, journal) = match (&self.snapshots, &self.journal) {
+            (Some(store), Some(journal)) => (store, journal),
+            _ => return,
+        };
+        let offset = journal.next_offset() - 1;
+        if self.last_snapshot.is_some_and(|(time, last_offset)| now - time < self.snapshot_interval || last_offset == offset) {
+            return;
+        }
+        match store.save(offset, &self.state()) {
+            Ok(()) => println!("Snapshot written at journal offset {}", offset),
+            Err(e) => eprintln!("Failed to write a snapshot: {}", e),
+        }
+        self.last_snapshot = Some((now, offset));
+    }
+
+    fn dispatch_unsolicited(&mut self) {
+        let unsolicited = self.take_unsolicited();
+        if unsolicited.is_empty() {
+            return;
+        }
+        let mut handlers = std::mem::take(&mut self.handlers);
+        for (order_id, message) in &unsolicited {
+            handlers.on_unsolicited(order_id, message, self);
+        }
+        self.handlers = handlers;
+    }
+
+    /// Processes one inbound message and returns the messages to send back to its originator.
+    pub fn handle(&mut self, message: FixMessage) -> Vec<FixMessage> {
+        self.handle_at(message, Utc::now())
+    }
+
+    /// Processes one inbound message as if it arrived at `now`.
+    pub fn handle_at(&mut self, message: FixMessage, now: DateTime<Utc>) -> Vec<FixMessage> {
+        self.handle_from(message, None, now)
+    }
+
+    /// Processes one inbound message from the counterparty `session` as if it arrived at `now`.
+    /// The journal keeps the counterparty as the message's SenderCompID (49).
+    pub fn handle_from(&mut self, message: FixMessage, session: Option<&str>, now: DateTime<Utc>) -> Vec<FixMessage> {
+        self.now = now;
+        self.session = session.map(str::to_string);
+        let unsolicited = self.unsolicited.len();
+        let inbound = message.to_writer().map(|writer| JournalEvent::Inbound(writer.optional_field("49", session).encode_body()));
+        let replies = self.process(message);
+        self.save_gtc_orders();
+        self.save_ids();
+        self.journal(inbound, &replies, unsolicited);
+        replies
+    }
+
+    /// Journals what happened at `self.now`: the inbound event, followed by the replies and the
+    /// unsolicited reports queued from index `unsolicited` on.
+    fn journal(&mut self, inbound: Option<JournalEvent>, replies: &[FixMessage], unsolicited: usize) {
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => return,
+        };
+        let outbound = replies
+            .iter()
+            .chain(self.unsolicited[unsolicited..].iter().map(|(_, message)| message))
+            .filter_map(FixMessage::to_writer)
+            .map(|writer| JournalEvent::Outbound(writer.encode_body()));
+        let events = inbound.into_iter().chain(outbound).collect();
+        if let Err(e) = journal.append(self.now, events) {
+            eprintln!("Failed to write to the journal: {}", e);
+        }
+    }
+
+    /// Runs `message` down the handler chain, which is taken out of the OMS meanwhile so that
+    /// the handlers can work on it.
+    fn process(&mut self, message: FixMessage) -> Vec<FixMessage> {
+        let mut handlers = std::mem::take(&mut self.handlers);
+        let replies = handlers.dispatch(&message, self);
+        self.handlers = handlers;
+        replies
+    }
+
+    /// Enters a new order: acknowledges or rejects it, then matches it if accepted.
+    pub fn enter_order(&mut self, new_order: &NewOrder) -> Vec<FixMessage> {
+        self.process_new_order(new_order, None)
+    }
+
+    /// Rejects a new order for a reason found outside the OMS, such as by a handler, giving it
+    /// an OrderID and keeping it in the order store like any other rejected order.
+    pub fn reject_order(&mut self, new_order: &NewOrder, ord_rej_reason: u32, text: String) -> Vec<FixMessage> {
+        self.process_new_order(new_order, Some((ord_rej_reason, text)))
+    }
+
+    /// Acknowledges or rejects the order, then matches an accepted order against the book.
+    /// Opening and closing auction orders wait for their auction and stop orders for their
+    /// trigger instead. A limit order remainder rests unless it is Immediate or Cancel; a market
+    /// order's remainder is canceled. Any trades go on to trigger stops. An order given a
+    /// `rejection` is rejected with it unless it is a duplicate.
+    fn process_new_order(&mut self, new_order: &NewOrder, rejection: Option<(u32, String)>) -> Vec<FixMessage> {
+        let now = self.now;
+
+        if self.orders.contains_cl_ord_id(self.session(), &new_order.cl_ord_id) {
+            eprintln!("Rejecting order with duplicate ClOrdID {}", new_order.cl_ord_id);
+            let mut order = Order::new(NO_ORDER_ID.to_string(), new_order, now);
+            let text = format!("Duplicate ClOrdID {}", new_order.cl_ord_id);
+            let _ = order.transition(OrdStatus::Rejected, now, Some(text));
+            return vec![FixMessage::ExecutionReport(self.rejection(&order, DUPLICATE_ORDER))];
+        }
+
+        let order_id = self.next_order_id();
+        let mut order = self.build_order(order_id.clone(), new_order);
+        order.session = self.session.clone();
+        let checked = match rejection {
+            Some(rejection) => Err(rejection),
+            None => validate(new_order)
+                .and_then(|()| check_stop_px(&order))
+                .and_then(|()| self.expire_time(&order, new_order, now))
+                .and_then(|expire_time| self.check_fill_or_kill(&order).map(|()| expire_time)),
+        };
+        let report = match checked {
+            Ok(expire_time) => {
+                order.expire_time = expire_time;
+                let _ = order.transition(OrdStatus::New, now, None);
+                self.execution_report(&order, EXEC_TYPE_NEW)
+            }
+            Err((ord_rej_reason, text)) => {
+                let _ = order.transition(OrdStatus::Rejected, now, Some(text));
+                self.rejection(&order, ord_rej_reason)
+            }
+        };
+        println!("Order {} for ClOrdID {} is {}", order.order_id, order.cl_ord_id, order.ord_status);
+        let accepted = order.ord_status == OrdStatus::New;
+        let auction = order.is_auction_order();
+        let stop = order.is_stop_order();
+        let symbol = order.symbol.clone();
+        self.gtc_dirty |= accepted && order.time_in_force() == GOOD_TILL_CANCEL;
+        if let Err(e) = self.orders.insert(order) {
+            eprintln!("{}", e);
+        }
+
+        let mut replies = vec![FixMessage::ExecutionReport(report)];
+        if !accepted {
+            return replies;
+        }
+        if auction {
+            self.auction_orders.push(order_id.clone());
+        } else if stop {
+            self.stop_orders.push(order_id.clone());
+        } else {
+            self.match_order(&order_id, &mut replies);
+        }
+        for (triggered_order_id, report) in self.trigger_stops(&symbol) {
+            if triggered_order_id == order_id {
+                replies.push(report);
+            } else {
+                self.unsolicited.push((triggered_order_id, report));
+            }
+        }
+        replies
+    }
+
+    /// The order a NewOrderSingle describes, as yet unchecked. A trailing stop without a StopPx
+    /// starts trailing from the last traded price.
+    pub fn build_order(&self, order_id: String, new_order: &NewOrder) -> Order {
+        let mut order = Order::new(order_id, new_order, self.now);
+        if order.stop_px.is_none() {
+            if let Some(last_px) = self.last_price(&order.symbol) {
+                order.trail(last_px);
+            }
+        }
+        order
+    }
 
-        while let Ok(message) = self.receiver.recv() {
-            match message {
-                FixMessage::NewOrder(order) => {
-                    self.process_new_order(order);
+    pub fn last_price(&self, symbol: &str) -> Option<f64> {
+        self.books.get(symbol).and_then(OrderBook::last_price)
+    }
+
+    /// Working orders of an account, each valued as the risk checks value a new order.
+    pub fn account_exposure(&self, account: Option<&str>) -> AccountExposure {
+        self.orders
+            .orders()
+            .filter(|order| !order.ord_status.is_terminal() && order.account.as_deref() == account)
+            .fold(AccountExposure::default(), |exposure, order| AccountExposure {
+                open_orders: exposure.open_orders + 1,
+                gross_exposure: exposure.gross_exposure
+                    + valuation_price(order, self.last_price(&order.symbol)).unwrap_or(0.0) * order.leaves_qty,
+            })
+    }
+
+    /// Trails and triggers the stop orders in `symbol` against its last traded price. Triggered
+    /// stops become market or limit orders and match straight away, and their trades can
+    /// trigger further stops. Returns the triggered orders' reports keyed by OrderID.
+    fn trigger_stops(&mut self, symbol: &str) -> Vec<(String, FixMessage)> {
+        let mut reports = Vec::new();
+        while let Some(last_px) = self.books.get(symbol).and_then(OrderBook::last_price) {
+            for order_id in &self.stop_orders {
+                if let Some(order) = self.orders.get_mut(order_id).filter(|order| order.symbol == symbol) {
+                    if order.trail(last_px) {
+                        self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+                    }
                 }
-                FixMessage::ExecutionReport(report) => {
-                    println!("Received ExecutionReport: {:?}", report);
+            }
+
+            let triggered = self.stop_orders.iter().position(|order_id| {
+                self.orders.get(order_id).is_some_and(|order| order.symbol == symbol && order.is_triggered_by(last_px))
+            });
+            let order_id = match triggered {
+                Some(index) => self.stop_orders.remove(index),
+                None => break,
+            };
+            let order = self.orders.get_mut(&order_id).expect("stop orders are in the store");
+            if let Err(e) = order.trigger(last_px, self.now) {
+                eprintln!("{}", e);
+                continue;
+            }
+            println!("Order {} triggered at last price {}", order_id, last_px);
+            self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+            let mut replies = vec![FixMessage::ExecutionReport(order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_TRIGGERED, transact_time(self.now)))];
+            self.match_order(&order_id, &mut replies);
+            reports.extend(replies.into_iter().map(|reply| (order_id.clone(), reply)));
+        }
+        reports
+    }
+
+    /// When an order should leave the market under its TimeInForce (59): Day orders at the
+    /// close, Good Till Date orders at ExpireTime (126) or the end of ExpireDate (432).
+    fn expire_time(&self, order: &Order, new_order: &NewOrder, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, (u32, String)> {
+        let expire_time = match order.time_in_force() {
+            DAY | AT_THE_OPENING | AT_THE_CLOSE => Some(self.close_after(now)),
+            GOOD_TILL_CANCEL | IMMEDIATE_OR_CANCEL | FILL_OR_KILL => None,
+            GOOD_TILL_DATE => match (&new_order.expire_time, &new_order.expire_date) {
+                (Some(expire_time), _) => Some(NaiveDateTime::parse_from_str(expire_time, "%Y%m%d-%H:%M:%S%.f")
+                    .map_err(|_| (OTHER, format!("Invalid ExpireTime {}", expire_time)))?
+                    .and_utc()),
+                (None, Some(expire_date)) => {
+                    let date = NaiveDate::parse_from_str(expire_date, "%Y%m%d")
+                        .map_err(|_| (OTHER, format!("Invalid ExpireDate {}", expire_date)))?;
+                    Some(self.end_of_day(date))
                 }
-                FixMessage::OrderCancelRequest(request) => {
-                    println!("Received OrderCancelRequest: {:?}", request);
+                (None, None) => return Err((OTHER, "Good Till Date order without ExpireTime or ExpireDate".to_string())),
+            },
+            other => return Err((OTHER, format!("Unsupported TimeInForce {}", other))),
+        };
+        match expire_time {
+            Some(expire_time) if expire_time <= now => Err((OTHER, format!("Order would expire at {}, which has passed", expire_time.format(UTC_TIMESTAMP_FORMAT)))),
+            _ => Ok(expire_time),
+        }
+    }
+
+    /// The next close of the trading schedule, or midnight UTC without one.
+    fn close_after(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.schedule
+            .as_ref()
+            .and_then(|schedule| schedule.next_session_end(now))
+            .unwrap_or_else(|| self.end_of_day(now.date_naive()))
+    }
+
+    /// Midnight at the end of `date`, in the schedule's time zone or UTC.
+    fn end_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = (date + Duration::days(1)).and_time(Default::default());
+        match &self.schedule {
+            Some(schedule) => schedule.time_zone().from_local_datetime(&midnight).earliest().map(|time| time.with_timezone(&Utc)),
+            None => None,
+        }
+        .unwrap_or_else(|| midnight.and_utc())
+    }
+
+    /// Fill or Kill orders are only accepted when the book can fill them in full straight away.
+    fn check_fill_or_kill(&self, order: &Order) -> Result<(), (u32, String)> {
+        if order.time_in_force() != FILL_OR_KILL {
+            return Ok(());
+        }
+        let limit = if order.ord_type == MARKET { None } else { order.price };
+        let own = |maker: &str| self.is_same_owner(order, maker);
+        let fillable = self.books.get(&order.symbol).map_or(0.0, |book| {
+            let crossing = book.crossing(order.side, limit);
+            // Own orders are canceled out of the way; under any other mode they end matching
+            let available: f64 = match self.self_trade_prevention.map(|prevention| prevention.mode) {
+                Some(SelfTradeMode::CancelResting) => crossing.filter(|(maker, _)| !own(maker)).map(|(_, qty)| qty).sum(),
+                _ => crossing.take_while(|(maker, _)| !own(maker)).map(|(_, qty)| qty).sum(),
+            };
+            available.min(order.order_qty)
+        });
+        if fillable < order.order_qty {
+            return Err((OTHER, format!("Fill or Kill order for {} can only fill {}", order.order_qty, fillable)));
+        }
+        Ok(())
+    }
+
+    /// Matches an order against its book. On reaching a resting order of its own owner, self-trade
+    /// prevention steps in and matching goes on for as long as the order is still working.
+    fn match_order(&mut self, order_id: &str, replies: &mut Vec<FixMessage>) {
+        loop {
+            let order = match self.orders.get(order_id) {
+                Some(order) => order,
+                None => return,
+            };
+            let limit = if order.ord_type == MARKET { None } else { order.price };
+            let (orders, key) = (&self.orders, self.self_trade_prevention.map(|prevention| prevention.key));
+            let book = self.books.entry(order.symbol.clone()).or_default();
+            let fills = book.match_order_until(order.side, limit, order.leaves_qty, |maker| {
+                key.is_some_and(|key| orders.get(maker).is_some_and(|maker| same_owner(key, order, maker)))
+            });
+
+            for fill in fills {
+                if let Some(report) = self.trade_report(order_id, fill.qty, fill.price) {
+                    replies.push(FixMessage::ExecutionReport(report));
                 }
-                FixMessage::OrderStatusRequest(status_request) => {
-                    println!("Received OrderStatusRequest: {:?}", status_request);
+                if let Some(mut report) = self.trade_report(&fill.maker_order_id, fill.qty, fill.price) {
+                    if report.max_floor.is_some() {
+                        report.display_qty = Some(fill.displayed_qty);
+                    }
+                    self.unsolicited.push((fill.maker_order_id.clone(), FixMessage::ExecutionReport(report)));
+                }
+            }
+
+            let order = match self.orders.get(order_id) {
+                Some(order) if order.leaves_qty > 0.0 => order,
+                _ => return,
+            };
+            let maker_order_id = self
+                .books
+                .get(&order.symbol)
+                .and_then(|book| book.next_match(order.side, limit))
+                .filter(|maker| self.is_same_owner(order, maker))
+                .map(str::to_string);
+            match (self.self_trade_prevention, maker_order_id) {
+                (Some(prevention), Some(maker_order_id)) => {
+                    if !self.prevent_self_trade(prevention.mode, order_id, &maker_order_id, replies) {
+                        return;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let order = match self.orders.get_mut(order_id) {
+            Some(order) if order.leaves_qty > 0.0 => order,
+            _ => return,
+        };
+        let unfilled = match (order.ord_type, order.price, order.time_in_force()) {
+            (MARKET, _, _) | (_, None, _) => "No liquidity for the market order remainder",
+            (_, _, IMMEDIATE_OR_CANCEL | FILL_OR_KILL) => "Immediate or Cancel remainder canceled",
+            (_, Some(price), _) => {
+                rest(self.books.entry(order.symbol.clone()).or_default(), order, price);
+                return;
+            }
+        };
+        if order.transition(OrdStatus::Canceled, self.now, Some(unfilled.to_string())).is_ok() {
+            replies.push(FixMessage::ExecutionReport(order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_CANCELED, transact_time(self.now))));
+        }
+    }
+
+    /// Whether the resting order `maker_order_id` trades for the same owner as `order`, which
+    /// self-trade prevention keeps them from trading with each other.
+    fn is_same_owner(&self, order: &Order, maker_order_id: &str) -> bool {
+        match (self.self_trade_prevention, self.orders.get(maker_order_id)) {
+            (Some(prevention), Some(maker)) => same_owner(prevention.key, order, maker),
+            _ => false,
+        }
+    }
+
+    /// Keeps an incoming order from trading with a resting order of its own owner as `mode`
+    /// says, reporting each order canceled or decremented to its originator. Returns whether
+    /// the incoming order is still working and may match on.
+    fn prevent_self_trade(&mut self, mode: SelfTradeMode, order_id: &str, maker_order_id: &str, replies: &mut Vec<FixMessage>) -> bool {
+        let leaves_qty = |order_id: &str| self.orders.get(order_id).map_or(0.0, |order| order.leaves_qty);
+        let (taker_qty, maker_qty) = (leaves_qty(order_id), leaves_qty(maker_order_id));
+        let (taker, maker) = match mode {
+            SelfTradeMode::CancelResting => (Prevention::Keep, Prevention::Cancel),
+            SelfTradeMode::CancelAggressor => (Prevention::Cancel, Prevention::Keep),
+            SelfTradeMode::CancelBoth => (Prevention::Cancel, Prevention::Cancel),
+            SelfTradeMode::DecrementAndCancel if taker_qty > maker_qty => (Prevention::Decrement(maker_qty), Prevention::Cancel),
+            SelfTradeMode::DecrementAndCancel if maker_qty > taker_qty => (Prevention::Cancel, Prevention::Decrement(taker_qty)),
+            SelfTradeMode::DecrementAndCancel => (Prevention::Cancel, Prevention::Cancel),
+        };
+        println!("Order {} kept from trading with order {} of the same owner", order_id, maker_order_id);
+        if let Some(report) = self.prevent(maker_order_id, maker) {
+            self.unsolicited.push((maker_order_id.to_string(), FixMessage::ExecutionReport(report)));
+        }
+        if let Some(report) = self.prevent(order_id, taker) {
+            replies.push(FixMessage::ExecutionReport(report));
+        }
+        self.orders.get(order_id).is_some_and(|order| !order.ord_status.is_terminal())
+    }
+
+    /// Cancels or decrements an order for self-trade prevention, reporting it as Canceled or
+    /// Restated.
+    fn prevent(&mut self, order_id: &str, prevention: Prevention) -> Option<ExecutionReport> {
+        if prevention == Prevention::Keep {
+            return None;
+        }
+        let order = self.orders.get_mut(order_id)?;
+        let book = self.books.entry(order.symbol.clone()).or_default();
+        let changed = match prevention {
+            Prevention::Decrement(qty) => order
+                .decrement(qty, self.now, format!("Decremented by {} by self-trade prevention", qty))
+                .map(|()| book.reduce(order_id, qty))
+                .map(|_| EXEC_TYPE_RESTATED),
+            _ => order
+                .transition(OrdStatus::Canceled, self.now, Some("Canceled by self-trade prevention".to_string()))
+                .map(|()| book.remove(order_id))
+                .map(|_| EXEC_TYPE_CANCELED),
+        };
+        let exec_type = match changed {
+            Ok(exec_type) => exec_type,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        };
+        self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+        let mut report = order.execution_report(self.exec_ids.next_id(), exec_type, transact_time(self.now));
+        report.display_qty = report.max_floor.and(book.displayed_qty(order_id)).or(report.display_qty);
+        Some(report)
+    }
+
+    /// Applies one side of an execution to an order and reports it as a trade.
+    fn trade_report(&mut self, order_id: &str, last_qty: f64, last_px: f64) -> Option<ExecutionReport> {
+        let order = self.orders.get_mut(order_id)?;
+        if let Err(e) = order.fill(last_qty, last_px, self.now) {
+            eprintln!("{}", e);
+            return None;
+        }
+        println!("Order {} traded {} at {}", order_id, last_qty, last_px);
+        self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+        {
+            let mut positions = self.positions.lock().unwrap();
+            positions.fill(order.account.as_deref(), &order.symbol, order.side, last_qty, last_px);
+            positions.mark(&order.symbol, last_px);
+        }
+        let mut report = order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_TRADE, transact_time(self.now));
+        report.last_qty = Some(last_qty);
+        report.last_px = Some(last_px);
+        Some(report)
+    }
+
+    /// Uncrosses the opening (OPG) or closing (ATC) auction orders waiting in each symbol at a
+    /// single price. Auction orders only meet each other; whatever is left of them expires.
+    pub fn run_auction(&mut self, time_in_force: char, now: DateTime<Utc>) {
+        let (participants, waiting): (Vec<String>, Vec<String>) = std::mem::take(&mut self.auction_orders)
+            .into_iter()
+            .partition(|order_id| self.orders.get(order_id).is_some_and(|order| order.time_in_force() == time_in_force));
+        self.auction_orders = waiting;
+
+        let mut orders_by_symbol: BTreeMap<String, Vec<AuctionOrder>> = BTreeMap::new();
+        for order in participants.iter().filter_map(|order_id| self.orders.get(order_id)) {
+            if !order.ord_status.is_terminal() {
+                orders_by_symbol.entry(order.symbol.clone()).or_default().push(AuctionOrder {
+                    order_id: order.order_id.clone(),
+                    side: order.side,
+                    limit: if order.ord_type == MARKET { None } else { order.price },
+                    qty: order.leaves_qty,
+                });
+            }
+        }
+
+        for (symbol, orders) in orders_by_symbol {
+            let book = self.books.entry(symbol.clone()).or_default();
+            let result = match uncross(&orders, book.last_price()) {
+                Some(result) => result,
+                None => continue,
+            };
+            book.record_trade(result.price);
+            println!("Auction in {} uncrossed at {}", symbol, result.price);
+            for (order_id, qty) in result.executions {
+                if let Some(report) = self.trade_report(&order_id, qty, result.price) {
+                    self.unsolicited.push((order_id, FixMessage::ExecutionReport(report)));
                 }
-                FixMessage::Unknown => {
-                    println!("Received an Unknown message type");
+            }
+            let triggered = self.trigger_stops(&symbol);
+            self.unsolicited.extend(triggered);
+        }
+
+        let text = match time_in_force {
+            AT_THE_OPENING => "Unfilled in the opening auction",
+            _ => "Unfilled in the closing auction",
+        };
+        for order_id in participants {
+            self.expire(&order_id, now, text);
+        }
+    }
+
+    fn expire_orders(&mut self, now: DateTime<Utc>) {
+        let mut due: Vec<(String, char)> = self
+            .orders
+            .orders()
+            .filter(|order| !order.ord_status.is_terminal() && order.expire_time.is_some_and(|expire_time| expire_time <= now))
+            .map(|order| (order.order_id.clone(), order.time_in_force()))
+            .collect();
+        due.sort_by_key(|(order_id, _)| self.order_number(order_id));
+        for (order_id, time_in_force) in due {
+            let text = match time_in_force {
+                GOOD_TILL_DATE => "Good Till Date order expired",
+                _ => "Day order expired at the close",
+            };
+            self.expire(&order_id, now, text);
+        }
+        let working = |order_id: &String| self.orders.get(order_id).is_some_and(|order| !order.ord_status.is_terminal());
+        self.auction_orders.retain(working);
+        self.stop_orders.retain(working);
+    }
+
+    /// Takes a working order out of the market and reports it as Expired to its originator.
+    fn expire(&mut self, order_id: &str, now: DateTime<Utc>, text: &str) {
+        if self.orders.get(order_id).is_none_or(|order| order.ord_status.is_terminal()) {
+            return;
+        }
+        let order = self.orders.get_mut(order_id).expect("order looked up above");
+        if let Err(e) = order.transition(OrdStatus::Expired, now, Some(text.to_string())) {
+            eprintln!("{}", e);
+            return;
+        }
+        if let Some(book) = self.books.get_mut(&order.symbol) {
+            book.remove(order_id);
+        }
+        println!("Order {} expired: {}", order_id, text);
+        let report = order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_EXPIRED, transact_time(self.now));
+        self.unsolicited.push((order_id.to_string(), FixMessage::ExecutionReport(report)));
+    }
+
+    fn save_gtc_orders(&mut self) {
+        if !std::mem::take(&mut self.gtc_dirty) {
+            return;
+        }
+        if let Some(store) = &self.gtc_store {
+            let mut orders: Vec<&Order> = self.orders.orders().collect();
+            orders.sort_by_key(|order| self.order_number(&order.order_id));
+            if let Err(e) = store.save(orders) {
+                eprintln!("Failed to save GTC orders: {}", e);
+            }
+        }
+    }
+
+    /// Moves numbering on to where the ID store left off, returning the new numbers if it was
+    /// ahead.
+    fn resume_ids(&mut self) -> Option<NextIds> {
+        let saved = self.id_store.as_ref()?.saved();
+        let next = self.next_ids();
+        if saved.next_order_id <= next.next_order_id && saved.next_exec_id <= next.next_exec_id {
+            return None;
+        }
+        self.order_ids.resume(saved.next_order_id);
+        self.exec_ids.resume(saved.next_exec_id);
+        Some(self.next_ids())
+    }
+
+    fn next_ids(&self) -> NextIds {
+        NextIds { next_order_id: self.order_ids.next_number(), next_exec_id: self.exec_ids.next_number() }
+    }
+
+    fn save_ids(&mut self) {
+        let next = self.next_ids();
+        if let Some(store) = &mut self.id_store {
+            if let Err(e) = store.save(next) {
+                eprintln!("Failed to save the next IDs: {}", e);
+            }
+        }
+    }
+
+    /// Cancels the order named by OrigClOrdID, provided Side and Symbol match it. Only the
+    /// counterparty that entered an order can name it. The order takes on the request's
+    /// ClOrdID, keeping the previous one as OrigClOrdID.
+    pub fn cancel_order(&mut self, request: &OrderCancelRequest) -> FixMessage {
+        let order = match self.orders.get_by_cl_ord_id(self.session(), &request.orig_cl_ord_id) {
+            Some(order) => order,
+            None => return cancel_reject(request, None, UNKNOWN_ORDER_TO_CANCEL, "Unknown order"),
+        };
+        if order.side != request.side || order.symbol != request.symbol {
+            return cancel_reject(request, Some(order), CXL_REJ_OTHER, "Side or Symbol does not match the order");
+        }
+        if self.orders.contains_cl_ord_id(self.session(), &request.cl_ord_id) {
+            let text = format!("Duplicate ClOrdID {}", request.cl_ord_id);
+            return cancel_reject(request, Some(order), DUPLICATE_CL_ORD_ID, &text);
+        }
+        if order.ord_status.is_terminal() {
+            let text = format!("Order is already {}", order.ord_status);
+            return cancel_reject(request, Some(order), TOO_LATE_TO_CANCEL, &text);
+        }
+
+        let order_id = order.order_id.clone();
+        let order = self.orders.get_mut(&order_id).expect("order looked up above");
+        if let Err(e) = order.transition(OrdStatus::Canceled, self.now, None) {
+            return cancel_reject(request, Some(order), CXL_REJ_OTHER, &e);
+        }
+        order.orig_cl_ord_id = Some(std::mem::replace(&mut order.cl_ord_id, request.cl_ord_id.clone()));
+        if let Some(book) = self.books.get_mut(&order.symbol) {
+            book.remove(&order_id);
+        }
+        self.auction_orders.retain(|waiting_order_id| waiting_order_id != &order_id);
+        self.stop_orders.retain(|waiting_order_id| waiting_order_id != &order_id);
+        self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+        let report = order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_CANCELED, transact_time(self.now));
+        println!("Order {} canceled by ClOrdID {}", order_id, request.cl_ord_id);
+        if let Err(e) = self.orders.add_cl_ord_id(&order_id, &request.cl_ord_id) {
+            eprintln!("{}", e);
+        }
+        FixMessage::ExecutionReport(report)
+    }
+
+    /// Cancels every working order in the request's scope: one security for
+    /// MassCancelRequestType 1, or all orders for 7, narrowed to the request's Account and Side
+    /// when it gives them. A request from a counterparty only reaches its own orders, while one
+    /// from no session, such as the kill switch, reaches everyone's. Each canceled order is
+    /// reported to its originator, and the report answering the request counts them.
+    pub fn mass_cancel(&mut self, request: &OrderMassCancelRequest) -> OrderMassCancelReport {
+        let mut report = OrderMassCancelReport {
+            cl_ord_id: request.cl_ord_id.clone(),
+            order_id: self.next_order_id(),
+            mass_cancel_request_type: request.mass_cancel_request_type,
+            mass_cancel_response: request.mass_cancel_request_type,
+            mass_cancel_reject_reason: None,
+            total_affected_orders: None,
+            symbol: request.symbol.clone(),
+            side: request.side,
+            account: request.account.clone(),
+            transact_time: Some(transact_time(self.now)),
+            text: None,
+        };
+        let symbol = match (request.mass_cancel_request_type, request.symbol.as_deref()) {
+            (MASS_CANCEL_SECURITY, Some(symbol)) => Some(symbol),
+            (MASS_CANCEL_ALL, _) => None,
+            (request_type, _) => {
+                let (reason, text) = match request_type {
+                    MASS_CANCEL_SECURITY => (INVALID_SECURITY, "Mass cancel for a security without a Symbol".to_string()),
+                    _ => (MASS_CANCEL_NOT_SUPPORTED, format!("Unsupported MassCancelRequestType {}", request_type)),
+                };
+                eprintln!("Rejecting mass cancel {}: {}", request.cl_ord_id, text);
+                report.mass_cancel_response = MASS_CANCEL_REJECTED;
+                report.mass_cancel_reject_reason = Some(reason);
+                report.text = Some(text);
+                return report;
+            }
+        };
+
+        let mut order_ids: Vec<String> = self
+            .orders
+            .orders()
+            .filter(|order| {
+                !order.ord_status.is_terminal()
+                    && symbol.is_none_or(|symbol| order.symbol == symbol)
+                    && request.side.is_none_or(|side| order.side == side)
+                    && request.account.as_ref().is_none_or(|account| order.account.as_ref() == Some(account))
+                    && self.session.as_ref().is_none_or(|session| order.session.as_ref() == Some(session))
+            })
+            .map(|order| order.order_id.clone())
+            .collect();
+        order_ids.sort_by_key(|order_id| self.order_number(order_id));
+        let text = format!("Canceled by mass cancel {}", request.cl_ord_id);
+        let mut canceled = 0;
+        for order_id in order_ids {
+            let order = self.orders.get_mut(&order_id).expect("order looked up above");
+            if let Err(e) = order.transition(OrdStatus::Canceled, self.now, Some(text.clone())) {
+                eprintln!("{}", e);
+                continue;
+            }
+            if let Some(book) = self.books.get_mut(&order.symbol) {
+                book.remove(&order_id);
+            }
+            self.gtc_dirty |= order.time_in_force() == GOOD_TILL_CANCEL;
+            let canceled_report = order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_CANCELED, transact_time(self.now));
+            self.unsolicited.push((order_id, FixMessage::ExecutionReport(canceled_report)));
+            canceled += 1;
+        }
+        let working = |order_id: &String| self.orders.get(order_id).is_some_and(|order| !order.ord_status.is_terminal());
+        self.auction_orders.retain(working);
+        self.stop_orders.retain(working);
+        println!("Mass cancel {} canceled {} orders", request.cl_ord_id, canceled);
+        report.total_affected_orders = Some(canceled);
+        report
+    }
+
+    /// Reports the current state of an order, found by OrderID if given and ClOrdID otherwise.
+    /// Another counterparty's order is reported as unknown.
+    pub fn order_status(&mut self, request: &OrderStatusRequest) -> ExecutionReport {
+        let order = match &request.order_id {
+            Some(order_id) => self.orders.get(order_id).filter(|order| order.session == self.session),
+            None => self.orders.get_by_cl_ord_id(self.session(), &request.cl_ord_id),
+        };
+        match order {
+            Some(order) => {
+                let mut report = order.execution_report(self.exec_ids.next_id(), EXEC_TYPE_ORDER_STATUS, transact_time(self.now));
+                if let Some(displayed_qty) = self.books.get(&order.symbol).and_then(|book| book.displayed_qty(&order.order_id)) {
+                    report.display_qty = report.max_floor.map(|_| displayed_qty);
                 }
+                report
+            }
+            None => ExecutionReport {
+                cl_ord_id: request.cl_ord_id.clone(),
+                order_id: request.order_id.clone().unwrap_or_else(|| NO_ORDER_ID.to_string()),
+                exec_id: self.exec_ids.next_id(),
+                exec_type: EXEC_TYPE_ORDER_STATUS,
+                ord_status: OrdStatus::Rejected.as_char(),
+                side: request.side,
+                leaves_qty: 0.0,
+                cum_qty: 0.0,
+                avg_px: None,
+                symbol: request.symbol.clone(),
+                transact_time: transact_time(self.now),
+                order_qty: 0.0,
+                price: None,
+                ord_type: None,
+                stop_px: None,
+                max_floor: None,
+                display_qty: None,
+                exec_trans_type: Some('0'),
+                account: None,
+                orig_cl_ord_id: None,
+                ord_rej_reason: Some(UNKNOWN_ORDER),
+                last_qty: None,
+                last_px: None,
+                text: Some("Unknown order".to_string()),
+            },
+        }
+    }
+
+    fn execution_report(&mut self, order: &Order, exec_type: char) -> ExecutionReport {
+        order.execution_report(self.exec_ids.next_id(), exec_type, transact_time(self.now))
+    }
+
+    fn rejection(&mut self, order: &Order, ord_rej_reason: u32) -> ExecutionReport {
+        let mut report = self.execution_report(order, EXEC_TYPE_REJECTED);
+        report.ord_rej_reason = Some(ord_rej_reason);
+        report
+    }
+
+    fn next_order_id(&mut self) -> String {
+        self.order_ids.next_id()
+    }
+
+    /// Sequence number within an OrderID this OMS assigned, giving arrival order.
+    fn order_number(&self, order_id: &str) -> Option<u64> {
+        self.order_ids.number(order_id)
+    }
+}
+
+impl Default for OrderManagementSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn transact_time(now: DateTime<Utc>) -> String {
+    now.format(UTC_TIMESTAMP_FORMAT).to_string()
+}
+
+fn cancel_reject(request: &OrderCancelRequest, order: Option<&Order>, cxl_rej_reason: u32, text: &str) -> FixMessage {
+    eprintln!("Rejecting cancel {} of {}: {}", request.cl_ord_id, request.orig_cl_ord_id, text);
+    FixMessage::OrderCancelReject(OrderCancelReject {
+        order_id: order.map_or_else(|| NO_ORDER_ID.to_string(), |order| order.order_id.clone()),
+        cl_ord_id: request.cl_ord_id.clone(),
+        orig_cl_ord_id: request.orig_cl_ord_id.clone(),
+        ord_status: order.map_or(OrdStatus::Rejected, |order| order.ord_status).as_char(),
+        cxl_rej_response_to: RESPONSE_TO_CANCEL_REQUEST,
+        cxl_rej_reason: Some(cxl_rej_reason),
+        text: Some(text.to_string()),
+    })
+}
+
+/// Static checks on a NewOrderSingle, returning the OrdRejReason and text on failure.
+pub fn validate(new_order: &NewOrder) -> Result<(), (u32, String)> {
+    if new_order.order_qty <= 0.0 {
+        return Err((INCORRECT_QUANTITY, format!("Invalid OrderQty {}", new_order.order_qty)));
+    }
+    if !matches!(new_order.side, '1' | '2') {
+        return Err((OTHER, format!("Unsupported Side {}", new_order.side)));
+    }
+    match (new_order.ord_type, new_order.price) {
+        (MARKET | STOP, _) => {}
+        (LIMIT, None) => return Err((OTHER, "Limit order without a Price".to_string())),
+        (STOP_LIMIT, None) => return Err((OTHER, "Stop limit order without a Price".to_string())),
+        (LIMIT | STOP_LIMIT, _) => {}
+        (other, _) => return Err((OTHER, format!("Unsupported OrdType {}", other))),
+    }
+    if let Some(price) = new_order.price.filter(|price| *price <= 0.0) {
+        return Err((OTHER, format!("Invalid Price {}", price)));
+    }
+    if let Some(stop_px) = new_order.stop_px.filter(|stop_px| *stop_px <= 0.0) {
+        return Err((OTHER, format!("Invalid StopPx {}", stop_px)));
+    }
+    if let Some(offset) = new_order.peg_offset_value.filter(|offset| *offset <= 0.0) {
+        return Err((OTHER, format!("Invalid PegOffsetValue {}", offset)));
+    }
+    match new_order.max_floor {
+        Some(max_floor) if max_floor <= 0.0 => return Err((OTHER, format!("Invalid MaxFloor {}", max_floor))),
+        Some(max_floor) if max_floor > new_order.order_qty => {
+            return Err((INCORRECT_QUANTITY, format!("MaxFloor {} exceeds OrderQty {}", max_floor, new_order.order_qty)));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// What self-trade prevention does to one of the two orders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prevention {
+    Keep,
+    Cancel,
+    Decrement(f64),
+}
+
+/// Whether two orders trade for the same owner, as named by `key`. Orders without one have no
+/// owner in common with any other.
+fn same_owner(key: SelfTradeKey, order: &Order, other: &Order) -> bool {
+    owner(key, order).is_some_and(|owner_id| owner(key, other) == Some(owner_id))
+}
+
+fn owner(key: SelfTradeKey, order: &Order) -> Option<&str> {
+    match key {
+        SelfTradeKey::Account => order.account.as_deref(),
+        SelfTradeKey::SelfMatchPreventionId => order.self_match_prevention_id.as_deref(),
+    }
+}
+
+/// Rests what is left of an order at `price`, showing a slice of it if it is an iceberg order.
+fn rest(book: &mut OrderBook, order: &Order, price: f64) {
+    match order.max_floor {
+        Some(max_floor) => book.add_iceberg(&order.order_id, order.side, price, order.leaves_qty, max_floor),
+        None => book.add(&order.order_id, order.side, price, order.leaves_qty),
+    }
+}
+
+/// A stop order needs a StopPx, given outright or trailed from the last traded price.
+fn check_stop_px(order: &Order) -> Result<(), (u32, String)> {
+    match (order.is_stop_order(), order.stop_px, order.peg_offset_value) {
+        (true, None, Some(_)) => Err((OTHER, format!("Trailing stop without a StopPx before {} has traded", order.symbol))),
+        (true, None, None) => Err((OTHER, "Stop order without a StopPx".to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use crate::oms::order::{BUY, SELL};
+    use crate::oms::oms_request::ReplyTo;
+    use crate::oms::order_book::BookLevel;
+    use crate::oms::snapshot::SnapshotStore;
+    use tokio::sync::mpsc;
+
+    fn new_order(cl_ord_id: &str, order_qty: &str) -> FixMessage {
+        let message = format!("35=D|11={}|54=1|60=20230101-12:00:00|38={}|40=2|44=50.5|55=XYZ|", cl_ord_id, order_qty);
+        FixMessageParser::parse_message(&message)
+    }
+
+    fn order(cl_ord_id: &str, side: char, order_qty: f64, price: Option<f64>) -> FixMessage {
+        order_with(cl_ord_id, side, order_qty, price, "")
+    }
+
+    /// An order with extra fields such as TimeInForce, e.g. `"59=3|"`.
+    fn order_with(cl_ord_id: &str, side: char, order_qty: f64, price: Option<f64>, extra_fields: &str) -> FixMessage {
+        let price_fields = match price {
+            Some(price) => format!("40=2|44={}|", price),
+            None => "40=1|".to_string(),
+        };
+        let message = format!("35=D|11={}|54={}|60=20230101-12:00:00|38={}|{}55=XYZ|{}", cl_ord_id, side, order_qty, price_fields, extra_fields);
+        FixMessageParser::parse_message(&message)
+    }
+
+    fn unsolicited_reports(oms: &mut OrderManagementSystem) -> Vec<ExecutionReport> {
+        reports(oms.take_unsolicited().into_iter().map(|(_, message)| message).collect())
+    }
+
+    fn reports(replies: Vec<FixMessage>) -> Vec<ExecutionReport> {
+        replies
+            .into_iter()
+            .map(|reply| match reply {
+                FixMessage::ExecutionReport(report) => report,
+                other => panic!("Expected an ExecutionReport, got {:?}", other),
+            })
+            .collect()
+    }
+
+    fn report(mut replies: Vec<FixMessage>) -> ExecutionReport {
+        assert_eq!(replies.len(), 1);
+        match replies.remove(0) {
+            FixMessage::ExecutionReport(report) => report,
+            other => panic!("Expected an ExecutionReport, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_new_order_is_acknowledged() {
+        let mut oms = OrderManagementSystem::new();
+
+        let first = report(oms.handle(new_order("C1", "100")));
+        let second = report(oms.handle(new_order("C2", "100")));
+
+        assert_eq!(first.exec_type, EXEC_TYPE_NEW);
+        assert_eq!(first.ord_status, '0');
+        assert_eq!(first.leaves_qty, 100.0);
+        assert_eq!(first.cl_ord_id, "C1");
+        assert_ne!(first.order_id, second.order_id);
+        assert_ne!(first.exec_id, second.exec_id);
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C2").unwrap().ord_status, OrdStatus::New);
+    }
+
+    #[test]
+    fn test_invalid_and_duplicate_orders_are_rejected() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(new_order("C1", "100"));
+
+        let duplicate = report(oms.handle(new_order("C1", "100")));
+        assert_eq!(duplicate.exec_type, EXEC_TYPE_REJECTED);
+        assert_eq!(duplicate.ord_status, '8');
+        assert_eq!(duplicate.ord_rej_reason, Some(DUPLICATE_ORDER));
+        assert_eq!(duplicate.text.as_deref(), Some("Duplicate ClOrdID C1"));
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C1").unwrap().ord_status, OrdStatus::New);
+
+        let invalid = report(oms.handle(new_order("C2", "0")));
+        assert_eq!(invalid.ord_rej_reason, Some(INCORRECT_QUANTITY));
+        assert_eq!(invalid.leaves_qty, 0.0);
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C2").unwrap().ord_status, OrdStatus::Rejected);
+    }
+
+    fn cancel(cl_ord_id: &str, orig_cl_ord_id: &str, side: char) -> FixMessage {
+        let message = format!("35=F|11={}|41={}|54={}|55=XYZ|60=20230101-12:00:00|", cl_ord_id, orig_cl_ord_id, side);
+        FixMessageParser::parse_message(&message)
+    }
+
+    fn cancel_reject(mut replies: Vec<FixMessage>) -> OrderCancelReject {
+        match replies.remove(0) {
+            FixMessage::OrderCancelReject(reject) => reject,
+            other => panic!("Expected an OrderCancelReject, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cancel_request() {
+        let mut oms = OrderManagementSystem::new();
+        let ack = report(oms.handle(new_order("C1", "100")));
+
+        let canceled = report(oms.handle(cancel("C2", "C1", '1')));
+
+        assert_eq!(canceled.exec_type, EXEC_TYPE_CANCELED);
+        assert_eq!(canceled.ord_status, '4');
+        assert_eq!(canceled.order_id, ack.order_id);
+        assert_eq!(canceled.cl_ord_id, "C2");
+        assert_eq!(canceled.orig_cl_ord_id.as_deref(), Some("C1"));
+        assert_eq!(canceled.leaves_qty, 0.0);
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C2").unwrap().order_id, ack.order_id);
+
+        let too_late = cancel_reject(oms.handle(cancel("C3", "C2", '1')));
+        assert_eq!(too_late.cxl_rej_reason, Some(TOO_LATE_TO_CANCEL));
+        assert_eq!(too_late.ord_status, '4');
+    }
+
+    #[test]
+    fn test_invalid_cancel_requests_are_rejected() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(new_order("C1", "100"));
+
+        let unknown = cancel_reject(oms.handle(cancel("C2", "NOPE", '1')));
+        assert_eq!(unknown.cxl_rej_reason, Some(UNKNOWN_ORDER_TO_CANCEL));
+        assert_eq!(unknown.order_id, NO_ORDER_ID);
+
+        let wrong_side = cancel_reject(oms.handle(cancel("C2", "C1", '2')));
+        assert_eq!(wrong_side.cxl_rej_reason, Some(CXL_REJ_OTHER));
+        assert_eq!(wrong_side.cxl_rej_response_to, '1');
+        assert_eq!(wrong_side.ord_status, '0');
+
+        let duplicate = cancel_reject(oms.handle(cancel("C1", "C1", '1')));
+        assert_eq!(duplicate.cxl_rej_reason, Some(DUPLICATE_CL_ORD_ID));
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "C1").unwrap().ord_status, OrdStatus::New);
+    }
+
+    #[test]
+    fn test_orders_are_only_known_to_the_counterparty_that_entered_them() {
+        let mut oms = OrderManagementSystem::new();
+        let now = Utc::now();
+        let status_request = |fields: &str| FixMessageParser::parse_message(&format!("35=H|11=1|54=1|55=XYZ|60=20230101-12:00:00|{}", fields));
+
+        let first = report(oms.handle_from(new_order("1", "100"), Some("CLIENT1"), now));
+        let second = report(oms.handle_from(new_order("1", "100"), Some("CLIENT2"), now));
+        assert_eq!((first.exec_type, second.exec_type), (EXEC_TYPE_NEW, EXEC_TYPE_NEW));
+        assert_ne!(first.order_id, second.order_id);
+
+        let unknown = cancel_reject(oms.handle_from(cancel("2", "1", '1'), Some("CLIENT3"), now));
+        assert_eq!(unknown.cxl_rej_reason, Some(UNKNOWN_ORDER_TO_CANCEL));
+        let by_order_id = report(oms.handle_from(status_request(&format!("37={}|", first.order_id)), Some("CLIENT2"), now));
+        assert_eq!(by_order_id.ord_rej_reason, Some(UNKNOWN_ORDER));
+
+        let canceled = report(oms.handle_from(cancel("2", "1", '1'), Some("CLIENT2"), now));
+        assert_eq!((canceled.order_id, canceled.exec_type), (second.order_id, EXEC_TYPE_CANCELED));
+        assert_eq!(report(oms.handle_from(status_request(""), Some("CLIENT1"), now)).ord_status, OrdStatus::New.as_char());
+    }
+
+    #[test]
+    fn test_status_request() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(new_order("C1", "100"));
+
+        let status_request = |cl_ord_id: &str| FixMessageParser::parse_message(&format!("35=H|11={}|54=1|55=XYZ|60=20230101-12:00:00|", cl_ord_id));
+        let status = report(oms.handle(status_request("C1")));
+        assert_eq!(status.exec_type, EXEC_TYPE_ORDER_STATUS);
+        assert_eq!(status.ord_status, '0');
+        assert_eq!(status.leaves_qty, 100.0);
+
+        let unknown = report(oms.handle(status_request("C9")));
+        assert_eq!(unknown.exec_type, EXEC_TYPE_ORDER_STATUS);
+        assert_eq!(unknown.ord_status, '8');
+        assert_eq!(unknown.ord_rej_reason, Some(UNKNOWN_ORDER));
+    }
+
+    #[tokio::test]
+    async fn test_replies_are_routed_to_the_requester() {
+        let (sender, receiver) = mpsc::channel(8);
+        let (reply_sender, mut replies) = mpsc::unbounded_channel();
+        sender.send(OmsRequest::new(new_order("C1", "100"), ReplyTo::Connection(reply_sender))).await.unwrap();
+        drop(sender);
+
+        let (_stop, shutdown) = oneshot::channel();
+        let mut oms = OrderManagementSystem::new();
+        oms.listen_for_orders(receiver, shutdown).await;
+
+        assert!(matches!(replies.try_recv(), Ok(FixMessage::ExecutionReport(report)) if report.cl_ord_id == "C1"));
+    }
+
+    #[test]
+    fn test_crossing_order_trades_with_both_sides() {
+        let mut oms = OrderManagementSystem::new();
+        let maker = report(oms.handle(order("S1", SELL, 100.0, Some(50.0))));
+
+        let taker = reports(oms.handle(order("B1", BUY, 60.0, Some(51.0))));
+
+        assert_eq!(taker.len(), 2);
+        assert_eq!(taker[0].exec_type, EXEC_TYPE_NEW);
+        assert_eq!(taker[1].exec_type, EXEC_TYPE_TRADE);
+        assert_eq!(taker[1].ord_status, '2');
+        assert_eq!(taker[1].last_qty, Some(60.0));
+        assert_eq!(taker[1].last_px, Some(50.0));
+
+        let unsolicited = oms.take_unsolicited();
+        assert_eq!(unsolicited.len(), 1);
+        let (order_id, message) = &unsolicited[0];
+        assert_eq!(order_id, &maker.order_id);
+        match message {
+            FixMessage::ExecutionReport(report) => {
+                assert_eq!(report.exec_type, EXEC_TYPE_TRADE);
+                assert_eq!(report.ord_status, '1');
+                assert_eq!(report.cum_qty, 60.0);
+                assert_eq!(report.leaves_qty, 40.0);
+                assert_eq!(report.last_px, Some(50.0));
+            }
+            other => panic!("Expected an ExecutionReport, got {:?}", other),
+        }
+
+        let book = oms.book("XYZ").unwrap();
+        assert_eq!(book.depth(SELL), vec![BookLevel { price: 50.0, qty: 40.0, orders: 1 }]);
+        assert!(book.depth(BUY).is_empty());
+    }
+
+    #[test]
+    fn test_limit_remainder_rests_and_market_remainder_is_canceled() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order("S1", SELL, 30.0, Some(50.0)));
+
+        let limit = reports(oms.handle(order("B1", BUY, 50.0, Some(50.0))));
+        assert_eq!(limit.last().unwrap().ord_status, '1');
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 50.0, qty: 20.0, orders: 1 }]);
+
+        let market = reports(oms.handle(order("S2", SELL, 50.0, None)));
+        let exec_types: Vec<char> = market.iter().map(|report| report.exec_type).collect();
+        assert_eq!(exec_types, vec![EXEC_TYPE_NEW, EXEC_TYPE_TRADE, EXEC_TYPE_CANCELED]);
+        assert_eq!(market[2].cum_qty, 20.0);
+        assert_eq!(market[2].ord_status, '4');
+        assert!(oms.book("XYZ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_takes_order_off_the_book() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order("B1", BUY, 100.0, Some(50.0)));
+
+        report(oms.handle(cancel("B2", "B1", BUY)));
+        let market = reports(oms.handle(order("S1", SELL, 10.0, None)));
+
+        assert!(oms.book("XYZ").unwrap().is_empty());
+        assert_eq!(market.last().unwrap().exec_type, EXEC_TYPE_CANCELED);
+        assert!(oms.take_unsolicited().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fills_reach_the_resting_order_connection() {
+        let (sender, receiver) = mpsc::channel(8);
+        let (maker_sender, mut maker_replies) = mpsc::unbounded_channel();
+        let (taker_sender, mut taker_replies) = mpsc::unbounded_channel();
+        sender.send(OmsRequest::new(order("S1", SELL, 10.0, Some(50.0)), ReplyTo::Connection(maker_sender))).await.unwrap();
+        sender.send(OmsRequest::new(order("B1", BUY, 10.0, Some(50.0)), ReplyTo::Connection(taker_sender))).await.unwrap();
+
+        // Shutting down still answers what was already queued
+        let (stop, shutdown) = oneshot::channel();
+        stop.send(()).unwrap();
+        let mut oms = OrderManagementSystem::new();
+        oms.listen_for_orders(receiver, shutdown).await;
+        assert!(sender.send(OmsRequest::without_reply(order("B2", BUY, 10.0, Some(50.0)))).await.is_err());
+
+        let exec_types = |replies: &mut mpsc::UnboundedReceiver<FixMessage>| {
+            let mut exec_types = Vec::new();
+            while let Ok(FixMessage::ExecutionReport(report)) = replies.try_recv() {
+                exec_types.push(report.exec_type);
+            }
+            exec_types
+        };
+        assert_eq!(exec_types(&mut maker_replies), vec![EXEC_TYPE_NEW, EXEC_TYPE_TRADE]);
+        assert_eq!(exec_types(&mut taker_replies), vec![EXEC_TYPE_NEW, EXEC_TYPE_TRADE]);
+    }
+
+    #[test]
+    fn test_immediate_or_cancel_and_fill_or_kill() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order("S1", SELL, 30.0, Some(50.0)));
+
+        let fok = report(oms.handle(order_with("B1", BUY, 40.0, Some(50.0), "59=4|")));
+        assert_eq!(fok.exec_type, EXEC_TYPE_REJECTED);
+        assert_eq!(fok.text.as_deref(), Some("Fill or Kill order for 40 can only fill 30"));
+
+        let ioc = reports(oms.handle(order_with("B2", BUY, 40.0, Some(50.0), "59=3|")));
+        let exec_types: Vec<char> = ioc.iter().map(|report| report.exec_type).collect();
+        assert_eq!(exec_types, vec![EXEC_TYPE_NEW, EXEC_TYPE_TRADE, EXEC_TYPE_CANCELED]);
+        assert_eq!(ioc[2].cum_qty, 30.0);
+        assert!(oms.book("XYZ").unwrap().is_empty());
+
+        oms.handle(order("S2", SELL, 30.0, Some(50.0)));
+        let filled = reports(oms.handle(order_with("B3", BUY, 30.0, None, "59=4|")));
+        assert_eq!(filled.last().unwrap().ord_status, '2');
+    }
+
+    #[test]
+    fn test_day_and_good_till_date_orders_expire() {
+        let mut oms = OrderManagementSystem::new();
+        let now = Utc::now();
+        let expire_time = (now + Duration::hours(1)).format(UTC_TIMESTAMP_FORMAT);
+
+        let day = report(oms.handle(order("B1", BUY, 10.0, Some(50.0))));
+        let gtd = report(oms.handle(order_with("B2", BUY, 10.0, Some(49.0), &format!("59=6|126={}|", expire_time))));
+        oms.handle(order_with("B3", BUY, 10.0, Some(48.0), "59=1|"));
+
+        let missing_expiry = report(oms.handle(order_with("B4", BUY, 10.0, Some(48.0), "59=6|")));
+        assert_eq!(missing_expiry.text.as_deref(), Some("Good Till Date order without ExpireTime or ExpireDate"));
+        let expired_date = report(oms.handle(order_with("B5", BUY, 10.0, Some(48.0), "59=6|432=20200101|")));
+        assert_eq!(expired_date.exec_type, EXEC_TYPE_REJECTED);
+
+        oms.on_timer(now + Duration::hours(2));
+        let expired = unsolicited_reports(&mut oms);
+        assert_eq!(expired.len(), 1);
+        assert_eq!((expired[0].order_id.as_str(), expired[0].exec_type, expired[0].ord_status), (gtd.order_id.as_str(), EXEC_TYPE_EXPIRED, 'C'));
+
+        oms.on_timer(now + Duration::days(2));
+        let expired = unsolicited_reports(&mut oms);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].order_id, day.order_id);
+        assert_eq!(expired[0].text.as_deref(), Some("Day order expired at the close"));
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 48.0, qty: 10.0, orders: 1 }]);
+    }
+
+    #[test]
+    fn test_scheduled_auctions_uncross_waiting_orders() {
+        let schedule = SessionSchedule::new(&crate::config::server_config::ScheduleConfig {
+            start_time: "08:00:00".to_string(),
+            end_time: "16:30:00".to_string(),
+            time_zone: "UTC".to_string(),
+            weekdays: None,
+            start_day: None,
+            end_day: None,
+        }).unwrap();
+        let mut oms = OrderManagementSystem::new().with_schedule(schedule);
+        let morning = Utc::now().date_naive().and_hms_opt(7, 0, 0).unwrap().and_utc();
+        oms.on_timer(morning);
+
+        oms.handle(order_with("B1", BUY, 100.0, Some(51.0), "59=2|"));
+        oms.handle(order_with("S1", SELL, 60.0, Some(50.0), "59=2|"));
+        oms.handle(order_with("B2", BUY, 10.0, Some(52.0), "59=7|"));
+        assert!(oms.book("XYZ").is_none());
+
+        oms.on_timer(morning + Duration::hours(1));
+        let opening = unsolicited_reports(&mut oms);
+        let summary: Vec<(&str, char, Option<f64>)> = opening.iter().map(|report| (report.cl_ord_id.as_str(), report.exec_type, report.last_px)).collect();
+        assert_eq!(summary, vec![
+            ("B1", EXEC_TYPE_TRADE, Some(50.0)),
+            ("S1", EXEC_TYPE_TRADE, Some(50.0)),
+            ("B1", EXEC_TYPE_EXPIRED, None),
+        ]);
+        assert_eq!(opening[2].cum_qty, 60.0);
+        assert_eq!(oms.book("XYZ").unwrap().last_price(), Some(50.0));
+
+        oms.on_timer(morning + Duration::hours(10));
+        let closing = unsolicited_reports(&mut oms);
+        assert_eq!(closing.len(), 1);
+        assert_eq!((closing[0].cl_ord_id.as_str(), closing[0].text.as_deref()), ("B2", Some("Unfilled in the closing auction")));
+    }
+
+    #[test]
+    fn test_good_till_cancel_orders_survive_restart() {
+        let path = std::env::temp_dir().join(format!("fix_oms_gtc_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut oms = OrderManagementSystem::new().with_gtc_store(GtcStore::new(&path)).unwrap();
+        let gtc = report(oms.handle(order_with("S1", SELL, 100.0, Some(50.0), "59=1|")));
+        oms.handle(order("S2", SELL, 100.0, Some(51.0)));
+        oms.handle(order("B1", BUY, 40.0, Some(50.0)));
+
+        let mut restarted = OrderManagementSystem::new().with_gtc_store(GtcStore::new(&path)).unwrap();
+        assert_eq!(restarted.orders().len(), 1);
+        assert_eq!(restarted.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 50.0, qty: 60.0, orders: 1 }]);
+
+        let next = report(restarted.handle(order("B2", BUY, 10.0, Some(49.0))));
+        assert_ne!(next.order_id, gtc.order_id);
+        let status = report(restarted.handle(FixMessageParser::parse_message("35=H|11=S1|54=2|55=XYZ|60=20230101-12:00:00|")));
+        assert_eq!((status.ord_status, status.cum_qty), ('1', 40.0));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stop_order_triggers_on_last_traded_price() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order("S1", SELL, 10.0, Some(50.0)));
+        oms.handle(order("S2", SELL, 10.0, Some(51.0)));
+
+        let stop = reports(oms.handle(order_with("B1", BUY, 10.0, None, "40=3|99=50.5|")));
+        assert_eq!(stop.len(), 1);
+        assert_eq!((stop[0].ord_type, stop[0].stop_px), (Some(STOP), Some(50.5)));
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![]);
+
+        oms.handle(order("B2", BUY, 10.0, Some(50.0)));
+        assert!(oms.take_unsolicited().iter().all(|(order_id, _)| order_id != &stop[0].order_id), "50 is below the stop");
+        oms.handle(order("S3", SELL, 10.0, Some(51.0)));
+        oms.handle(order("B3", BUY, 5.0, Some(51.0)));
+
+        let triggered: Vec<ExecutionReport> = reports(oms.take_unsolicited()
+            .into_iter()
+            .filter(|(order_id, _)| order_id == &stop[0].order_id)
+            .map(|(_, message)| message)
+            .collect());
+        let exec_types: Vec<char> = triggered.iter().map(|report| report.exec_type).collect();
+        assert_eq!(exec_types, vec![EXEC_TYPE_TRIGGERED, EXEC_TYPE_TRADE, EXEC_TYPE_TRADE]);
+        assert_eq!(triggered[0].ord_type, Some(MARKET));
+        assert_eq!(triggered[0].text.as_deref(), Some("Stop triggered at last price 51"));
+        assert_eq!((triggered[2].ord_status, triggered[2].cum_qty), ('2', 10.0));
+    }
+
+    #[test]
+    fn test_stop_limit_rests_once_triggered_and_trailing_stop_trails() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order("B1", BUY, 10.0, Some(100.0)));
+        oms.handle(order("S1", SELL, 10.0, Some(100.0)));
+
+        let trailing = report(oms.handle(order_with("S2", SELL, 10.0, None, "40=3|211=2|")));
+        assert_eq!(trailing.stop_px, Some(98.0));
+        let stop_limit = report(oms.handle(order_with("S3", SELL, 10.0, Some(97.0), "40=4|99=97|")));
+
+        oms.handle(order("B2", BUY, 1.0, Some(104.0)));
+        oms.handle(order("S4", SELL, 1.0, Some(104.0)));
+        assert_eq!(oms.orders().get(&trailing.order_id).unwrap().stop_px, Some(102.0));
+
+        oms.handle(order("B3", BUY, 5.0, Some(96.0)));
+        oms.take_unsolicited();
+        oms.handle(order("S5", SELL, 1.0, Some(96.0)));
+
+        let reports_for = |unsolicited: &[(String, FixMessage)], order_id: &str| -> Vec<(char, char)> {
+            unsolicited
+                .iter()
+                .filter(|(reported_order_id, _)| reported_order_id == order_id)
+                .map(|(_, message)| match message {
+                    FixMessage::ExecutionReport(report) => (report.exec_type, report.ord_status),
+                    other => panic!("Expected an ExecutionReport, got {:?}", other),
+                })
+                .collect()
+        };
+        let unsolicited = oms.take_unsolicited();
+        assert_eq!(reports_for(&unsolicited, &trailing.order_id), vec![
+            (EXEC_TYPE_TRIGGERED, '0'),
+            (EXEC_TYPE_TRADE, '1'),
+            (EXEC_TYPE_CANCELED, '4'),
+        ]);
+        assert_eq!(reports_for(&unsolicited, &stop_limit.order_id), vec![(EXEC_TYPE_TRIGGERED, '0')]);
+        assert_eq!(oms.orders().get(&stop_limit.order_id).unwrap().ord_type, LIMIT);
+        assert_eq!(oms.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 97.0, qty: 10.0, orders: 1 }]);
+    }
+
+    #[test]
+    fn test_stop_orders_need_a_stop_price() {
+        let mut oms = OrderManagementSystem::new();
+
+        let no_stop_px = report(oms.handle(order_with("B1", BUY, 10.0, None, "40=3|")));
+        assert_eq!(no_stop_px.text.as_deref(), Some("Stop order without a StopPx"));
+        let untraded = report(oms.handle(order_with("B2", BUY, 10.0, None, "40=3|211=1|")));
+        assert_eq!(untraded.text.as_deref(), Some("Trailing stop without a StopPx before XYZ has traded"));
+        let no_price = report(oms.handle(order_with("B3", BUY, 10.0, None, "40=4|99=10|")));
+        assert_eq!(no_price.text.as_deref(), Some("Stop limit order without a Price"));
+        let pegged = report(oms.handle(order_with("B4", BUY, 10.0, None, "40=P|")));
+        assert_eq!(pegged.text.as_deref(), Some("Unsupported OrdType P"));
+    }
+
+    #[test]
+    fn test_risk_checks_reject_before_the_book() {
+        let config = crate::config::server_config::RiskConfig {
+            max_order_qty: Some(1000.0),
+            max_open_orders_per_account: Some(1),
+            ..Default::default()
+        };
+        let mut oms = OrderManagementSystem::new().with_risk_checks(RiskChecks::new(&config));
+
+        let too_big = report(oms.handle(order("B1", BUY, 5000.0, Some(50.0))));
+        assert_eq!((too_big.exec_type, too_big.ord_rej_reason), (EXEC_TYPE_REJECTED, Some(ORDER_EXCEEDS_LIMIT)));
+        assert_eq!(too_big.text.as_deref(), Some("OrderQty 5000 exceeds the maximum of 1000"));
+
+        report(oms.handle(order_with("B2", BUY, 10.0, Some(50.0), "1=ACC1|")));
+        let second = report(oms.handle(order_with("B3", BUY, 10.0, Some(49.0), "1=ACC1|")));
+        assert_eq!(second.text.as_deref(), Some("Account ACC1 already has the maximum of 1 open orders"));
+        assert_eq!(oms.orders().get_by_cl_ord_id(None, "B3").unwrap().ord_status, OrdStatus::Rejected);
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 50.0, qty: 10.0, orders: 1 }]);
+
+        let other_account = report(oms.handle(order_with("B4", BUY, 10.0, Some(49.0), "1=ACC2|")));
+        assert_eq!(other_account.exec_type, EXEC_TYPE_NEW);
+    }
+
+    #[test]
+    fn test_fills_update_positions() {
+        let mut oms = OrderManagementSystem::new();
+        oms.handle(order_with("S1", SELL, 100.0, Some(50.0), "1=SELLER|"));
+        oms.handle(order_with("B1", BUY, 60.0, Some(50.0), "1=BUYER|"));
+        oms.handle(order_with("B2", BUY, 40.0, Some(50.0), "1=SELLER|"));
+
+        let positions = oms.positions();
+        let positions = positions.lock().unwrap();
+        let buyer = &positions.positions(Some("BUYER"))[0];
+        assert_eq!((buyer.symbol.as_str(), buyer.net_qty, buyer.avg_cost), ("XYZ", 60.0, 50.0));
+        let seller = &positions.positions(Some("SELLER"))[0];
+        assert_eq!((seller.net_qty, seller.realised_pnl, seller.mark_price), (-60.0, 0.0, Some(50.0)));
+    }
+
+    #[test]
+    fn test_iceberg_order_shows_max_floor_and_replenishes() {
+        let mut oms = OrderManagementSystem::new();
+        let ack = report(oms.handle(order_with("S1", SELL, 100.0, Some(50.0), "111=20|")));
+        assert_eq!((ack.leaves_qty, ack.max_floor, ack.display_qty), (100.0, Some(20.0), Some(20.0)));
+        assert_eq!(oms.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 50.0, qty: 20.0, orders: 1 }]);
+
+        let taker = reports(oms.handle(order("B1", BUY, 30.0, Some(50.0))));
+        assert_eq!(taker.iter().filter_map(|report| report.last_qty).collect::<Vec<_>>(), vec![20.0, 10.0]);
+        let maker: Vec<_> = unsolicited_reports(&mut oms).into_iter().map(|report| (report.leaves_qty, report.display_qty)).collect();
+        assert_eq!(maker, vec![(80.0, Some(20.0)), (70.0, Some(10.0))]);
+        assert_eq!(oms.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 50.0, qty: 10.0, orders: 1 }]);
+
+        let status = report(oms.handle(FixMessageParser::parse_message("35=H|11=S1|54=2|55=XYZ|60=20230101-12:00:00|")));
+        assert_eq!((status.leaves_qty, status.display_qty), (70.0, Some(10.0)));
+        let too_big = report(oms.handle(order_with("S2", SELL, 10.0, Some(50.0), "111=20|")));
+        assert_eq!(too_big.text.as_deref(), Some("MaxFloor 20 exceeds OrderQty 10"));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_cancels_or_decrements_own_orders() {
+        let prevention = SelfTradePrevention { mode: SelfTradeMode::CancelResting, key: SelfTradeKey::Account };
+        let mut oms = OrderManagementSystem::new().with_self_trade_prevention(prevention);
+        oms.handle(order_with("S1", SELL, 10.0, Some(50.0), "1=A|"));
+        oms.handle(order_with("S2", SELL, 10.0, Some(50.0), "1=B|"));
+        let taker = reports(oms.handle(order_with("B1", BUY, 15.0, Some(50.0), "1=A|")));
+        assert_eq!(taker.iter().map(|report| (report.exec_type, report.leaves_qty)).collect::<Vec<_>>(), vec![(EXEC_TYPE_NEW, 15.0), (EXEC_TYPE_TRADE, 5.0)]);
+        let makers: Vec<_> = unsolicited_reports(&mut oms).into_iter().map(|report| (report.cl_ord_id, report.exec_type)).collect();
+        assert_eq!(makers, vec![("S1".to_string(), EXEC_TYPE_CANCELED), ("S2".to_string(), EXEC_TYPE_TRADE)]);
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 50.0, qty: 5.0, orders: 1 }]);
+
+        let prevention = SelfTradePrevention { mode: SelfTradeMode::DecrementAndCancel, key: SelfTradeKey::SelfMatchPreventionId };
+        let mut oms = OrderManagementSystem::new().with_self_trade_prevention(prevention);
+        oms.handle(order_with("S1", SELL, 10.0, Some(50.0), "1=A|2362=FIRM|"));
+        let smaller = reports(oms.handle(order_with("B1", BUY, 4.0, Some(50.0), "1=B|2362=FIRM|")));
+        assert_eq!((smaller[1].exec_type, smaller[1].text.as_deref()), (EXEC_TYPE_CANCELED, Some("Canceled by self-trade prevention")));
+        let restated = unsolicited_reports(&mut oms).remove(0);
+        assert_eq!((restated.exec_type, restated.order_qty, restated.leaves_qty), (EXEC_TYPE_RESTATED, 6.0, 6.0));
+
+        let larger = reports(oms.handle(order_with("B2", BUY, 8.0, Some(50.0), "2362=FIRM|")));
+        assert_eq!((larger[1].exec_type, larger[1].order_qty, larger[1].leaves_qty), (EXEC_TYPE_RESTATED, 2.0, 2.0));
+        assert_eq!(unsolicited_reports(&mut oms)[0].exec_type, EXEC_TYPE_CANCELED);
+        assert_eq!(oms.book("XYZ").unwrap().depth(SELL), vec![]);
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 50.0, qty: 2.0, orders: 1 }]);
+
+        let fill_or_kill = report(oms.handle(order_with("S2", SELL, 2.0, Some(50.0), "59=4|2362=FIRM|")));
+        assert_eq!(fill_or_kill.exec_type, EXEC_TYPE_REJECTED);
+    }
+
+    #[test]
+    fn test_mass_cancel_is_scoped_to_the_request_and_its_session() {
+        let mut oms = OrderManagementSystem::new();
+        let now = Utc::now();
+        oms.handle_from(order_with("A1", BUY, 10.0, Some(49.0), "1=A|"), Some("CLIENT1"), now);
+        oms.handle_from(order_with("A2", SELL, 10.0, Some(51.0), "1=A|"), Some("CLIENT2"), now);
+        oms.handle_from(order_with("B1", BUY, 10.0, Some(48.0), "1=B|"), Some("CLIENT1"), now);
+        let mass_cancel = |fields: &str| FixMessageParser::parse_message(&format!("35=q|11=M1|60=20230101-12:00:00|{}", fields));
+        let mass_cancel_report = |replies: Vec<FixMessage>| match replies.into_iter().next() {
+            Some(FixMessage::OrderMassCancelReport(report)) => report,
+            other => panic!("Expected an OrderMassCancelReport, got {:?}", other),
+        };
+
+        let own = mass_cancel_report(oms.handle_from(mass_cancel("530=7|1=A|"), Some("CLIENT1"), now));
+        assert_eq!((own.mass_cancel_response, own.total_affected_orders), (MASS_CANCEL_ALL, Some(1)));
+        let canceled = unsolicited_reports(&mut oms).remove(0);
+        assert_eq!((canceled.cl_ord_id.as_str(), canceled.exec_type, canceled.text.as_deref()), ("A1", EXEC_TYPE_CANCELED, Some("Canceled by mass cancel M1")));
+
+        assert_eq!(mass_cancel_report(oms.handle(mass_cancel("530=1|55=ABC|"))).total_affected_orders, Some(0));
+        let sells = mass_cancel_report(oms.handle(mass_cancel("530=1|55=XYZ|54=2|")));
+        assert_eq!(sells.total_affected_orders, Some(1));
+        assert_eq!(unsolicited_reports(&mut oms).remove(0).cl_ord_id, "A2");
+        assert_eq!(oms.book("XYZ").unwrap().depth(BUY), vec![BookLevel { price: 48.0, qty: 10.0, orders: 1 }]);
+
+        let rejected = mass_cancel_report(oms.handle(mass_cancel("530=1|")));
+        assert_eq!((rejected.mass_cancel_response, rejected.mass_cancel_reject_reason), (MASS_CANCEL_REJECTED, Some(INVALID_SECURITY)));
+        let unsupported = mass_cancel_report(oms.handle(mass_cancel("530=3|")));
+        assert_eq!(unsupported.mass_cancel_reject_reason, Some(MASS_CANCEL_NOT_SUPPORTED));
+    }
+
+    #[test]
+    fn test_exec_ids_are_only_drawn_for_reports_sent() {
+        let mut oms = OrderManagementSystem::new().with_id_format("LDN-", 6);
+        // A resting remainder and a status request for an unknown order draw one ExecID each
+        let resting = report(oms.handle(order("B1", BUY, 10.0, Some(49.0))));
+        let unknown = report(oms.handle(FixMessageParser::parse_message("35=H|11=NONE|54=1|55=XYZ|60=20230101-12:00:00|")));
+        let next = report(oms.handle(order("B2", BUY, 10.0, Some(49.0))));
+        let exec_ids = [resting, unknown, next].map(|report| report.exec_id);
+        assert_eq!(exec_ids, ["LDN-EXEC000001", "LDN-EXEC000002", "LDN-EXEC000003"]);
+    }
+
+    #[test]
+    fn test_ids_carry_on_after_a_restart_with_the_id_store() {
+        let directory = std::env::temp_dir().join(format!("fix_oms_ids_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let open = |journal: bool| {
+            let oms = OrderManagementSystem::new()
+                .with_id_format("LDN-", 6)
+                .with_id_store(IdStore::new(directory.join("ids.json")))
+                .unwrap();
+            match journal {
+                true => oms.with_journal(Journal::open(directory.join("journal")).unwrap()).unwrap(),
+                false => oms,
             }
+        };
+
+        let mut oms = open(false);
+        let first = report(oms.handle(order("B1", BUY, 10.0, Some(49.0))));
+        assert_eq!((first.order_id.as_str(), first.exec_id.as_str()), ("LDN-ORD000001", "LDN-EXEC000001"));
+
+        // Without a journal the store alone keeps IDs from being issued again
+        let mut restarted = open(false);
+        let next = report(restarted.handle(order("B2", BUY, 10.0, Some(49.0))));
+        assert_eq!(next.order_id, "LDN-ORD000002");
+        assert!(next.exec_id > first.exec_id, "padded IDs sort in the order issued");
+
+        // With a journal started afresh, replay reproduces the journaled IDs and the store moves
+        // new ones on past everything issued before
+        let mut journaled = open(true);
+        journaled.handle(order("B3", BUY, 10.0, Some(49.0)));
+        let mut restarted = open(true);
+        assert!(restarted.orders().get("LDN-ORD000003").is_some_and(|order| order.cl_ord_id == "B3"));
+        assert_eq!(report(restarted.handle(order("B4", BUY, 10.0, Some(49.0)))).order_id, "LDN-ORD000004");
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_journal_replay_rebuilds_the_oms() {
+        let path = std::env::temp_dir().join(format!("fix_oms_journal_{}", std::process::id())).join("journal");
+        let _ = std::fs::remove_file(&path);
+        let start = Utc::now();
+
+        let mut oms = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        oms.on_timer(start);
+        oms.handle_at(order_with("S1", SELL, 100.0, Some(50.0), "1=SELLER|"), start);
+        oms.handle_at(order_with("S2", SELL, 10.0, Some(51.0), "59=1|"), start);
+        oms.handle_at(order_with("B1", BUY, 60.0, Some(50.0), "1=BUYER|"), start + Duration::minutes(1));
+        oms.handle_at(cancel("S2-CXL", "S2", SELL), start + Duration::minutes(2));
+        oms.take_unsolicited();
+
+        let mut restarted = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        assert!(restarted.take_unsolicited().is_empty(), "reports are not sent twice");
+        assert_eq!(restarted.orders().len(), 3);
+        for order in oms.orders().orders() {
+            let replayed = restarted.orders().get(&order.order_id).unwrap();
+            assert_eq!((&replayed.cl_ord_id, replayed.ord_status, replayed.cum_qty), (&order.cl_ord_id, order.ord_status, order.cum_qty));
+            assert_eq!(replayed.events, order.events);
         }
+        assert_eq!(restarted.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 50.0, qty: 40.0, orders: 1 }]);
+        assert_eq!(restarted.positions().lock().unwrap().positions(None), oms.positions().lock().unwrap().positions(None));
+
+        let next = report(restarted.handle(order("B2", BUY, 10.0, Some(49.0))));
+        let expected = report(oms.handle(order("B2", BUY, 10.0, Some(49.0))));
+        assert_eq!((next.order_id, next.exec_id), (expected.order_id, expected.exec_id));
+
+        // Replaying up to a point in time shows the OMS as it was then
+        let mut earlier = OrderManagementSystem::new();
+        earlier.replay(Journal::read(&path).unwrap().into_iter().take_while(|entry| entry.time <= start));
+        assert_eq!(earlier.orders().len(), 2);
+        assert_eq!(earlier.book("XYZ").unwrap().depth(SELL).len(), 2);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_values_with_line_breaks_or_delimiters_never_reach_the_journal() {
+        let path = std::env::temp_dir().join(format!("fix_oms_journal_values_{}", std::process::id())).join("journal");
+        let _ = std::fs::remove_file(&path);
+
+        let mut oms = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        oms.handle(order("C1", BUY, 100.0, Some(50.0)));
+        let line_break = FixMessageParser::parse_message("35=D|11=C2|54=1|60=20230101-12:00:00|38=100|40=2|44=50|55=XYZ\n");
+        let delimiter = FixMessageParser::parse_message("35=D\x0111=C|3\x0154=1\x0160=20230101-12:00:00\x0138=100\x0140=2\x0144=50\x0155=XYZ\x01");
+        assert!(matches!((&line_break, &delimiter), (FixMessage::Unknown, FixMessage::Unknown)));
+        assert!(oms.handle(line_break).is_empty());
+        assert!(oms.handle(delimiter).is_empty());
+        oms.handle(order("C4", SELL, 40.0, Some(50.0)));
+
+        let restarted = OrderManagementSystem::new().with_journal(Journal::open(&path).unwrap()).unwrap();
+        assert_eq!(restarted.orders().len(), 2);
+        for order in oms.orders().orders() {
+            let replayed = restarted.orders().get(&order.order_id).unwrap();
+            assert_eq!((&replayed.cl_ord_id, &replayed.symbol, replayed.ord_status, replayed.cum_qty), (&order.cl_ord_id, &order.symbol, order.ord_status, order.cum_qty));
+        }
+        assert_eq!(restarted.book("XYZ").unwrap().depth(BUY), oms.book("XYZ").unwrap().depth(BUY));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
     }
 
-    fn process_new_order(&self, order: NewOrder) {
-        println!("Processing new order: {:?}", order);
-        // logic to follow ...
+    #[test]
+    fn test_restart_replays_the_journal_from_the_latest_snapshot() {
+        let directory = std::env::temp_dir().join(format!("fix_oms_snapshots_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let journal_path = directory.join("journal");
+        let start = Utc::now();
+        let open = || {
+            OrderManagementSystem::new()
+                .with_snapshots(SnapshotStore::new(directory.join("snapshots")), Duration::minutes(5))
+                .with_journal(Journal::open(&journal_path).unwrap())
+                .unwrap()
+        };
+
+        let mut oms = open();
+        oms.handle_at(order_with("S1", SELL, 100.0, Some(50.0), "1=SELLER|"), start);
+        oms.handle_at(order_with("B1", BUY, 60.0, Some(50.0), "1=BUYER|"), start);
+        oms.on_timer(start + Duration::minutes(6));
+        oms.handle_at(order_with("B2", BUY, 10.0, Some(50.0), "1=BUYER|"), start + Duration::minutes(7));
+
+        // Only the entries after the snapshot are needed to rebuild the OMS
+        let entries = Journal::read(&journal_path).unwrap();
+        let tail: String = std::fs::read_to_string(&journal_path).unwrap().lines().skip(entries.len() - 4).map(|line| format!("{}\n", line)).collect();
+        std::fs::write(&journal_path, tail).unwrap();
+
+        let restarted = open();
+        assert_eq!(restarted.orders().len(), 3);
+        assert_eq!(restarted.orders().get_by_cl_ord_id(None, "S1").unwrap().cum_qty, 70.0);
+        assert_eq!(restarted.book("XYZ").unwrap().depth(SELL), vec![BookLevel { price: 50.0, qty: 30.0, orders: 1 }]);
+        assert_eq!(restarted.positions().lock().unwrap().positions(None), oms.positions().lock().unwrap().positions(None));
+
+        let _ = std::fs::remove_dir_all(&directory);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,152 @@
+use crate::oms::order::Order;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Orders of record, addressable by the OMS-assigned OrderID and by the client's ClOrdID.
+/// ClOrdIDs are only unique per counterparty, so they are kept under the CompID that entered
+/// the order, or an empty one for orders entered outside any session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrderStore {
+    orders: HashMap<String, Order>,
+    order_ids_by_cl_ord_id: HashMap<String, HashMap<String, String>>,
+}
+
+impl OrderStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new order, refusing a ClOrdID its counterparty already used or an OrderID that is
+    /// already in use.
+    pub fn insert(&mut self, order: Order) -> Result<(), String> {
+        if self.contains_cl_ord_id(order.session.as_deref(), &order.cl_ord_id) {
+            return Err(format!("Duplicate ClOrdID {}", order.cl_ord_id));
+        }
+        if self.orders.contains_key(&order.order_id) {
+            return Err(format!("Duplicate OrderID {}", order.order_id));
+        }
+        self.cl_ord_ids_of(order.session.as_deref()).insert(order.cl_ord_id.clone(), order.order_id.clone());
+        self.orders.insert(order.order_id.clone(), order);
+        Ok(())
+    }
+
+    /// Makes the order reachable under a further ClOrdID, as assigned by a cancel or replace
+    /// request. Earlier ClOrdIDs keep resolving to the same order.
+    pub fn add_cl_ord_id(&mut self, order_id: &str, cl_ord_id: &str) -> Result<(), String> {
+        let session = match self.orders.get(order_id) {
+            Some(order) => order.session.clone(),
+            None => return Err(format!("Unknown OrderID {}", order_id)),
+        };
+        if self.contains_cl_ord_id(session.as_deref(), cl_ord_id) {
+            return Err(format!("Duplicate ClOrdID {}", cl_ord_id));
+        }
+        self.cl_ord_ids_of(session.as_deref()).insert(cl_ord_id.to_string(), order_id.to_string());
+        Ok(())
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&Order> {
+        self.orders.get(order_id)
+    }
+
+    pub fn get_mut(&mut self, order_id: &str) -> Option<&mut Order> {
+        self.orders.get_mut(order_id)
+    }
+
+    /// The order `session` entered under `cl_ord_id`.
+    pub fn get_by_cl_ord_id(&self, session: Option<&str>, cl_ord_id: &str) -> Option<&Order> {
+        self.order_id_of(session, cl_ord_id).and_then(|order_id| self.orders.get(order_id))
+    }
+
+    pub fn get_by_cl_ord_id_mut(&mut self, session: Option<&str>, cl_ord_id: &str) -> Option<&mut Order> {
+        let order_id = self.order_id_of(session, cl_ord_id)?.clone();
+        self.orders.get_mut(&order_id)
+    }
+
+    pub fn contains_cl_ord_id(&self, session: Option<&str>, cl_ord_id: &str) -> bool {
+        self.order_id_of(session, cl_ord_id).is_some()
+    }
+
+    /// Every ClOrdID an order has gone by with the CompID that used it, including those
+    /// replaced by later requests.
+    pub fn cl_ord_ids(&self) -> impl Iterator<Item = (Option<&str>, &str)> {
+        self.order_ids_by_cl_ord_id.iter().flat_map(|(session, cl_ord_ids)| {
+            let session = Some(session.as_str()).filter(|session| !session.is_empty());
+            cl_ord_ids.keys().map(move |cl_ord_id| (session, cl_ord_id.as_str()))
+        })
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn order_id_of(&self, session: Option<&str>, cl_ord_id: &str) -> Option<&String> {
+        self.order_ids_by_cl_ord_id.get(session.unwrap_or_default()).and_then(|cl_ord_ids| cl_ord_ids.get(cl_ord_id))
+    }
+
+    fn cl_ord_ids_of(&mut self, session: Option<&str>) -> &mut HashMap<String, String> {
+        self.order_ids_by_cl_ord_id.entry(session.unwrap_or_default().to_string()).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message::FixMessage;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use crate::oms::order::OrdStatus;
+    use chrono::Utc;
+
+    fn order(order_id: &str, cl_ord_id: &str) -> Order {
+        let message = format!("35=D|11={}|54=1|60=20230101-12:00:00|38=100|40=2|44=50.5|55=XYZ|", cl_ord_id);
+        match FixMessageParser::parse_message(&message) {
+            FixMessage::NewOrder(new_order) => Order::new(order_id.to_string(), &new_order, Utc::now()),
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        }
+    }
+
+    fn order_from(session: &str, order_id: &str, cl_ord_id: &str) -> Order {
+        Order { session: Some(session.to_string()), ..order(order_id, cl_ord_id) }
+    }
+
+    #[test]
+    fn test_lookup_by_order_id_and_cl_ord_id() {
+        let mut store = OrderStore::new();
+        store.insert(order("O1", "C1")).unwrap();
+
+        store.get_by_cl_ord_id_mut(None, "C1").unwrap().transition(OrdStatus::New, Utc::now(), None).unwrap();
+
+        assert_eq!(store.get("O1").unwrap().ord_status, OrdStatus::New);
+        assert_eq!(store.get_by_cl_ord_id(None, "C1").unwrap().order_id, "O1");
+        assert!(store.get_by_cl_ord_id(None, "C2").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_refused() {
+        let mut store = OrderStore::new();
+        store.insert(order("O1", "C1")).unwrap();
+
+        assert_eq!(store.insert(order("O2", "C1")).unwrap_err(), "Duplicate ClOrdID C1");
+        assert_eq!(store.insert(order("O1", "C2")).unwrap_err(), "Duplicate OrderID O1");
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_cl_ord_ids_are_unique_per_counterparty() {
+        let mut store = OrderStore::new();
+        store.insert(order_from("CLIENT1", "O1", "1")).unwrap();
+        store.insert(order_from("CLIENT2", "O2", "1")).unwrap();
+
+        assert_eq!(store.insert(order_from("CLIENT1", "O3", "1")).unwrap_err(), "Duplicate ClOrdID 1");
+        assert_eq!(store.get_by_cl_ord_id(Some("CLIENT1"), "1").unwrap().order_id, "O1");
+        assert_eq!(store.get_by_cl_ord_id(Some("CLIENT2"), "1").unwrap().order_id, "O2");
+        assert!(store.get_by_cl_ord_id(None, "1").is_none());
+    }
+}
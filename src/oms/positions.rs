@@ -0,0 +1,183 @@
+use crate::oms::order::BUY;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+/// Net position of one account in one symbol, with its P&L.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Position {
+    pub account: Option<String>,
+    pub symbol: String,
+    /// Signed quantity: long positive, short negative.
+    pub net_qty: f64,
+    /// Average price the open quantity was entered at.
+    pub avg_cost: f64,
+    /// P&L locked in by quantity that has been closed out.
+    pub realised_pnl: f64,
+    /// P&L of the open quantity at the mark price.
+    pub unrealised_pnl: f64,
+    /// Last traded price of the symbol, if it has traded.
+    pub mark_price: Option<f64>,
+}
+
+impl Position {
+    /// Applies a fill of `qty` at `px`. Quantity in the direction of the position raises it at a
+    /// blended average cost; quantity against it realises P&L and may flip the position.
+    fn fill(&mut self, side: char, qty: f64, px: f64) {
+        let signed_qty = if side == BUY { qty } else { -qty };
+        if self.net_qty == 0.0 || self.net_qty.signum() == signed_qty.signum() {
+            let open_qty = self.net_qty.abs();
+            self.avg_cost = (self.avg_cost * open_qty + px * qty) / (open_qty + qty);
+            self.net_qty += signed_qty;
+            return;
+        }
+
+        let closed_qty = qty.min(self.net_qty.abs());
+        self.realised_pnl += closed_qty * (px - self.avg_cost) * self.net_qty.signum();
+        self.net_qty += signed_qty;
+        if self.net_qty == 0.0 {
+            self.avg_cost = 0.0;
+        } else if qty > closed_qty {
+            self.avg_cost = px;
+        }
+    }
+
+    fn unrealised_pnl(&self, mark_price: Option<f64>) -> f64 {
+        // Adding zero turns the -0 of a flat mark on a short position into 0
+        mark_price.map_or(0.0, |mark_price| (mark_price - self.avg_cost) * self.net_qty + 0.0)
+    }
+}
+
+/// Positions per account and symbol, built from the OMS's fills and marked to the last traded
+/// price of each symbol.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "PositionsRecord", into = "PositionsRecord")]
+pub struct Positions {
+    positions: BTreeMap<(Option<String>, String), Position>,
+    marks: HashMap<String, f64>,
+}
+
+impl Positions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fill(&mut self, account: Option<&str>, symbol: &str, side: char, qty: f64, px: f64) {
+        let key = (account.map(str::to_string), symbol.to_string());
+        let position = self.positions.entry(key).or_insert_with(|| Position {
+            account: account.map(str::to_string),
+            symbol: symbol.to_string(),
+            ..Position::default()
+        });
+        position.fill(side, qty, px);
+    }
+
+    pub fn mark(&mut self, symbol: &str, mark_price: f64) {
+        self.marks.insert(symbol.to_string(), mark_price);
+    }
+
+    /// Positions of one account, or of every account, with unrealised P&L at the current marks.
+    pub fn positions(&self, account: Option<&str>) -> Vec<Position> {
+        self.positions
+            .values()
+            .filter(|position| account.is_none() || position.account.as_deref() == account)
+            .map(|position| {
+                let mark_price = self.marks.get(&position.symbol).copied();
+                Position { mark_price, unrealised_pnl: position.unrealised_pnl(mark_price), ..position.clone() }
+            })
+            .collect()
+    }
+
+    /// Starts a new trading day: realised P&L goes back to zero and open positions carry over,
+    /// re-based to the mark so unrealised P&L counts from the previous close. Flat positions
+    /// are dropped.
+    pub fn start_of_day(&mut self) {
+        let marks = &self.marks;
+        self.positions.retain(|_, position| position.net_qty != 0.0);
+        for position in self.positions.values_mut() {
+            position.realised_pnl = 0.0;
+            if let Some(mark_price) = marks.get(&position.symbol) {
+                position.avg_cost = *mark_price;
+            }
+        }
+    }
+}
+
+/// Serialised form of `Positions`, whose (Account, Symbol) keys have no JSON equivalent.
+#[derive(Serialize, Deserialize)]
+struct PositionsRecord {
+    positions: Vec<Position>,
+    marks: HashMap<String, f64>,
+}
+
+impl From<Positions> for PositionsRecord {
+    fn from(positions: Positions) -> Self {
+        PositionsRecord { positions: positions.positions.into_values().collect(), marks: positions.marks }
+    }
+}
+
+impl From<PositionsRecord> for Positions {
+    fn from(record: PositionsRecord) -> Self {
+        let positions = record
+            .positions
+            .into_iter()
+            .map(|position| ((position.account.clone(), position.symbol.clone()), position))
+            .collect();
+        Positions { positions, marks: record.marks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oms::order::SELL;
+
+    fn position(positions: &Positions, account: &str, symbol: &str) -> Position {
+        positions
+            .positions(Some(account))
+            .into_iter()
+            .find(|position| position.symbol == symbol)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_average_cost_and_realised_pnl() {
+        let mut positions = Positions::new();
+        positions.fill(Some("ACC1"), "XYZ", BUY, 100.0, 10.0);
+        positions.fill(Some("ACC1"), "XYZ", BUY, 100.0, 12.0);
+        positions.fill(Some("ACC1"), "XYZ", SELL, 50.0, 13.0);
+        positions.mark("XYZ", 14.0);
+
+        let long = position(&positions, "ACC1", "XYZ");
+        assert_eq!((long.net_qty, long.avg_cost), (150.0, 11.0));
+        assert_eq!(long.realised_pnl, 100.0);
+        assert_eq!(long.unrealised_pnl, 450.0);
+        assert_eq!(long.mark_price, Some(14.0));
+
+        // Selling through the position flips it short at the fill price
+        positions.fill(Some("ACC1"), "XYZ", SELL, 200.0, 15.0);
+        let short = position(&positions, "ACC1", "XYZ");
+        assert_eq!((short.net_qty, short.avg_cost), (-50.0, 15.0));
+        assert_eq!(short.realised_pnl, 700.0);
+        assert_eq!(short.unrealised_pnl, 50.0);
+    }
+
+    #[test]
+    fn test_positions_are_kept_per_account_and_reset_at_start_of_day() {
+        let mut positions = Positions::new();
+        positions.fill(Some("ACC1"), "XYZ", BUY, 10.0, 10.0);
+        positions.fill(Some("ACC2"), "XYZ", SELL, 10.0, 10.0);
+        positions.fill(Some("ACC2"), "ABC", BUY, 5.0, 20.0);
+        positions.fill(Some("ACC2"), "ABC", SELL, 5.0, 22.0);
+        positions.mark("XYZ", 11.0);
+
+        assert_eq!(positions.positions(None).len(), 3);
+        assert_eq!(position(&positions, "ACC2", "XYZ").unrealised_pnl, -10.0);
+        assert_eq!(position(&positions, "ACC2", "ABC").realised_pnl, 10.0);
+
+        positions.start_of_day();
+        assert_eq!(positions.positions(Some("ACC2")).len(), 1, "flat positions are dropped");
+        let carried = position(&positions, "ACC1", "XYZ");
+        assert_eq!((carried.net_qty, carried.avg_cost, carried.unrealised_pnl), (10.0, 11.0, 0.0));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::config::server_config::RiskConfig;
+use crate::oms::order::Order;
+use crate::oms::order_management_system::{OrderManagementSystem, ORDER_EXCEEDS_LIMIT, OTHER, PRICE_EXCEEDS_BAND};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// What an account already has working, as counted against its limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AccountExposure {
+    pub open_orders: usize,
+    pub gross_exposure: f64,
+}
+
+/// What one account has working on each OMS shard, keyed by shard.
+type ShardExposures = HashMap<usize, AccountExposure>;
+
+/// What every account has working on each OMS shard, keyed by account. The shards' risk checks
+/// share it, so an account's limits hold across all of its symbols rather than once per shard.
+#[derive(Debug, Clone, Default)]
+pub struct SharedExposure {
+    accounts: Arc<Mutex<HashMap<Option<String>, ShardExposures>>>,
+}
+
+impl SharedExposure {
+    pub fn new() -> Self {
+        SharedExposure::default()
+    }
+}
+
+/// Pre-trade checks a new order must pass before the OMS accepts it. Failures carry the
+/// OrdRejReason (103) and the Text (58) naming the limit that was hit.
+#[derive(Debug, Clone, Default)]
+pub struct RiskChecks {
+    config: RiskConfig,
+    /// The exposure shared with the other shards, and the shard these checks run on.
+    shared: Option<(SharedExposure, usize)>,
+}
+
+impl RiskChecks {
+    pub fn new(config: &RiskConfig) -> Self {
+        RiskChecks { config: config.clone(), shared: None }
+    }
+
+    /// Counts what the account has working on the other shards of `shared` towards its limits,
+    /// these checks running on shard `shard`.
+    pub fn with_shared_exposure(mut self, shared: SharedExposure, shard: usize) -> Self {
+        self.shared = Some((shared, shard));
+        self
+    }
+
+    pub fn shares_exposure(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// Records what `account` has working on this shard, for the other shards to count.
+    pub fn publish(&self, account: Option<&str>, exposure: AccountExposure) {
+        if let Some((shared, shard)) = &self.shared {
+            shared.accounts.lock().unwrap().entry(account.map(str::to_string)).or_default().insert(*shard, exposure);
+        }
+    }
+
+    /// Records what every account has working in `oms`, as restored from its stores.
+    pub fn publish_working(&self, oms: &OrderManagementSystem) {
+        let accounts: HashSet<_> = oms.orders().orders().filter(|order| !order.ord_status.is_terminal()).map(|order| order.account.as_deref()).collect();
+        for account in accounts {
+            self.publish(account, oms.account_exposure(account));
+        }
+    }
+
+    /// Checks `order` given the last traded price in its symbol and what its account has working
+    /// on this shard. With a shared exposure the account's orders on the other shards count too,
+    /// and an order that passes is counted at once, so that two shards checking orders for one
+    /// account together cannot both take its last headroom.
+    pub fn check(&self, order: &Order, last_px: Option<f64>, account: AccountExposure) -> Result<(), (u32, String)> {
+        let (shared, shard) = match &self.shared {
+            Some(shared) => shared,
+            None => return self.check_against(order, last_px, account),
+        };
+        let mut accounts = shared.accounts.lock().unwrap();
+        let shards = accounts.entry(order.account.clone()).or_default();
+        let total = shards
+            .iter()
+            .filter(|(other, _)| *other != shard)
+            .fold(account, |total, (_, exposure)| AccountExposure {
+                open_orders: total.open_orders + exposure.open_orders,
+                gross_exposure: total.gross_exposure + exposure.gross_exposure,
+            });
+        self.check_against(order, last_px, total)?;
+        let notional = valuation_price(order, last_px).unwrap_or(0.0) * order.order_qty;
+        shards.insert(*shard, AccountExposure { open_orders: account.open_orders + 1, gross_exposure: account.gross_exposure + notional });
+        Ok(())
+    }
+
+    fn check_against(&self, order: &Order, last_px: Option<f64>, account: AccountExposure) -> Result<(), (u32, String)> {
+        let config = &self.config;
+
+        if config.restricted_symbols.contains(&order.symbol) {
+            return Err((OTHER, format!("Symbol {} is restricted", order.symbol)));
+        }
+
+        if let Some(max_order_qty) = config.max_order_qty.filter(|max| order.order_qty > *max) {
+            return Err((ORDER_EXCEEDS_LIMIT, format!("OrderQty {} exceeds the maximum of {}", order.order_qty, max_order_qty)));
+        }
+
+        if let (Some(band), Some(price), Some(last_px)) = (config.price_band_percent, order.price, last_px) {
+            if (price - last_px).abs() > last_px * band / 100.0 {
+                return Err((PRICE_EXCEEDS_BAND, format!("Price {} is outside the {}% band around the last trade at {}", price, band, last_px)));
+            }
+        }
+
+        let notional = valuation_price(order, last_px).map(|price| price * order.order_qty);
+        let currency = order.currency.as_ref().or(config.default_currency.as_ref());
+        if let Some((currency, max_notional)) = currency.and_then(|currency| config.max_notional.get(currency).map(|max| (currency, max))) {
+            match notional {
+                Some(notional) if notional > *max_notional => {
+                    return Err((ORDER_EXCEEDS_LIMIT, format!("Notional {} {} exceeds the maximum of {} {}", notional, currency, max_notional, currency)));
+                }
+                Some(_) => {}
+                None => return Err((ORDER_EXCEEDS_LIMIT, format!("No price to value the order against the {} notional limit", currency))),
+            }
+        }
+
+        let account_name = order.account.as_deref().unwrap_or("without an Account");
+        if let Some(max_open_orders) = config.max_open_orders_per_account.filter(|max| account.open_orders >= *max) {
+            return Err((ORDER_EXCEEDS_LIMIT, format!("Account {} already has the maximum of {} open orders", account_name, max_open_orders)));
+        }
+
+        if let Some(max_gross_exposure) = config.max_gross_exposure_per_account {
+            let gross_exposure = account.gross_exposure + notional.unwrap_or(0.0);
+            if gross_exposure > max_gross_exposure {
+                return Err((ORDER_EXCEEDS_LIMIT, format!("Gross exposure of account {} would be {}, above the maximum of {}", account_name, gross_exposure, max_gross_exposure)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Price an order's quantity is valued at: its limit price, else its stop price, else the last
+/// traded price.
+pub fn valuation_price(order: &Order, last_px: Option<f64>) -> Option<f64> {
+    order.price.or(order.stop_px).or(last_px)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message::FixMessage;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use chrono::Utc;
+
+    fn order(order_qty: f64, price: Option<f64>, extra_fields: &str) -> Order {
+        let price_fields = price.map_or("40=1|".to_string(), |price| format!("40=2|44={}|", price));
+        let message = format!("35=D|11=C1|1=ACC1|54=1|60=20230101-12:00:00|38={}|{}55=XYZ|{}", order_qty, price_fields, extra_fields);
+        match FixMessageParser::parse_message(&message) {
+            FixMessage::NewOrder(new_order) => Order::new("O1".to_string(), &new_order, Utc::now()),
+            other => panic!("Expected a NewOrder, got {:?}", other),
+        }
+    }
+
+    fn checks(config: RiskConfig) -> RiskChecks {
+        RiskChecks::new(&config)
+    }
+
+    #[test]
+    fn test_order_limits() {
+        let config = RiskConfig {
+            max_order_qty: Some(1000.0),
+            max_notional: [("GBP".to_string(), 50000.0)].into_iter().collect(),
+            default_currency: Some("GBP".to_string()),
+            price_band_percent: Some(10.0),
+            restricted_symbols: vec!["ABC".to_string()],
+            ..RiskConfig::default()
+        };
+        let risk = checks(config);
+        let none = AccountExposure::default();
+
+        assert!(risk.check(&order(500.0, Some(100.0), ""), Some(100.0), none).is_ok());
+        assert_eq!(risk.check(&order(1500.0, Some(10.0), ""), None, none).unwrap_err().0, ORDER_EXCEEDS_LIMIT);
+        assert_eq!(
+            risk.check(&order(600.0, Some(100.0), ""), None, none).unwrap_err(),
+            (ORDER_EXCEEDS_LIMIT, "Notional 60000 GBP exceeds the maximum of 50000 GBP".to_string())
+        );
+        assert!(risk.check(&order(600.0, Some(100.0), "15=USD|"), None, none).is_ok(), "no USD limit is configured");
+        assert_eq!(
+            risk.check(&order(100.0, Some(111.0), ""), Some(100.0), none).unwrap_err(),
+            (PRICE_EXCEEDS_BAND, "Price 111 is outside the 10% band around the last trade at 100".to_string())
+        );
+        assert_eq!(risk.check(&order(100.0, None, ""), None, none).unwrap_err().0, ORDER_EXCEEDS_LIMIT, "a market order needs a price to value it");
+
+        let mut restricted = order(1.0, Some(1.0), "");
+        restricted.symbol = "ABC".to_string();
+        assert_eq!(risk.check(&restricted, None, none).unwrap_err(), (OTHER, "Symbol ABC is restricted".to_string()));
+    }
+
+    #[test]
+    fn test_account_limits() {
+        let risk = checks(RiskConfig {
+            max_open_orders_per_account: Some(2),
+            max_gross_exposure_per_account: Some(10000.0),
+            ..RiskConfig::default()
+        });
+
+        let busy = AccountExposure { open_orders: 2, gross_exposure: 0.0 };
+        assert_eq!(
+            risk.check(&order(1.0, Some(1.0), ""), None, busy).unwrap_err().1,
+            "Account ACC1 already has the maximum of 2 open orders"
+        );
+
+        let exposed = AccountExposure { open_orders: 1, gross_exposure: 9000.0 };
+        assert!(risk.check(&order(10.0, Some(100.0), ""), None, exposed).is_ok());
+        assert_eq!(
+            risk.check(&order(20.0, Some(100.0), ""), None, exposed).unwrap_err().1,
+            "Gross exposure of account ACC1 would be 11000, above the maximum of 10000"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::oms::order_book::OrderBook;
+use crate::oms::order_store::OrderStore;
+use crate::oms::positions::Positions;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+
+/// How many snapshots are kept, so that a corrupt latest one still leaves one to fall back to.
+const SNAPSHOTS_KEPT: usize = 3;
+
+/// Everything the OMS needs to carry on from a point in its journal.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OmsState {
+    pub orders: OrderStore,
+    pub books: HashMap<String, OrderBook>,
+    pub positions: Positions,
+    pub auction_orders: Vec<String>,
+    pub stop_orders: Vec<String>,
+    pub last_timer: Option<DateTime<Utc>>,
+    pub next_order_id: u64,
+    pub next_exec_id: u64,
+}
+
+/// Keeps snapshots of the OMS in a directory, one file per snapshot named after the journal
+/// offset it covers. Each file starts with a line `<offset> <crc32> <length>` describing the
+/// JSON state that follows, so a corrupt or half-written snapshot is never loaded.
+pub struct SnapshotStore {
+    directory: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        SnapshotStore { directory: directory.as_ref().to_path_buf() }
+    }
+
+    /// Writes `state` as covering the journal up to and including `journal_offset`, then drops
+    /// all but the latest few snapshots.
+    pub fn save(&self, journal_offset: u64, state: &OmsState) -> io::Result<()> {
+        let body = serde_json::to_string(state)?;
+        let contents = format!("{} {:08x} {}\n{}", journal_offset, crc32fast::hash(body.as_bytes()), body.len(), body);
+
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("{}{:020}", SNAPSHOT_PREFIX, journal_offset));
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(temp_path, path)?;
+
+        for (_, stale) in self.snapshots()?.into_iter().skip(SNAPSHOTS_KEPT) {
+            fs::remove_file(stale)?;
+        }
+        Ok(())
+    }
+
+    /// Loads the latest valid snapshot covering no more than `max_offset` entries of the
+    /// journal, returning it with the offset it covers. Invalid snapshots are skipped.
+    pub fn load_latest(&self, max_offset: u64) -> io::Result<Option<(u64, OmsState)>> {
+        for (offset, path) in self.snapshots()?.into_iter().filter(|(offset, _)| *offset <= max_offset) {
+            match load(&path) {
+                Ok((journal_offset, state)) if journal_offset == offset => return Ok(Some((offset, state))),
+                Ok(_) => eprintln!("Skipping snapshot {}: its offset does not match its name", path.display()),
+                Err(e) => eprintln!("Skipping snapshot {}: {}", path.display(), e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Snapshot files with their journal offsets, latest first.
+    fn snapshots(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let offset = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(SNAPSHOT_PREFIX))
+                .and_then(|offset| offset.parse::<u64>().ok());
+            if let Some(offset) = offset {
+                snapshots.push((offset, path));
+            }
+        }
+        snapshots.sort_by_key(|(offset, _)| Reverse(*offset));
+        Ok(snapshots)
+    }
+}
+
+fn load(path: &Path) -> Result<(u64, OmsState), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let (header, body) = contents.split_once('\n').ok_or("no header")?;
+    let fields: Vec<&str> = header.split(' ').collect();
+    let (offset, checksum, length) = match fields[..] {
+        [offset, checksum, length] => (
+            offset.parse::<u64>().map_err(|_| "invalid offset")?,
+            u32::from_str_radix(checksum, 16).map_err(|_| "invalid checksum")?,
+            length.parse::<usize>().map_err(|_| "invalid length")?,
+        ),
+        _ => return Err("invalid header".to_string()),
+    };
+    if body.len() != length {
+        return Err(format!("expected {} bytes of state, found {}", length, body.len()));
+    }
+    if crc32fast::hash(body.as_bytes()) != checksum {
+        return Err("checksum mismatch".to_string());
+    }
+    let state = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    Ok((offset, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(next_order_id: u64) -> OmsState {
+        OmsState { next_order_id, ..OmsState::default() }
+    }
+
+    #[test]
+    fn test_latest_valid_snapshot_is_loaded() {
+        let directory = std::env::temp_dir().join(format!("fix_snapshots_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let store = SnapshotStore::new(&directory);
+
+        for offset in [10, 20, 30, 40] {
+            store.save(offset, &state(offset)).unwrap();
+        }
+        assert_eq!(store.snapshots().unwrap().len(), SNAPSHOTS_KEPT);
+        assert_eq!(store.load_latest(u64::MAX).unwrap().map(|(offset, state)| (offset, state.next_order_id)), Some((40, 40)));
+        assert_eq!(store.load_latest(35).unwrap().unwrap().0, 30, "snapshots past the journal's end are ignored");
+
+        // Flip a byte of the latest snapshot's state and cut the one before it short
+        let latest = directory.join(format!("{}{:020}", SNAPSHOT_PREFIX, 40));
+        let contents = fs::read_to_string(&latest).unwrap();
+        fs::write(&latest, contents.replacen("\"next_order_id\":40", "\"next_order_id\":41", 1)).unwrap();
+        let previous = directory.join(format!("{}{:020}", SNAPSHOT_PREFIX, 30));
+        let contents = fs::read_to_string(&previous).unwrap();
+        fs::write(&previous, &contents[..contents.len() - 5]).unwrap();
+
+        assert_eq!(store.load_latest(u64::MAX).unwrap().unwrap().0, 20);
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
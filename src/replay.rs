@@ -0,0 +1,153 @@
+use fixparser::config::server_config::{ServerConfig, DEFAULT_CONFIG_PATH};
+use fixparser::oms::audit_trail::{audit_trail, to_csv, to_jsonl};
+use fixparser::oms::instruments::InstrumentMaster;
+use fixparser::oms::journal::{parse_time, Journal, JournalEvent};
+use fixparser::oms::order::{Order, BUY, SELL};
+use fixparser::oms::order_management_system::OrderManagementSystem;
+use fixparser::oms::risk::RiskChecks;
+use fixparser::session::session_clock::UTC_TIMESTAMP_FORMAT;
+use fixparser::session::session_schedule::SessionSchedule;
+
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: replay <journal> [--until YYYYMMDD-HH:MM:SS] [--config <path>] [--verbose] [--audit csv|jsonl]";
+
+struct Args {
+    journal_path: String,
+    until: Option<DateTime<Utc>>,
+    config_path: String,
+    verbose: bool,
+    /// Print the audit trail in this format instead of the OMS's state.
+    audit: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut journal_path = None;
+    let mut until = None;
+    let mut config_path = DEFAULT_CONFIG_PATH.to_string();
+    let mut verbose = false;
+    let mut audit = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--until" => until = Some(parse_time(&args.next().ok_or(USAGE)?)?),
+            "--config" => config_path = args.next().ok_or(USAGE)?,
+            "--verbose" => verbose = true,
+            "--audit" => match args.next().as_deref() {
+                Some(format @ ("csv" | "jsonl")) => audit = Some(format.to_string()),
+                _ => return Err(USAGE.to_string()),
+            },
+            _ if journal_path.is_none() && !arg.starts_with("--") => journal_path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(Args { journal_path: journal_path.ok_or(USAGE)?, until, config_path, verbose, audit })
+}
+
+/// Rebuilds the OMS from a journal up to a point in time, using the ID format, schedule,
+/// instruments, self-trade prevention and risk limits of the server's config, and prints its orders, books and positions as they stood then.
+/// With `--audit`, prints the order audit trail up to that point instead.
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let config = ServerConfig::load(&args.config_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let entries = Journal::read(&args.journal_path).unwrap_or_else(|e| {
+        eprintln!("Failed to read journal {}: {}", args.journal_path, e);
+        std::process::exit(1);
+    });
+
+    if let Some(format) = &args.audit {
+        let entries = entries.into_iter().take_while(|entry| args.until.is_none_or(|until| entry.time <= until));
+        let events = audit_trail(entries);
+        print!("{}", if format == "csv" { to_csv(&events) } else { to_jsonl(&events) });
+        return;
+    }
+
+    let mut oms = OrderManagementSystem::new()
+        .with_id_format(&config.oms.ids.prefix, config.oms.ids.width)
+        .with_risk_checks(RiskChecks::new(&config.risk));
+    if let Some(schedule) = &config.oms.schedule {
+        oms = oms.with_schedule(SessionSchedule::new(schedule).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }));
+    }
+    if let Some(path) = &config.oms.instruments_path {
+        oms = oms.with_instruments(Arc::new(InstrumentMaster::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })));
+    }
+    if let Some(prevention) = config.oms.self_trade_prevention {
+        oms = oms.with_self_trade_prevention(prevention);
+    }
+
+    let total = entries.len();
+    let entries: Vec<_> = entries.into_iter().take_while(|entry| args.until.is_none_or(|until| entry.time <= until)).collect();
+    if args.verbose {
+        for entry in &entries {
+            let time = entry.time.format(UTC_TIMESTAMP_FORMAT);
+            match &entry.event {
+                JournalEvent::Inbound(message) => println!("{} {} IN  {}", entry.offset, time, message),
+                JournalEvent::Outbound(message) => println!("{} {} OUT {}", entry.offset, time, message),
+                JournalEvent::Timer => println!("{} {} TIMER", entry.offset, time),
+                JournalEvent::Ids(next) => println!("{} {} IDS {} {}", entry.offset, time, next.next_order_id, next.next_exec_id),
+            }
+        }
+    }
+    let last = entries.last().map(|entry| (entry.offset, entry.time));
+    oms.replay(entries.clone());
+
+    match last {
+        Some((offset, time)) => println!("Replayed {} of {} journal entries, up to offset {} at {}", entries.len(), total, offset, time.format(UTC_TIMESTAMP_FORMAT)),
+        None => println!("No journal entries to replay out of {}", total),
+    }
+
+    let mut orders: Vec<&Order> = oms.orders().orders().collect();
+    orders.sort_by_key(|order| (order.events.first().map(|event| event.time), order.order_id.clone()));
+    println!("\nOrders:");
+    for order in &orders {
+        println!(
+            "  {} {} {} {} {} @ {}: {}, {} filled, {} leaves",
+            order.order_id,
+            order.cl_ord_id,
+            if order.side == BUY { "Buy" } else { "Sell" },
+            order.order_qty,
+            order.symbol,
+            order.price.map_or("market".to_string(), |price| price.to_string()),
+            order.ord_status,
+            order.cum_qty,
+            order.leaves_qty,
+        );
+    }
+
+    let symbols: BTreeSet<&str> = orders.iter().map(|order| order.symbol.as_str()).collect();
+    for (symbol, book) in symbols.into_iter().filter_map(|symbol| oms.book(symbol).map(|book| (symbol, book))) {
+        println!("\nBook {} (last {}):", symbol, book.last_price().map_or("none".to_string(), |price| price.to_string()));
+        for (name, side) in [("Bid", BUY), ("Ask", SELL)] {
+            for level in book.depth(side) {
+                println!("  {} {} @ {} ({} orders)", name, level.qty, level.price, level.orders);
+            }
+        }
+    }
+
+    println!("\nPositions:");
+    for position in oms.positions().lock().unwrap().positions(None) {
+        println!(
+            "  {} {}: net {} at {}, realised {}, unrealised {}",
+            position.account.as_deref().unwrap_or("-"),
+            position.symbol,
+            position.net_qty,
+            position.avg_cost,
+            position.realised_pnl,
+            position.unrealised_pnl,
+        );
+    }
+}
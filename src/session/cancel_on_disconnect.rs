@@ -0,0 +1,223 @@
+use crate::fix::fix_message::FixMessage;
+use crate::fix::messages::execution_report::ExecutionReport;
+use crate::fix::messages::order_cancel_request::OrderCancelRequest;
+use crate::oms::oms_dispatcher::OmsDispatcher;
+use crate::oms::oms_request::OmsRequest;
+use crate::oms::order::OrdStatus;
+use crate::oms::order_management_system::DUPLICATE_ORDER;
+use crate::session::session_clock::UTC_TIMESTAMP_FORMAT;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Side and Symbol of a working order, which a cancel has to repeat.
+#[derive(Debug, Clone)]
+struct WorkingOrder {
+    side: char,
+    symbol: String,
+}
+
+/// One connection and the working orders entered through it, by the CompID they were entered
+/// under (if any) and ClOrdID. The generation moves on with every connect and disconnect, so a
+/// pending cancel can tell whether anything happened while it waited.
+#[derive(Debug, Default)]
+struct Connection {
+    open: bool,
+    generation: u64,
+    orders: HashMap<(Option<String>, String), WorkingOrder>,
+}
+
+/// Tracks the working orders entered through each FIX session or WebSocket and cancels them
+/// once their connection has been gone for a grace period. A FIX session is one connection
+/// however often it reconnects, so it keeps its orders if it is back within the grace period;
+/// each WebSocket is a connection of its own. Orders entered through other connections, even
+/// by the same counterparty, are left alone.
+#[derive(Debug, Clone, Default)]
+pub struct CancelOnDisconnect {
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+    sockets: Arc<AtomicU64>,
+}
+
+impl CancelOnDisconnect {
+    /// Connection ID for a new WebSocket, which no other socket shares.
+    pub fn next_socket(&self) -> String {
+        format!("ws-{}", self.sockets.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    pub fn connected(&self, connection: &str) {
+        let mut connections = self.connections.lock().unwrap();
+        let connection = connections.entry(connection.to_string()).or_default();
+        connection.open = true;
+        connection.generation += 1;
+    }
+
+    /// Records a message going to the OMS through `connection` on behalf of `session`; a new
+    /// order is tracked until a report shows it is done.
+    pub fn entered(&self, connection: &str, session: Option<&str>, message: &FixMessage) {
+        let FixMessage::NewOrder(new_order) = message else { return };
+        let mut connections = self.connections.lock().unwrap();
+        let connection = connections.entry(connection.to_string()).or_default();
+        let order = WorkingOrder { side: new_order.side, symbol: new_order.symbol.clone() };
+        connection.orders.insert((session.map(str::to_string), new_order.cl_ord_id.clone()), order);
+    }
+
+    /// Stops tracking the order of `session` a report sent down `connection` shows is done. The
+    /// rejection of a duplicate ClOrdID is about another order, which is still working.
+    pub fn reported(&self, connection: &str, session: Option<&str>, message: &FixMessage) {
+        let FixMessage::ExecutionReport(report) = message else { return };
+        if !is_done(report) || report.ord_rej_reason == Some(DUPLICATE_ORDER) {
+            return;
+        }
+        if let Some(connection) = self.connections.lock().unwrap().get_mut(connection) {
+            let done = |cl_ord_id: &str| cl_ord_id == report.cl_ord_id || report.orig_cl_ord_id.as_deref() == Some(cl_ord_id);
+            connection.orders.retain(|(entered_by, cl_ord_id), _| entered_by.as_deref() != session || !done(cl_ord_id));
+        }
+    }
+
+    /// Records that `connection` closed. With a `grace` period, the orders entered through it
+    /// are canceled if it has not reconnected by the end of it; without one they are left
+    /// working and no longer tracked. Returns the task that waits out the grace period, which
+    /// is done once any cancels are queued.
+    pub fn disconnected(&self, connection: &str, grace: Option<Duration>, sender: &OmsDispatcher) -> Option<JoinHandle<()>> {
+        let mut connections = self.connections.lock().unwrap();
+        let Some(grace) = grace else {
+            connections.remove(connection);
+            return None;
+        };
+        let closed = connections.entry(connection.to_string()).or_default();
+        closed.open = false;
+        closed.generation += 1;
+        let generation = closed.generation;
+        drop(connections);
+
+        let tracker = self.clone();
+        let connection = connection.to_string();
+        let sender = sender.clone();
+        let pending = tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            let Some(orders) = tracker.take_orders_if_gone(&connection, generation) else { return };
+            println!("Canceling {} orders entered through {} after {:?} disconnected", orders.len(), connection, grace);
+            for ((session, cl_ord_id), order) in orders {
+                let mut request = OmsRequest::without_reply(FixMessage::OrderCancelRequest(cancel(&cl_ord_id, &order)));
+                request.session = session;
+                if sender.send(request).await.is_err() {
+                    eprintln!("Failed to send cancel-on-disconnect of {} to the OMS", cl_ord_id);
+                }
+            }
+        });
+        Some(pending)
+    }
+
+    /// The orders of `connection`, which it stops tracking, if the connection has stayed closed
+    /// since its disconnect at `generation`.
+    fn take_orders_if_gone(&self, connection: &str, generation: u64) -> Option<HashMap<(Option<String>, String), WorkingOrder>> {
+        let mut connections = self.connections.lock().unwrap();
+        let gone = connections.get(connection).is_some_and(|connection| !connection.open && connection.generation == generation);
+        if !gone {
+            return None;
+        }
+        connections.remove(connection).map(|connection| connection.orders)
+    }
+}
+
+fn is_done(report: &ExecutionReport) -> bool {
+    [OrdStatus::Filled, OrdStatus::Canceled, OrdStatus::Replaced, OrdStatus::Rejected, OrdStatus::Expired]
+        .iter()
+        .any(|status| status.as_char() == report.ord_status)
+}
+
+/// A cancel of the order `cl_ord_id`, sent on behalf of the counterparty that entered it.
+fn cancel(cl_ord_id: &str, order: &WorkingOrder) -> OrderCancelRequest {
+    let now = Utc::now();
+    OrderCancelRequest {
+        orig_cl_ord_id: cl_ord_id.to_string(),
+        cl_ord_id: format!("COD-{}-{}", now.timestamp_millis(), cl_ord_id),
+        side: order.side,
+        symbol: order.symbol.clone(),
+        transact_time: now.format(UTC_TIMESTAMP_FORMAT).to_string(),
+        order_qty: None,
+        account: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fix::fix_message_parser::FixMessageParser;
+    use crate::oms::oms_dispatcher::OmsShards;
+    use crate::oms::oms_request::ReplyTo;
+    use crate::oms::order_management_system::OrderManagementSystem;
+    use tokio::sync::oneshot;
+
+    /// Sends `message` through `connection` as the server would and returns the OrdStatus (39)
+    /// of the last report back.
+    async fn ord_status(tracker: &CancelOnDisconnect, sender: &OmsDispatcher, connection: &str, session: Option<&str>, message: &str) -> char {
+        let (reply_sender, reply) = oneshot::channel();
+        let message = FixMessageParser::parse_message(message);
+        tracker.entered(connection, session, &message);
+        let mut request = OmsRequest::new(message, ReplyTo::Request(reply_sender));
+        request.session = session.map(str::to_string);
+        sender.send(request).await.unwrap();
+        let replies = reply.await.unwrap();
+        for reply in &replies {
+            tracker.reported(connection, session, reply);
+        }
+        match replies.last() {
+            Some(FixMessage::ExecutionReport(report)) => report.ord_status,
+            other => panic!("Expected an ExecutionReport, got {:?}", other),
+        }
+    }
+
+    fn order(cl_ord_id: &str) -> String {
+        format!("35=D|11={}|54=1|60=20230101-12:00:00|38=10|40=2|44=50|55=XYZ|", cl_ord_id)
+    }
+
+    fn status(cl_ord_id: &str) -> String {
+        format!("35=H|11={}|54=1|55=XYZ|60=20230101-12:00:00|", cl_ord_id)
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_canceled_unless_their_connection_is_back_in_time() {
+        tokio::time::pause();
+        let (shards, sender) = OmsShards::start(vec![OrderManagementSystem::new()], 8).unwrap();
+        let tracker = CancelOnDisconnect::default();
+        let grace = Some(Duration::from_secs(30));
+        let (session, first_socket, second_socket) = ("FIX.4.2:SERVER->CLIENT1", tracker.next_socket(), tracker.next_socket());
+        for connection in [session, &first_socket, &second_socket] {
+            tracker.connected(connection);
+        }
+        let new = OrdStatus::New.as_char();
+        assert_eq!(ord_status(&tracker, &sender, session, Some("CLIENT1"), &order("C1")).await, new);
+        assert_eq!(ord_status(&tracker, &sender, &first_socket, Some("CLIENT2"), &order("C2")).await, new);
+        assert_eq!(ord_status(&tracker, &sender, &first_socket, None, &order("C3")).await, new);
+        assert_eq!(ord_status(&tracker, &sender, &second_socket, Some("CLIENT2"), &order("C4")).await, new);
+        // A duplicate leaves the order it duplicates tracked
+        assert_eq!(ord_status(&tracker, &sender, &first_socket, Some("CLIENT2"), &order("C2")).await, OrdStatus::Rejected.as_char());
+        // So does the end of another counterparty's order under the same ClOrdID on that socket
+        let canceled = OrdStatus::Canceled.as_char();
+        assert_eq!(ord_status(&tracker, &sender, &first_socket, Some("CLIENT3"), &order("C2")).await, new);
+        let cancel = "35=F|11=X2|41=C2|54=1|55=XYZ|60=20230101-12:00:00|";
+        assert_eq!(ord_status(&tracker, &sender, &first_socket, Some("CLIENT3"), cancel).await, canceled);
+
+        // The session is back before the grace period ends; the first socket never is, while
+        // CLIENT2's second socket stays open
+        let reconnected = tracker.disconnected(session, grace, &sender).unwrap();
+        let gone = tracker.disconnected(&first_socket, grace, &sender).unwrap();
+        tokio::time::advance(Duration::from_secs(10)).await;
+        tracker.connected(session);
+        tokio::time::advance(Duration::from_secs(21)).await;
+        reconnected.await.unwrap();
+        gone.await.unwrap();
+
+        assert_eq!(ord_status(&tracker, &sender, session, Some("CLIENT1"), &status("C1")).await, new);
+        assert_eq!(ord_status(&tracker, &sender, &second_socket, Some("CLIENT2"), &status("C2")).await, canceled);
+        assert_eq!(ord_status(&tracker, &sender, &second_socket, None, &status("C3")).await, canceled, "orders without a CompID are canceled too");
+        assert_eq!(ord_status(&tracker, &sender, &second_socket, Some("CLIENT2"), &status("C4")).await, new);
+
+        drop(sender);
+        shards.stop();
+    }
+}
@@ -0,0 +1,1300 @@
+use crate::auth::authenticator::{Authenticator, Credentials};
+use crate::auth::entitlements::Entitlements;
+use crate::config::server_config::ResetPolicy;
+use crate::fix::data_dictionary::DataDictionary;
+use crate::fix::fix_message::{is_admin_msg_type, FixMessage};
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::fix::fix_message_writer::FixMessageWriter;
+use crate::fix::messages::business_message_reject::BusinessMessageReject;
+use crate::fix::messages::heartbeat::Heartbeat;
+use crate::fix::messages::logon::Logon;
+use crate::fix::messages::logout::Logout;
+use crate::fix::messages::reject::Reject;
+use crate::fix::messages::resend_request::ResendRequest;
+use crate::fix::messages::sequence_reset::SequenceReset;
+use crate::fix::messages::standard_header::StandardHeader;
+use crate::fix::messages::test_request::TestRequest;
+use crate::session::message_store::MessageStore;
+use crate::session::session_clock::Clock;
+use crate::session::session_schedule::SessionSchedule;
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Share of HeartBtInt granted on top of the interval before an idle counterparty is probed.
+const DEFAULT_HEARTBEAT_TOLERANCE_PERCENT: u64 = 20;
+/// How long to wait for the counterparty to confirm a Logout we initiated.
+const LOGOUT_TIMEOUT_SECS: i64 = 10;
+
+// SessionRejectReason (373) values
+pub const REQUIRED_TAG_MISSING: u32 = 1;
+pub const VALUE_IS_INCORRECT: u32 = 5;
+pub const COMP_ID_PROBLEM: u32 = 9;
+pub const SENDING_TIME_ACCURACY_PROBLEM: u32 = 10;
+pub const INVALID_MSG_TYPE: u32 = 11;
+
+// BusinessRejectReason (380) values
+pub const NOT_AUTHORIZED: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId {
+    pub begin_string: String,
+    pub sender_comp_id: String,
+    pub target_comp_id: String,
+}
+
+impl SessionId {
+    pub fn new(begin_string: &str, sender_comp_id: &str, target_comp_id: &str) -> Self {
+        SessionId {
+            begin_string: begin_string.to_string(),
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}->{}", self.begin_string, self.sender_comp_id, self.target_comp_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    AwaitingLogon,
+    LogonSent,
+    LoggedOn,
+    LogoutSent,
+    Disconnected,
+}
+
+/// Work the transport has to carry out on behalf of the session.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Actions are consumed straight away, never stored in bulk
+pub enum SessionAction {
+    Send(String),
+    Deliver(FixMessage),
+    Disconnect,
+}
+
+/// Outstanding TestRequest awaiting any inbound traffic from the counterparty.
+struct PendingTestRequest {
+    test_req_id: String,
+    sent_at: DateTime<Utc>,
+}
+
+/// Transport-independent FIX session: validates inbound sequence numbers, recovers gaps with
+/// ResendRequest, and answers the counterparty's ResendRequests from the `MessageStore`.
+/// Liveness is checked whenever the transport calls `on_timer`.
+pub struct FixSession {
+    session_id: SessionId,
+    heart_bt_int: u64,
+    heartbeat_tolerance: Option<Duration>,
+    reset_policy: ResetPolicy,
+    data_dictionary: Option<Arc<DataDictionary>>,
+    schedule: Option<SessionSchedule>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    entitlements: Option<Arc<Entitlements>>,
+    username: Option<String>,
+    password: Option<String>,
+    store: Box<dyn MessageStore>,
+    clock: Arc<dyn Clock>,
+    state: SessionState,
+    queued_messages: BTreeMap<u64, String>,
+    resend_pending_until: Option<u64>,
+    last_sent_at: DateTime<Utc>,
+    last_received_at: DateTime<Utc>,
+    pending_test_request: Option<PendingTestRequest>,
+    test_request_counter: u64,
+    logged_on_period_start: Option<DateTime<Utc>>,
+    logout_sent_at: Option<DateTime<Utc>>,
+}
+
+impl FixSession {
+    pub fn new(session_id: SessionId, heart_bt_int: u64, store: Box<dyn MessageStore>, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        FixSession {
+            session_id,
+            heart_bt_int,
+            heartbeat_tolerance: None,
+            reset_policy: ResetPolicy::Never,
+            data_dictionary: None,
+            schedule: None,
+            authenticator: None,
+            entitlements: None,
+            username: None,
+            password: None,
+            store,
+            clock,
+            state: SessionState::AwaitingLogon,
+            queued_messages: BTreeMap::new(),
+            resend_pending_until: None,
+            last_sent_at: now,
+            last_received_at: now,
+            pending_test_request: None,
+            test_request_counter: 0,
+            logged_on_period_start: None,
+            logout_sent_at: None,
+        }
+    }
+
+    /// Overrides the extra inbound silence allowed after HeartBtInt before a TestRequest is sent.
+    pub fn with_heartbeat_tolerance(mut self, tolerance: Duration) -> Self {
+        self.heartbeat_tolerance = Some(tolerance);
+        self
+    }
+
+    pub fn with_reset_policy(mut self, reset_policy: ResetPolicy) -> Self {
+        self.reset_policy = reset_policy;
+        self
+    }
+
+    /// Restricts logons to the schedule's trading window and logs out when it closes.
+    pub fn with_schedule(mut self, schedule: SessionSchedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// Rejects inbound messages that lack a tag the dictionary marks as required.
+    pub fn with_data_dictionary(mut self, data_dictionary: Arc<DataDictionary>) -> Self {
+        self.data_dictionary = Some(data_dictionary);
+        self
+    }
+
+    /// Refuses Logons from a counterparty the authenticator does not accept.
+    pub fn with_authenticator(mut self, authenticator: Arc<dyn Authenticator>) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Answers application messages the counterparty is not entitled to send with a
+    /// BusinessMessageReject instead of delivering them.
+    pub fn with_entitlements(mut self, entitlements: Arc<Entitlements>) -> Self {
+        self.entitlements = Some(entitlements);
+        self
+    }
+
+    /// Username and Password sent on the Logon when initiating.
+    pub fn with_credentials(mut self, username: String, password: String) -> Self {
+        self.username = Some(username);
+        self.password = Some(password);
+        self
+    }
+
+    pub fn session_id(&self) -> &SessionId {
+        &self.session_id
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    pub fn is_logged_on(&self) -> bool {
+        self.state == SessionState::LoggedOn
+    }
+
+    pub fn is_session_time(&self) -> bool {
+        self.schedule.as_ref().is_none_or(|schedule| schedule.is_session_time(self.clock.now()))
+    }
+
+    /// Starts the session as the initiator by sending a Logon.
+    pub fn logon(&mut self) -> Vec<SessionAction> {
+        self.reset_if_new_trading_day();
+        let mut actions = Vec::new();
+        let mut reset_seq_num_flag = None;
+        if self.reset_policy == ResetPolicy::OnLogon {
+            self.reset();
+            reset_seq_num_flag = Some('Y');
+        }
+        let logon = Logon {
+            encrypt_method: 0,
+            heart_bt_int: self.heart_bt_int,
+            reset_seq_num_flag,
+            username: self.username.clone(),
+            password: self.password.clone(),
+        };
+        self.send(logon.to_writer(), &mut actions);
+        self.state = SessionState::LogonSent;
+        actions
+    }
+
+    /// Sends an application message, storing it so it can be resent on request. While the
+    /// session is not logged on the message is only stored and will be recovered by resend.
+    pub fn send_app_message(&mut self, writer: FixMessageWriter) -> Vec<SessionAction> {
+        let mut actions = Vec::new();
+        self.send(writer, &mut actions);
+        if !self.is_logged_on() {
+            actions.clear();
+        }
+        actions
+    }
+
+    /// Resets transient state once the transport has gone away; sequence numbers are kept.
+    pub fn on_disconnect(&mut self) {
+        self.state = SessionState::AwaitingLogon;
+        self.queued_messages.clear();
+        self.resend_pending_until = None;
+        self.pending_test_request = None;
+        if self.reset_policy == ResetPolicy::OnDisconnect {
+            self.reset();
+        }
+    }
+
+    /// Starts a graceful logout; the connection is dropped once the counterparty confirms it
+    /// or after a timeout.
+    pub fn logout(&mut self, text: &str) -> Vec<SessionAction> {
+        let mut actions = Vec::new();
+        if self.is_logged_on() {
+            println!("[{}] Logging out: {}", self.session_id, text);
+            self.send(Logout { text: Some(text.to_string()) }.to_writer(), &mut actions);
+            self.state = SessionState::LogoutSent;
+            self.logout_sent_at = Some(self.clock.now());
+        }
+        actions
+    }
+
+    /// Resets sequence numbers back to 1 and discards stored messages.
+    pub fn reset(&mut self) {
+        println!("[{}] Resetting sequence numbers", self.session_id);
+        let now = self.clock.now();
+        self.persist(|store| store.reset());
+        self.persist(|store| store.set_creation_time(now));
+        self.queued_messages.clear();
+        self.resend_pending_until = None;
+    }
+
+    /// Applies the daily reset policy while no counterparty is connected, so the store starts
+    /// each trading day afresh even if nobody logs on.
+    pub fn check_schedule(&mut self) {
+        if !matches!(self.state, SessionState::LoggedOn | SessionState::LogoutSent) {
+            self.reset_if_new_trading_day();
+        }
+    }
+
+    fn reset_if_new_trading_day(&mut self) {
+        if self.reset_policy != ResetPolicy::Daily {
+            return;
+        }
+        let now = self.clock.now();
+        let period_start = match self.schedule.as_ref().and_then(|schedule| schedule.current_session_start(now)) {
+            Some(period_start) => period_start,
+            None => return,
+        };
+        match self.store.creation_time() {
+            Some(creation_time) if creation_time >= period_start => {}
+            Some(_) => self.reset(),
+            None => self.persist(|store| store.set_creation_time(now)),
+        }
+    }
+
+    /// Checks liveness in both directions. Sends a Heartbeat after HeartBtInt of outbound
+    /// silence, a TestRequest after HeartBtInt plus tolerance of inbound silence, and logs out
+    /// if that TestRequest is still unanswered a further HeartBtInt later.
+    pub fn on_timer(&mut self) -> Vec<SessionAction> {
+        let mut actions = Vec::new();
+        let now = self.clock.now();
+
+        if self.state == SessionState::LogoutSent {
+            if self.logout_sent_at.is_some_and(|sent_at| now - sent_at >= Duration::seconds(LOGOUT_TIMEOUT_SECS)) {
+                eprintln!("[{}] Logout not confirmed, disconnecting", self.session_id);
+                self.state = SessionState::Disconnected;
+                actions.push(SessionAction::Disconnect);
+            }
+            return actions;
+        }
+        if !self.is_logged_on() {
+            return actions;
+        }
+
+        if let Some(schedule) = &self.schedule {
+            let same_period = schedule.current_session_start(now) == self.logged_on_period_start;
+            if !same_period || !schedule.is_session_time(now) {
+                return self.logout("End of trading session");
+            }
+        }
+
+        if self.heart_bt_int == 0 {
+            return actions;
+        }
+
+        let interval = Duration::seconds(self.heart_bt_int as i64);
+
+        if let Some(pending) = &self.pending_test_request {
+            if now - pending.sent_at >= interval {
+                let text = format!("TestRequest {} not answered", pending.test_req_id);
+                eprintln!("[{}] {}, disconnecting", self.session_id, text);
+                self.terminate(&text, &mut actions);
+                return actions;
+            }
+        } else if now - self.last_received_at >= interval + self.heartbeat_tolerance() {
+            self.test_request_counter += 1;
+            let test_req_id = format!("TEST{}", self.test_request_counter);
+            println!("[{}] No inbound traffic, sending TestRequest {}", self.session_id, test_req_id);
+            self.send(TestRequest { test_req_id: test_req_id.clone() }.to_writer(), &mut actions);
+            self.pending_test_request = Some(PendingTestRequest { test_req_id, sent_at: now });
+        }
+
+        if now - self.last_sent_at >= interval {
+            self.send(Heartbeat { test_req_id: None }.to_writer(), &mut actions);
+        }
+        actions
+    }
+
+    fn heartbeat_tolerance(&self) -> Duration {
+        self.heartbeat_tolerance.unwrap_or_else(|| {
+            let millis = self.heart_bt_int * 1000 * DEFAULT_HEARTBEAT_TOLERANCE_PERCENT / 100;
+            Duration::milliseconds(millis as i64)
+        })
+    }
+
+    pub fn on_message(&mut self, message: &str) -> Vec<SessionAction> {
+        self.last_received_at = self.clock.now();
+        if let Some(pending) = self.pending_test_request.take() {
+            println!("[{}] Counterparty alive, TestRequest {} answered", self.session_id, pending.test_req_id);
+        }
+
+        let mut actions = Vec::new();
+        self.process(message, &mut actions);
+        self.drain_queued_messages(&mut actions);
+        actions
+    }
+
+    fn process(&mut self, raw: &str, actions: &mut Vec<SessionAction>) {
+        // A garbled message is dropped without consuming its sequence number, so the gap it
+        // leaves is recovered through a ResendRequest
+        if let Err(e) = FixMessageParser::check_envelope(raw) {
+            eprintln!("[{}] Dropping garbled message: {}", self.session_id, e);
+            return;
+        }
+        let fields = FixMessageParser::parse_fields(raw);
+        let header = match StandardHeader::new(&fields) {
+            Ok(header) => header,
+            Err(e) => {
+                eprintln!("[{}] Dropping message with invalid header: {}", self.session_id, e);
+                return;
+            }
+        };
+
+        if header.begin_string != self.session_id.begin_string
+            || header.sender_comp_id != self.session_id.target_comp_id
+            || header.target_comp_id != self.session_id.sender_comp_id {
+            self.send_reject(&header, COMP_ID_PROBLEM, None, "CompID problem", actions);
+            self.terminate("Incorrect BeginString or CompID", actions);
+            return;
+        }
+
+        let awaiting_logon = matches!(self.state, SessionState::AwaitingLogon | SessionState::LogonSent);
+        if awaiting_logon && header.msg_type != "A" {
+            eprintln!("[{}] First message was not a Logon, disconnecting", self.session_id);
+            self.state = SessionState::Disconnected;
+            actions.push(SessionAction::Disconnect);
+            return;
+        }
+
+        if self.state == SessionState::AwaitingLogon {
+            if !self.is_session_time() {
+                eprintln!("[{}] Refusing Logon outside of session time", self.session_id);
+                self.terminate("Logon outside of session time", actions);
+                return;
+            }
+            self.reset_if_new_trading_day();
+        }
+
+        let missing_tag = self.data_dictionary.as_ref().and_then(|dictionary| dictionary.missing_required_tag(&fields));
+        let message = FixMessageParser::parse_fields_into_message(fields);
+
+        if let FixMessage::Logon(logon) = &message {
+            if self.state == SessionState::AwaitingLogon {
+                if let Err(e) = self.authenticate(logon) {
+                    eprintln!("[{}] Refusing Logon: {}", self.session_id, e);
+                    self.terminate(&e, actions);
+                    return;
+                }
+            }
+        }
+
+        match &message {
+            FixMessage::Logon(logon) if logon.reset_seq_num_flag == Some('Y') && self.state == SessionState::AwaitingLogon => {
+                self.reset();
+            }
+            FixMessage::Logon(_) if self.reset_policy == ResetPolicy::OnLogon && self.state == SessionState::AwaitingLogon => {
+                self.reset();
+                self.persist(|store| store.set_next_target_seq_num(header.msg_seq_num));
+            }
+            FixMessage::SequenceReset(reset) if !reset.is_gap_fill() => {
+                self.on_sequence_reset(&header, reset, actions);
+                return;
+            }
+            _ => {}
+        }
+
+        let expected = self.store.next_target_seq_num();
+        match header.msg_seq_num.cmp(&expected) {
+            Ordering::Equal => match missing_tag {
+                Some(tag) => {
+                    self.send_reject(&header, REQUIRED_TAG_MISSING, Some(tag), "Required tag missing", actions);
+                    self.persist(|store| store.set_next_target_seq_num(header.msg_seq_num + 1));
+                }
+                None => self.process_in_sequence(&header, message, actions),
+            },
+            Ordering::Greater => self.on_sequence_too_high(&header, raw, message, expected, actions),
+            Ordering::Less => self.on_sequence_too_low(&header, expected, actions),
+        }
+    }
+
+    fn process_in_sequence(&mut self, header: &StandardHeader, message: FixMessage, actions: &mut Vec<SessionAction>) {
+        if header.is_poss_dup() && !self.validate_orig_sending_time(header, actions) {
+            self.persist(|store| store.set_next_target_seq_num(header.msg_seq_num + 1));
+            return;
+        }
+
+        if let FixMessage::SequenceReset(reset) = &message {
+            if reset.new_seq_no > header.msg_seq_num {
+                self.persist(|store| store.set_next_target_seq_num(reset.new_seq_no));
+            } else {
+                self.send_reject(header, VALUE_IS_INCORRECT, Some(36), "NewSeqNo must be greater than MsgSeqNum", actions);
+                self.persist(|store| store.set_next_target_seq_num(header.msg_seq_num + 1));
+            }
+            return;
+        }
+
+        self.persist(|store| store.set_next_target_seq_num(header.msg_seq_num + 1));
+
+        match message {
+            FixMessage::Logon(logon) => self.on_logon(logon, actions),
+            FixMessage::Logout(_) => self.on_logout(actions),
+            FixMessage::Heartbeat(_) => {}
+            FixMessage::TestRequest(test_request) => {
+                let heartbeat = Heartbeat { test_req_id: Some(test_request.test_req_id) };
+                self.send(heartbeat.to_writer(), actions);
+            }
+            FixMessage::ResendRequest(resend_request) => self.on_resend_request(&resend_request, actions),
+            FixMessage::Reject(reject) => {
+                eprintln!("[{}] Counterparty rejected MsgSeqNum {}: {:?}", self.session_id, reject.ref_seq_num, reject.text);
+            }
+            FixMessage::SequenceReset(_) => {}
+            FixMessage::Unknown => {
+                self.send_reject(header, INVALID_MSG_TYPE, None, "Unsupported or malformed message", actions);
+            }
+            application_message => match self.check_entitlements(header, &application_message) {
+                Ok(()) => actions.push(SessionAction::Deliver(application_message)),
+                Err(text) => {
+                    eprintln!("[{}] Rejecting MsgSeqNum {}: {}", self.session_id, header.msg_seq_num, text);
+                    let reject = BusinessMessageReject {
+                        ref_seq_num: Some(header.msg_seq_num),
+                        ref_msg_type: header.msg_type.clone(),
+                        business_reject_ref_id: application_message.cl_ord_id().map(str::to_string),
+                        business_reject_reason: NOT_AUTHORIZED,
+                        text: Some(text),
+                    };
+                    self.send(reject.to_writer(), actions);
+                }
+            },
+        }
+    }
+
+    fn authenticate(&self, logon: &Logon) -> Result<(), String> {
+        match &self.authenticator {
+            Some(authenticator) => authenticator.authenticate(&Credentials {
+                comp_id: self.session_id.target_comp_id.clone(),
+                username: logon.username.clone(),
+                password: logon.password.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn check_entitlements(&self, header: &StandardHeader, message: &FixMessage) -> Result<(), String> {
+        match &self.entitlements {
+            Some(entitlements) => entitlements.check(&header.sender_comp_id, &header.msg_type, message),
+            None => Ok(()),
+        }
+    }
+
+    fn on_sequence_too_high(&mut self, header: &StandardHeader, raw: &str, message: FixMessage, expected: u64, actions: &mut Vec<SessionAction>) {
+        println!("[{}] MsgSeqNum too high, expected {} but received {}", self.session_id, expected, header.msg_seq_num);
+
+        // Logon and ResendRequest are acted on immediately; the counterparty gap-fills over them.
+        match message {
+            FixMessage::Logon(logon) => self.on_logon(logon, actions),
+            FixMessage::ResendRequest(resend_request) => self.on_resend_request(&resend_request, actions),
+            _ => {
+                self.queued_messages.insert(header.msg_seq_num, raw.to_string());
+            }
+        }
+
+        match self.resend_pending_until {
+            Some(until) if until >= expected => {
+                self.resend_pending_until = Some(until.max(header.msg_seq_num));
+            }
+            _ => {
+                self.send_resend_request(expected, actions);
+                self.resend_pending_until = Some(header.msg_seq_num);
+            }
+        }
+    }
+
+    fn on_sequence_too_low(&mut self, header: &StandardHeader, expected: u64, actions: &mut Vec<SessionAction>) {
+        if header.is_poss_dup() {
+            if self.validate_orig_sending_time(header, actions) {
+                println!("[{}] Ignoring possible duplicate MsgSeqNum {}", self.session_id, header.msg_seq_num);
+            }
+            return;
+        }
+
+        let text = format!("MsgSeqNum too low, expecting {} but received {}", expected, header.msg_seq_num);
+        eprintln!("[{}] {}", self.session_id, text);
+        self.terminate(&text, actions);
+    }
+
+    fn validate_orig_sending_time(&mut self, header: &StandardHeader, actions: &mut Vec<SessionAction>) -> bool {
+        match &header.orig_sending_time {
+            None => {
+                self.send_reject(header, REQUIRED_TAG_MISSING, Some(122), "Required tag missing", actions);
+                false
+            }
+            Some(orig_sending_time) if orig_sending_time.as_str() > header.sending_time.as_str() => {
+                self.send_reject(header, SENDING_TIME_ACCURACY_PROBLEM, Some(122), "OrigSendingTime is after SendingTime", actions);
+                self.terminate("SendingTime accuracy problem", actions);
+                false
+            }
+            Some(_) => true,
+        }
+    }
+
+    fn on_sequence_reset(&mut self, header: &StandardHeader, reset: &SequenceReset, actions: &mut Vec<SessionAction>) {
+        let expected = self.store.next_target_seq_num();
+        match reset.new_seq_no.cmp(&expected) {
+            Ordering::Greater => {
+                println!("[{}] SequenceReset from {} to {}", self.session_id, expected, reset.new_seq_no);
+                self.persist(|store| store.set_next_target_seq_num(reset.new_seq_no));
+            }
+            Ordering::Less => {
+                self.send_reject(header, VALUE_IS_INCORRECT, Some(36), "NewSeqNo is lower than expected MsgSeqNum", actions);
+            }
+            Ordering::Equal => {}
+        }
+    }
+
+    fn on_logon(&mut self, logon: Logon, actions: &mut Vec<SessionAction>) {
+        match self.state {
+            SessionState::AwaitingLogon => {
+                self.heart_bt_int = logon.heart_bt_int;
+                let reset_seq_num_flag = match self.reset_policy {
+                    ResetPolicy::OnLogon => Some('Y'),
+                    _ => logon.reset_seq_num_flag,
+                };
+                let response = Logon {
+                    encrypt_method: 0,
+                    heart_bt_int: self.heart_bt_int,
+                    reset_seq_num_flag,
+                    username: None,
+                    password: None,
+                };
+                self.send(response.to_writer(), actions);
+                self.on_logged_on();
+                println!("[{}] Logon accepted", self.session_id);
+            }
+            SessionState::LogonSent => {
+                self.on_logged_on();
+                println!("[{}] Logon acknowledged", self.session_id);
+            }
+            _ => eprintln!("[{}] Ignoring Logon on an established session", self.session_id),
+        }
+    }
+
+    fn on_logged_on(&mut self) {
+        self.state = SessionState::LoggedOn;
+        self.logout_sent_at = None;
+        let now = self.clock.now();
+        self.logged_on_period_start = self.schedule.as_ref().and_then(|schedule| schedule.current_session_start(now));
+    }
+
+    fn on_logout(&mut self, actions: &mut Vec<SessionAction>) {
+        if self.state != SessionState::LogoutSent {
+            self.send(Logout { text: None }.to_writer(), actions);
+        }
+        println!("[{}] Logged out", self.session_id);
+        self.state = SessionState::Disconnected;
+        actions.push(SessionAction::Disconnect);
+        if self.reset_policy == ResetPolicy::OnLogout {
+            self.reset();
+        }
+    }
+
+    fn on_resend_request(&mut self, resend_request: &ResendRequest, actions: &mut Vec<SessionAction>) {
+        let last_sent = self.store.next_sender_seq_num() - 1;
+        let begin = resend_request.begin_seq_no;
+        let end = if resend_request.end_seq_no == 0 {
+            last_sent
+        } else {
+            resend_request.end_seq_no.min(last_sent)
+        };
+        println!("[{}] Resending messages {} to {}", self.session_id, begin, end);
+        if begin == 0 || begin > end {
+            return;
+        }
+
+        let mut gap_fill_start = None;
+        let mut next = begin;
+        for (seq_num, stored) in self.store.get(begin, end) {
+            if seq_num > next {
+                gap_fill_start.get_or_insert(next);
+            }
+            next = seq_num + 1;
+
+            let writer = match FixMessageWriter::from_message(&stored) {
+                Some(writer) if !is_admin_msg_type(writer.msg_type()) => writer,
+                _ => {
+                    gap_fill_start.get_or_insert(seq_num);
+                    continue;
+                }
+            };
+
+            if let Some(start) = gap_fill_start.take() {
+                self.send_gap_fill(start, seq_num, actions);
+            }
+
+            let orig_sending_time = FixMessageParser::parse_fields(&stored).remove("52");
+            let mut header = self.header(writer.msg_type(), seq_num);
+            header.poss_dup_flag = Some('Y');
+            header.orig_sending_time = orig_sending_time.or_else(|| Some(header.sending_time.clone()));
+            actions.push(SessionAction::Send(writer.encode(&header)));
+        }
+
+        if next <= end {
+            gap_fill_start.get_or_insert(next);
+        }
+        if let Some(start) = gap_fill_start {
+            self.send_gap_fill(start, end + 1, actions);
+        }
+    }
+
+    fn send_gap_fill(&mut self, seq_num: u64, new_seq_no: u64, actions: &mut Vec<SessionAction>) {
+        let gap_fill = SequenceReset { new_seq_no, gap_fill_flag: Some('Y') };
+        let writer = gap_fill.to_writer();
+        let mut header = self.header(writer.msg_type(), seq_num);
+        header.poss_dup_flag = Some('Y');
+        header.orig_sending_time = Some(header.sending_time.clone());
+        actions.push(SessionAction::Send(writer.encode(&header)));
+    }
+
+    fn send_resend_request(&mut self, begin_seq_no: u64, actions: &mut Vec<SessionAction>) {
+        println!("[{}] Requesting resend from {}", self.session_id, begin_seq_no);
+        let resend_request = ResendRequest { begin_seq_no, end_seq_no: 0 };
+        self.send(resend_request.to_writer(), actions);
+    }
+
+    fn send_reject(&mut self, header: &StandardHeader, reason: u32, ref_tag_id: Option<u32>, text: &str, actions: &mut Vec<SessionAction>) {
+        let reject = Reject {
+            ref_seq_num: header.msg_seq_num,
+            ref_tag_id,
+            ref_msg_type: Some(header.msg_type.clone()),
+            session_reject_reason: Some(reason),
+            text: Some(text.to_string()),
+        };
+        self.send(reject.to_writer(), actions);
+    }
+
+    fn terminate(&mut self, text: &str, actions: &mut Vec<SessionAction>) {
+        self.send(Logout { text: Some(text.to_string()) }.to_writer(), actions);
+        self.state = SessionState::Disconnected;
+        actions.push(SessionAction::Disconnect);
+    }
+
+    fn drain_queued_messages(&mut self, actions: &mut Vec<SessionAction>) {
+        while self.state != SessionState::Disconnected {
+            let expected = self.store.next_target_seq_num();
+            self.queued_messages = self.queued_messages.split_off(&expected);
+            match self.queued_messages.remove(&expected) {
+                Some(raw) => self.process(&raw, actions),
+                None => break,
+            }
+        }
+
+        let expected = self.store.next_target_seq_num();
+        if let Some(until) = self.resend_pending_until {
+            if expected > until {
+                println!("[{}] Gap recovered up to {}", self.session_id, until);
+                self.resend_pending_until = None;
+            }
+        }
+    }
+
+    fn send(&mut self, writer: FixMessageWriter, actions: &mut Vec<SessionAction>) {
+        let seq_num = self.store.next_sender_seq_num();
+        let message = writer.encode(&self.header(writer.msg_type(), seq_num));
+        if !is_admin_msg_type(writer.msg_type()) {
+            self.persist(|store| store.store(seq_num, &message));
+        }
+        self.persist(|store| store.set_next_sender_seq_num(seq_num + 1));
+        self.last_sent_at = self.clock.now();
+        actions.push(SessionAction::Send(message));
+    }
+
+    fn header(&self, msg_type: &str, msg_seq_num: u64) -> StandardHeader {
+        StandardHeader {
+            begin_string: self.session_id.begin_string.clone(),
+            msg_type: msg_type.to_string(),
+            sender_comp_id: self.session_id.sender_comp_id.clone(),
+            target_comp_id: self.session_id.target_comp_id.clone(),
+            msg_seq_num,
+            sending_time: self.clock.timestamp(),
+            poss_dup_flag: None,
+            orig_sending_time: None,
+        }
+    }
+
+    fn persist<F: FnOnce(&mut dyn MessageStore) -> io::Result<()>>(&mut self, operation: F) {
+        if let Err(e) = operation(self.store.as_mut()) {
+            eprintln!("[{}] Failed to update message store: {}", self.session_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::authenticator::PasswordAuthenticator;
+    use crate::config::server_config::CounterpartyConfig;
+    use crate::session::message_store::MemoryMessageStore;
+    use crate::session::session_clock::ManualClock;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    /// Plays the client side of a session, building messages with chosen sequence numbers.
+    struct ScriptedCounterparty {
+        next_seq_num: u64,
+    }
+
+    impl ScriptedCounterparty {
+        fn new() -> Self {
+            ScriptedCounterparty { next_seq_num: 1 }
+        }
+
+        fn header(msg_type: &str, msg_seq_num: u64) -> StandardHeader {
+            StandardHeader {
+                begin_string: "FIX.4.2".to_string(),
+                msg_type: msg_type.to_string(),
+                sender_comp_id: "CLIENT".to_string(),
+                target_comp_id: "SERVER".to_string(),
+                msg_seq_num,
+                sending_time: "20231027-15:48:00.123".to_string(),
+                poss_dup_flag: None,
+                orig_sending_time: None,
+            }
+        }
+
+        fn next(&mut self, writer: FixMessageWriter) -> String {
+            let seq_num = self.next_seq_num;
+            self.next_seq_num += 1;
+            writer.encode(&Self::header(writer.msg_type(), seq_num))
+        }
+
+        /// Burns a sequence number, simulating a message lost in transit.
+        fn skip(&mut self) {
+            self.next_seq_num += 1;
+        }
+
+        fn resend(&self, writer: FixMessageWriter, seq_num: u64) -> String {
+            let mut header = Self::header(writer.msg_type(), seq_num);
+            header.poss_dup_flag = Some('Y');
+            header.orig_sending_time = Some("20231027-15:47:00.000".to_string());
+            writer.encode(&header)
+        }
+
+        fn logon() -> FixMessageWriter {
+            Logon { encrypt_method: 0, heart_bt_int: 30, reset_seq_num_flag: None, username: None, password: None }.to_writer()
+        }
+
+        fn new_order(cl_ord_id: &str) -> FixMessageWriter {
+            FixMessageWriter::new("D")
+                .field("11", cl_ord_id)
+                .field("55", "XYZ")
+                .field("54", '1')
+                .field("60", "20231027-15:48:00.123")
+                .field("38", 100)
+                .field("40", '2')
+                .field("44", 50.5)
+        }
+    }
+
+    fn new_clock() -> Arc<ManualClock> {
+        Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2023, 10, 27, 15, 48, 0).unwrap()))
+    }
+
+    fn new_session_with_clock(clock: Arc<ManualClock>) -> FixSession {
+        FixSession::new(SessionId::new("FIX.4.2", "SERVER", "CLIENT"), 30, Box::new(MemoryMessageStore::new()), clock)
+    }
+
+    fn new_session() -> FixSession {
+        new_session_with_clock(new_clock())
+    }
+
+    fn logged_on_session(counterparty: &mut ScriptedCounterparty) -> FixSession {
+        let mut session = new_session();
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+        assert!(session.is_logged_on());
+        session
+    }
+
+    fn sent(actions: &[SessionAction]) -> Vec<HashMap<String, String>> {
+        actions.iter()
+            .filter_map(|action| match action {
+                SessionAction::Send(message) => Some(FixMessageParser::parse_fields(message)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn delivered_cl_ord_ids(actions: &[SessionAction]) -> Vec<String> {
+        actions.iter()
+            .filter_map(|action| match action {
+                SessionAction::Deliver(FixMessage::NewOrder(order)) => Some(order.cl_ord_id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn field<'a>(message: &'a HashMap<String, String>, tag: &str) -> &'a str {
+        message.get(tag).map(String::as_str).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_logon_is_acknowledged() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session();
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+        let sent = sent(&actions);
+
+        assert!(session.is_logged_on());
+        assert_eq!(sent.len(), 1);
+        assert_eq!(field(&sent[0], "35"), "A");
+        assert_eq!(field(&sent[0], "34"), "1");
+    }
+
+    #[test]
+    fn test_gap_sends_resend_request_and_queues_message() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        counterparty.skip();
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("B")));
+        let sent = sent(&actions);
+
+        assert!(delivered_cl_ord_ids(&actions).is_empty());
+        assert_eq!(sent.len(), 1);
+        assert_eq!(field(&sent[0], "35"), "2");
+        assert_eq!(field(&sent[0], "7"), "2");
+        assert_eq!(field(&sent[0], "16"), "0");
+    }
+
+    #[test]
+    fn test_garbled_message_is_dropped_and_recovered_as_a_gap() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        let garbled = counterparty.next(ScriptedCounterparty::new_order("A")).replace("55=XYZ", "55=XYY");
+        let actions = session.on_message(&garbled);
+        assert!(actions.is_empty());
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("B")));
+        let sent = sent(&actions);
+        assert!(delivered_cl_ord_ids(&actions).is_empty());
+        assert_eq!(field(&sent[0], "35"), "2");
+        assert_eq!(field(&sent[0], "7"), "2");
+    }
+
+    #[test]
+    fn test_queued_messages_are_delivered_in_order_after_resend() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        counterparty.skip();
+        session.on_message(&counterparty.next(ScriptedCounterparty::new_order("B")));
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("C")));
+        assert!(sent(&actions).is_empty(), "only one ResendRequest should be outstanding");
+
+        let actions = session.on_message(&counterparty.resend(ScriptedCounterparty::new_order("A"), 2));
+        assert_eq!(delivered_cl_ord_ids(&actions), vec!["A", "B", "C"]);
+
+        let actions = session.on_message(&counterparty.resend(ScriptedCounterparty::new_order("B"), 3));
+        assert!(actions.is_empty(), "duplicate resend should be ignored");
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("D")));
+        assert_eq!(delivered_cl_ord_ids(&actions), vec!["D"]);
+    }
+
+    #[test]
+    fn test_gap_fill_from_counterparty_releases_queue() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        counterparty.skip();
+        counterparty.skip();
+        session.on_message(&counterparty.next(ScriptedCounterparty::new_order("C")));
+
+        let gap_fill = SequenceReset { new_seq_no: 4, gap_fill_flag: Some('Y') }.to_writer();
+        let actions = session.on_message(&counterparty.resend(gap_fill, 2));
+
+        assert_eq!(delivered_cl_ord_ids(&actions), vec!["C"]);
+        assert_eq!(session.store.next_target_seq_num(), 5);
+    }
+
+    #[test]
+    fn test_resend_request_replays_application_messages_and_gap_fills_admin() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        // Outbound: 1=Logon, 2=ExecutionReport, 3=Heartbeat, 4=ExecutionReport, 5=Heartbeat
+        session.send_app_message(FixMessageWriter::new("8").field("11", "A"));
+        session.on_message(&counterparty.next(TestRequest { test_req_id: "T1".to_string() }.to_writer()));
+        session.send_app_message(FixMessageWriter::new("8").field("11", "B"));
+        session.on_message(&counterparty.next(TestRequest { test_req_id: "T2".to_string() }.to_writer()));
+
+        let resend_request = ResendRequest { begin_seq_no: 1, end_seq_no: 0 }.to_writer();
+        let actions = session.on_message(&counterparty.next(resend_request));
+        let sent = sent(&actions);
+
+        let summary: Vec<(&str, &str, &str)> = sent.iter()
+            .map(|message| (field(message, "35"), field(message, "34"), field(message, "36")))
+            .collect();
+        assert_eq!(summary, vec![("4", "1", "2"), ("8", "2", ""), ("4", "3", "4"), ("8", "4", ""), ("4", "5", "6")]);
+
+        for message in &sent {
+            assert_eq!(field(message, "43"), "Y");
+            assert!(message.contains_key("122"));
+        }
+        assert_eq!(field(&sent[0], "123"), "Y");
+        assert_eq!(field(&sent[3], "11"), "B");
+        assert_eq!(session.store.next_sender_seq_num(), 6, "resends must not consume sequence numbers");
+    }
+
+    #[test]
+    fn test_resend_request_with_bounded_end() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        session.send_app_message(FixMessageWriter::new("8").field("11", "A"));
+        session.send_app_message(FixMessageWriter::new("8").field("11", "B"));
+        session.send_app_message(FixMessageWriter::new("8").field("11", "C"));
+
+        let actions = session.on_message(&counterparty.next(ResendRequest { begin_seq_no: 3, end_seq_no: 3 }.to_writer()));
+        let sent = sent(&actions);
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(field(&sent[0], "34"), "3");
+        assert_eq!(field(&sent[0], "11"), "B");
+    }
+
+    #[test]
+    fn test_sequence_too_low_without_poss_dup_logs_out() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        let stale = ScriptedCounterparty::new_order("A").encode(&ScriptedCounterparty::header("D", 1));
+        let actions = session.on_message(&stale);
+
+        assert_eq!(field(&sent(&actions)[0], "35"), "5");
+        assert!(matches!(actions.last(), Some(SessionAction::Disconnect)));
+        assert_eq!(session.state(), SessionState::Disconnected);
+    }
+
+    #[test]
+    fn test_poss_dup_without_orig_sending_time_is_rejected() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        let mut header = ScriptedCounterparty::header("D", 1);
+        header.poss_dup_flag = Some('Y');
+        let actions = session.on_message(&ScriptedCounterparty::new_order("A").encode(&header));
+        let sent = sent(&actions);
+
+        assert_eq!(field(&sent[0], "35"), "3");
+        assert_eq!(field(&sent[0], "371"), "122");
+        assert!(session.is_logged_on());
+    }
+
+    #[test]
+    fn test_sequence_reset_mode_moves_expected_sequence_number() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty);
+
+        let reset = SequenceReset { new_seq_no: 10, gap_fill_flag: None }.to_writer();
+        session.on_message(&counterparty.resend(reset, 99));
+
+        assert_eq!(session.store.next_target_seq_num(), 10);
+    }
+
+    #[test]
+    fn test_message_before_logon_disconnects() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session();
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("A")));
+
+        assert!(matches!(actions.as_slice(), [SessionAction::Disconnect]));
+    }
+
+    #[test]
+    fn test_heartbeat_sent_after_outbound_silence() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session_with_clock(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.advance(Duration::seconds(29));
+        assert!(session.on_timer().is_empty());
+
+        clock.advance(Duration::seconds(1));
+        session.on_message(&counterparty.next(FixMessageWriter::new("0")));
+        let sent = sent(&session.on_timer());
+        assert_eq!(sent.len(), 1);
+        assert_eq!(field(&sent[0], "35"), "0");
+
+        assert!(session.on_timer().is_empty(), "heartbeat restarts the outbound timer");
+    }
+
+    #[test]
+    fn test_test_request_sent_after_inbound_silence_plus_tolerance() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session_with_clock(clock.clone()).with_heartbeat_tolerance(Duration::seconds(5));
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.advance(Duration::seconds(30));
+        let sent_messages = sent(&session.on_timer());
+        assert_eq!(sent_messages.len(), 1, "only a heartbeat within the tolerance");
+        assert_eq!(field(&sent_messages[0], "35"), "0");
+
+        clock.advance(Duration::seconds(5));
+        let sent_messages = sent(&session.on_timer());
+        assert_eq!(sent_messages.len(), 1);
+        assert_eq!(field(&sent_messages[0], "35"), "1");
+        assert_eq!(field(&sent_messages[0], "112"), "TEST1");
+    }
+
+    #[test]
+    fn test_answered_test_request_keeps_session_alive() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session_with_clock(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.advance(Duration::seconds(36));
+        session.on_timer();
+        let heartbeat = Heartbeat { test_req_id: Some("TEST1".to_string()) }.to_writer();
+        session.on_message(&counterparty.next(heartbeat));
+
+        clock.advance(Duration::seconds(30));
+        let actions = session.on_timer();
+        assert!(!actions.iter().any(|action| matches!(action, SessionAction::Disconnect)));
+        assert!(session.is_logged_on());
+    }
+
+    #[test]
+    fn test_unanswered_test_request_disconnects() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session_with_clock(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.advance(Duration::seconds(36));
+        session.on_timer();
+
+        clock.advance(Duration::seconds(29));
+        assert!(!session.on_timer().iter().any(|action| matches!(action, SessionAction::Disconnect)));
+
+        clock.advance(Duration::seconds(1));
+        let actions = session.on_timer();
+        assert_eq!(field(&sent(&actions)[0], "35"), "5");
+        assert!(matches!(actions.last(), Some(SessionAction::Disconnect)));
+        assert_eq!(session.state(), SessionState::Disconnected);
+    }
+
+    #[test]
+    fn test_timers_idle_before_logon() {
+        let clock = new_clock();
+        let mut session = new_session_with_clock(clock.clone());
+
+        clock.advance(Duration::seconds(120));
+        assert!(session.on_timer().is_empty());
+    }
+
+    #[test]
+    fn test_missing_required_tag_is_rejected_and_consumes_sequence_number() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let dictionary: DataDictionary = toml::from_str("[messages.D]\nrequired = [11, 21]\n").unwrap();
+        let mut session = new_session().with_data_dictionary(Arc::new(dictionary));
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("A")));
+        let sent = sent(&actions);
+
+        assert!(delivered_cl_ord_ids(&actions).is_empty());
+        assert_eq!(field(&sent[0], "35"), "3");
+        assert_eq!(field(&sent[0], "371"), "21");
+        assert_eq!(session.store.next_target_seq_num(), 3);
+    }
+
+    #[test]
+    fn test_reset_on_logon_policy_restarts_sequence_numbers() {
+        let mut session = new_session().with_reset_policy(ResetPolicy::OnLogon);
+        session.store.set_next_sender_seq_num(40).unwrap();
+        session.store.set_next_target_seq_num(40).unwrap();
+
+        let logon = ScriptedCounterparty::logon().encode(&ScriptedCounterparty::header("A", 1));
+        let sent = sent(&session.on_message(&logon));
+
+        assert!(session.is_logged_on());
+        assert_eq!(field(&sent[0], "34"), "1");
+        assert_eq!(field(&sent[0], "141"), "Y");
+        assert_eq!(session.store.next_target_seq_num(), 2);
+    }
+
+    #[test]
+    fn test_reset_on_disconnect_policy() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = new_session().with_reset_policy(ResetPolicy::OnDisconnect);
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        session.on_disconnect();
+
+        assert_eq!(session.store.next_sender_seq_num(), 1);
+        assert_eq!(session.store.next_target_seq_num(), 1);
+    }
+
+    fn scheduled_session(clock: Arc<ManualClock>) -> FixSession {
+        let schedule = SessionSchedule::new(&crate::config::server_config::ScheduleConfig {
+            start_time: "07:00:00".to_string(),
+            end_time: "17:30:00".to_string(),
+            time_zone: "UTC".to_string(),
+            weekdays: None,
+            start_day: None,
+            end_day: None,
+        }).unwrap();
+        new_session_with_clock(clock).with_schedule(schedule).with_reset_policy(ResetPolicy::Daily)
+    }
+
+    #[test]
+    fn test_logon_outside_schedule_is_refused() {
+        let clock = new_clock();
+        clock.set(Utc.with_ymd_and_hms(2023, 10, 27, 18, 0, 0).unwrap());
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = scheduled_session(clock);
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        assert_eq!(field(&sent(&actions)[0], "35"), "5");
+        assert!(matches!(actions.last(), Some(SessionAction::Disconnect)));
+        assert!(!session.is_logged_on());
+    }
+
+    #[test]
+    fn test_logout_at_end_of_schedule() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = scheduled_session(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.set(Utc.with_ymd_and_hms(2023, 10, 27, 17, 30, 0).unwrap());
+        let sent = sent(&session.on_timer());
+        assert_eq!(field(&sent[0], "35"), "5");
+        assert_eq!(field(&sent[0], "58"), "End of trading session");
+        assert_eq!(session.state(), SessionState::LogoutSent);
+
+        let actions = session.on_message(&counterparty.next(Logout { text: None }.to_writer()));
+        assert!(matches!(actions.as_slice(), [SessionAction::Disconnect]));
+    }
+
+    #[test]
+    fn test_unconfirmed_logout_times_out() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = scheduled_session(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+
+        clock.set(Utc.with_ymd_and_hms(2023, 10, 27, 17, 30, 0).unwrap());
+        session.on_timer();
+        clock.advance(Duration::seconds(LOGOUT_TIMEOUT_SECS));
+
+        assert!(matches!(session.on_timer().as_slice(), [SessionAction::Disconnect]));
+    }
+
+    #[test]
+    fn test_daily_reset_at_start_of_next_trading_day() {
+        let clock = new_clock();
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = scheduled_session(clock.clone());
+        session.on_message(&counterparty.next(ScriptedCounterparty::logon()));
+        session.on_message(&counterparty.next(ScriptedCounterparty::new_order("A")));
+        session.on_disconnect();
+
+        clock.set(Utc.with_ymd_and_hms(2023, 10, 27, 20, 0, 0).unwrap());
+        session.check_schedule();
+        assert_eq!(session.store.next_target_seq_num(), 3, "no reset until the next window opens");
+
+        clock.set(Utc.with_ymd_and_hms(2023, 10, 28, 7, 0, 0).unwrap());
+        session.check_schedule();
+        assert_eq!(session.store.next_sender_seq_num(), 1);
+        assert_eq!(session.store.next_target_seq_num(), 1);
+
+        let mut next_day = ScriptedCounterparty::new();
+        session.on_message(&next_day.next(ScriptedCounterparty::logon()));
+        assert!(session.is_logged_on());
+    }
+
+    fn client_config() -> CounterpartyConfig {
+        CounterpartyConfig {
+            comp_id: "CLIENT".to_string(),
+            username: Some("client".to_string()),
+            password: Some("secret".to_string()),
+            accounts: None,
+            symbols: Some(vec!["ABC".to_string()]),
+            msg_types: None,
+        }
+    }
+
+    #[test]
+    fn test_logon_with_wrong_password_is_refused() {
+        let authenticator = Arc::new(PasswordAuthenticator::new(&[client_config()]));
+        let mut session = new_session().with_authenticator(authenticator);
+        let mut counterparty = ScriptedCounterparty::new();
+
+        let logon = Logon {
+            encrypt_method: 0,
+            heart_bt_int: 30,
+            reset_seq_num_flag: None,
+            username: Some("client".to_string()),
+            password: Some("wrong".to_string()),
+        };
+        let actions = session.on_message(&counterparty.next(logon.to_writer()));
+
+        assert!(!session.is_logged_on());
+        assert_eq!(sent(&actions)[0]["35"], "5");
+        assert_eq!(sent(&actions)[0]["58"], "Invalid username or password");
+        assert!(matches!(actions.last(), Some(SessionAction::Disconnect)));
+    }
+
+    #[test]
+    fn test_unentitled_order_gets_business_message_reject() {
+        let mut counterparty = ScriptedCounterparty::new();
+        let mut session = logged_on_session(&mut counterparty)
+            .with_entitlements(Arc::new(Entitlements::new(&[client_config()])));
+
+        let actions = session.on_message(&counterparty.next(ScriptedCounterparty::new_order("A")));
+
+        assert!(delivered_cl_ord_ids(&actions).is_empty());
+        let reject = &sent(&actions)[0];
+        assert_eq!(reject["35"], "j");
+        assert_eq!(reject["45"], "2");
+        assert_eq!(reject["372"], "D");
+        assert_eq!(reject["379"], "A");
+        assert_eq!(reject["380"], NOT_AUTHORIZED.to_string());
+        assert_eq!(reject["58"], "CLIENT may not trade symbol XYZ");
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const SEQ_NUMS_FILE: &str = "seqnums";
+const MESSAGES_FILE: &str = "messages";
+const CREATION_TIME_FILE: &str = "creation_time";
+
+/// Persists a session's sequence numbers and the outbound application messages that may have
+/// to be resent when the counterparty issues a ResendRequest.
+pub trait MessageStore: Send {
+    fn next_sender_seq_num(&self) -> u64;
+    fn next_target_seq_num(&self) -> u64;
+    fn set_next_sender_seq_num(&mut self, seq_num: u64) -> io::Result<()>;
+    fn set_next_target_seq_num(&mut self, seq_num: u64) -> io::Result<()>;
+    fn store(&mut self, seq_num: u64, message: &str) -> io::Result<()>;
+    /// Returns the stored messages with `begin <= seq_num <= end`, in sequence order.
+    fn get(&self, begin: u64, end: u64) -> Vec<(u64, String)>;
+    /// When the current sequence started, i.e. the time of the last reset.
+    fn creation_time(&self) -> Option<DateTime<Utc>>;
+    fn set_creation_time(&mut self, creation_time: DateTime<Utc>) -> io::Result<()>;
+    fn reset(&mut self) -> io::Result<()>;
+}
+
+pub struct MemoryMessageStore {
+    next_sender_seq_num: u64,
+    next_target_seq_num: u64,
+    messages: BTreeMap<u64, String>,
+    creation_time: Option<DateTime<Utc>>,
+}
+
+impl MemoryMessageStore {
+    pub fn new() -> Self {
+        MemoryMessageStore {
+            next_sender_seq_num: 1,
+            next_target_seq_num: 1,
+            messages: BTreeMap::new(),
+            creation_time: None,
+        }
+    }
+}
+
+impl Default for MemoryMessageStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageStore for MemoryMessageStore {
+    fn next_sender_seq_num(&self) -> u64 {
+        self.next_sender_seq_num
+    }
+
+    fn next_target_seq_num(&self) -> u64 {
+        self.next_target_seq_num
+    }
+
+    fn set_next_sender_seq_num(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_sender_seq_num = seq_num;
+        Ok(())
+    }
+
+    fn set_next_target_seq_num(&mut self, seq_num: u64) -> io::Result<()> {
+        self.next_target_seq_num = seq_num;
+        Ok(())
+    }
+
+    fn store(&mut self, seq_num: u64, message: &str) -> io::Result<()> {
+        self.messages.insert(seq_num, message.to_string());
+        Ok(())
+    }
+
+    fn get(&self, begin: u64, end: u64) -> Vec<(u64, String)> {
+        if begin > end {
+            return Vec::new();
+        }
+        self.messages
+            .range(begin..=end)
+            .map(|(seq_num, message)| (*seq_num, message.clone()))
+            .collect()
+    }
+
+    fn creation_time(&self) -> Option<DateTime<Utc>> {
+        self.creation_time
+    }
+
+    fn set_creation_time(&mut self, creation_time: DateTime<Utc>) -> io::Result<()> {
+        self.creation_time = Some(creation_time);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.next_sender_seq_num = 1;
+        self.next_target_seq_num = 1;
+        self.messages.clear();
+        self.creation_time = None;
+        Ok(())
+    }
+}
+
+/// Message store persisted in a directory so that sequence numbers and resendable messages
+/// survive a restart. `seqnums` holds the next sender and target sequence numbers,
+/// `creation_time` the time of the last reset, and `messages` is an append-only log of
+/// `seq_num<TAB>message` lines.
+pub struct FileMessageStore {
+    directory: PathBuf,
+    cache: MemoryMessageStore,
+    messages_file: File,
+}
+
+impl FileMessageStore {
+    pub fn open<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        let mut cache = MemoryMessageStore::new();
+
+        if let Ok(seq_nums) = fs::read_to_string(directory.join(SEQ_NUMS_FILE)) {
+            let mut parts = seq_nums.split_whitespace().map(str::parse::<u64>);
+            if let (Some(Ok(sender)), Some(Ok(target))) = (parts.next(), parts.next()) {
+                cache.next_sender_seq_num = sender;
+                cache.next_target_seq_num = target;
+            }
+        }
+
+        if let Ok(creation_time) = fs::read_to_string(directory.join(CREATION_TIME_FILE)) {
+            cache.creation_time = DateTime::parse_from_rfc3339(creation_time.trim())
+                .ok()
+                .map(|time| time.with_timezone(&Utc));
+        }
+
+        let messages_path = directory.join(MESSAGES_FILE);
+        if messages_path.exists() {
+            for line in BufReader::new(File::open(&messages_path)?).lines() {
+                let line = line?;
+                if let Some((seq_num, message)) = line.split_once('\t') {
+                    if let Ok(seq_num) = seq_num.parse() {
+                        cache.messages.insert(seq_num, message.to_string());
+                    }
+                }
+            }
+        }
+
+        let messages_file = OpenOptions::new().create(true).append(true).open(&messages_path)?;
+        Ok(FileMessageStore { directory, cache, messages_file })
+    }
+
+    fn write_seq_nums(&self) -> io::Result<()> {
+        let seq_nums = format!("{} {}\n", self.cache.next_sender_seq_num, self.cache.next_target_seq_num);
+        let temp_path = self.directory.join(format!("{}.tmp", SEQ_NUMS_FILE));
+        fs::write(&temp_path, seq_nums)?;
+        fs::rename(temp_path, self.directory.join(SEQ_NUMS_FILE))
+    }
+}
+
+impl MessageStore for FileMessageStore {
+    fn next_sender_seq_num(&self) -> u64 {
+        self.cache.next_sender_seq_num
+    }
+
+    fn next_target_seq_num(&self) -> u64 {
+        self.cache.next_target_seq_num
+    }
+
+    fn set_next_sender_seq_num(&mut self, seq_num: u64) -> io::Result<()> {
+        self.cache.next_sender_seq_num = seq_num;
+        self.write_seq_nums()
+    }
+
+    fn set_next_target_seq_num(&mut self, seq_num: u64) -> io::Result<()> {
+        self.cache.next_target_seq_num = seq_num;
+        self.write_seq_nums()
+    }
+
+    fn store(&mut self, seq_num: u64, message: &str) -> io::Result<()> {
+        writeln!(self.messages_file, "{}\t{}", seq_num, message)?;
+        self.cache.store(seq_num, message)
+    }
+
+    fn get(&self, begin: u64, end: u64) -> Vec<(u64, String)> {
+        self.cache.get(begin, end)
+    }
+
+    fn creation_time(&self) -> Option<DateTime<Utc>> {
+        self.cache.creation_time
+    }
+
+    fn set_creation_time(&mut self, creation_time: DateTime<Utc>) -> io::Result<()> {
+        self.cache.creation_time = Some(creation_time);
+        fs::write(self.directory.join(CREATION_TIME_FILE), creation_time.to_rfc3339())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.cache.reset()?;
+        self.messages_file.set_len(0)?;
+        let _ = fs::remove_file(self.directory.join(CREATION_TIME_FILE));
+        self.write_seq_nums()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static DIR_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    fn temp_dir() -> PathBuf {
+        let mut path = std::env::temp_dir();
+        let counter = DIR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        path.push(format!("fix_message_store_{}_{}", std::process::id(), counter));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_file_store_survives_reopen() {
+        let directory = temp_dir();
+        {
+            let mut store = FileMessageStore::open(&directory).unwrap();
+            store.store(1, "8=FIX.4.2|35=8|34=1|10=000|").unwrap();
+            store.set_next_sender_seq_num(2).unwrap();
+            store.set_next_target_seq_num(5).unwrap();
+            store.set_creation_time(DateTime::parse_from_rfc3339("2024-01-15T07:00:00Z").unwrap().with_timezone(&Utc)).unwrap();
+        }
+
+        let store = FileMessageStore::open(&directory).unwrap();
+        assert_eq!(store.next_sender_seq_num(), 2);
+        assert_eq!(store.next_target_seq_num(), 5);
+        assert_eq!(store.creation_time().unwrap().to_rfc3339(), "2024-01-15T07:00:00+00:00");
+        assert_eq!(store.get(1, 1), vec![(1, "8=FIX.4.2|35=8|34=1|10=000|".to_string())]);
+    }
+
+    #[test]
+    fn test_file_store_reset_clears_messages() {
+        let directory = temp_dir();
+        let mut store = FileMessageStore::open(&directory).unwrap();
+        store.store(1, "8=FIX.4.2|35=8|34=1|10=000|").unwrap();
+        store.set_next_sender_seq_num(2).unwrap();
+        store.reset().unwrap();
+        drop(store);
+
+        let store = FileMessageStore::open(&directory).unwrap();
+        assert_eq!(store.next_sender_seq_num(), 1);
+        assert!(store.get(1, 10).is_empty());
+    }
+}
@@ -0,0 +1,10 @@
+pub mod cancel_on_disconnect;
+pub mod fix_session;
+pub mod message_store;
+pub mod session_acceptor;
+pub mod session_clock;
+pub mod session_connection;
+pub mod session_handle;
+pub mod session_initiator;
+pub mod session_manager;
+pub mod session_schedule;
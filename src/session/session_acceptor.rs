@@ -0,0 +1,88 @@
+use crate::fix::fix_message_parser::FixMessageParser;
+use crate::fix::messages::standard_header::StandardHeader;
+use crate::oms::oms_dispatcher::OmsDispatcher;
+use crate::session::fix_session::SessionId;
+use crate::session::session_connection::SessionConnection;
+use crate::session::session_handle::SessionHandle;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+const LOGON_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts FIX connections on one address for a fixed set of configured sessions. Sessions
+/// outlive their connections so that sequence numbers carry over when a counterparty reconnects.
+pub struct SessionAcceptor {
+    address: String,
+    sessions: HashMap<SessionId, Arc<SessionHandle>>,
+    sender: OmsDispatcher,
+}
+
+impl SessionAcceptor {
+    pub fn new(address: String, sessions: HashMap<SessionId, Arc<SessionHandle>>, sender: OmsDispatcher) -> Self {
+        SessionAcceptor { address, sessions, sender }
+    }
+
+    pub async fn listen(self: Arc<Self>) {
+        let listener = match TcpListener::bind(&self.address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to bind FIX acceptor on {}: {}", self.address, e);
+                return;
+            }
+        };
+        println!("FIX acceptor listening on {} for {} session(s)", self.address, self.sessions.len());
+
+        while let Ok((stream, peer)) = listener.accept().await {
+            println!("FIX connection from {}", peer);
+            let acceptor = Arc::clone(&self);
+            tokio::spawn(async move {
+                acceptor.handle_connection(stream).await;
+            });
+        }
+    }
+
+    async fn handle_connection(&self, stream: TcpStream) {
+        let mut connection = SessionConnection::new(stream);
+        let message = match tokio::time::timeout(LOGON_TIMEOUT, connection.read_message()).await {
+            Ok(Some(message)) => message,
+            _ => {
+                eprintln!("No Logon received on {}, closing connection", self.address);
+                return;
+            }
+        };
+
+        let handle = match self.session_for_logon(&message) {
+            Some(handle) => handle,
+            None => return,
+        };
+        let actions = handle.session().lock().unwrap().on_message(&message);
+        connection.run(&handle, actions, &self.sender).await;
+    }
+
+    fn session_for_logon(&self, message: &str) -> Option<Arc<SessionHandle>> {
+        let header = match StandardHeader::new(&FixMessageParser::parse_fields(message)) {
+            Ok(header) if header.msg_type == "A" => header,
+            _ => {
+                eprintln!("Rejecting connection on {}, first message was not a Logon", self.address);
+                return None;
+            }
+        };
+
+        let session_id = SessionId::new(&header.begin_string, &header.target_comp_id, &header.sender_comp_id);
+        let handle = match self.sessions.get(&session_id) {
+            Some(handle) => Arc::clone(handle),
+            None => {
+                eprintln!("Rejecting Logon for unknown session {}", session_id);
+                return None;
+            }
+        };
+
+        if handle.session().lock().unwrap().is_logged_on() {
+            eprintln!("[{}] Rejecting second connection for a logged on session", session_id);
+            return None;
+        }
+        Some(handle)
+    }
+}
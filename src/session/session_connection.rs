@@ -0,0 +1,138 @@
+use crate::fix::fix_message_parser::SOH;
+use crate::oms::oms_dispatcher::OmsDispatcher;
+use crate::oms::oms_request::OmsRequest;
+use crate::session::fix_session::SessionAction;
+use crate::session::session_handle::SessionHandle;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TRAILER_START: &str = "10=";
+const TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A TCP connection carrying one FIX session, shared by acceptors and initiators.
+pub struct SessionConnection {
+    stream: TcpStream,
+    buffer: String,
+}
+
+impl SessionConnection {
+    pub fn new(stream: TcpStream) -> Self {
+        SessionConnection { stream, buffer: String::new() }
+    }
+
+    /// Reads the next complete message, or `None` once the peer has closed the connection.
+    pub async fn read_message(&mut self) -> Option<String> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(message) = next_frame(&mut self.buffer) {
+                return Some(message);
+            }
+            match self.stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return None,
+                Ok(read) => self.buffer.push_str(&String::from_utf8_lossy(&chunk[..read])),
+            }
+        }
+    }
+
+    /// Drives the session until either side disconnects, starting with `actions` the session has
+    /// already produced (its Logon, or the response to the counterparty's). Messages the OMS
+    /// queues for the session are sent as they arrive.
+    pub async fn run(mut self, handle: &SessionHandle, actions: Vec<SessionAction>, sender: &OmsDispatcher) {
+        let session = handle.session();
+        let mut outbound = handle.outbound().lock().await;
+        let mut timer = tokio::time::interval(TIMER_INTERVAL);
+        let mut connected = self.execute(actions, handle, sender).await;
+        // Only a logged on counterparty counts as connected, so a rejected Logon cannot
+        // trigger a cancel of its orders
+        let (cancel_on_disconnect, grace) = handle.cancel_on_disconnect();
+        let mut logged_on = false;
+
+        while connected {
+            if !logged_on && session.lock().unwrap().is_logged_on() {
+                logged_on = true;
+                cancel_on_disconnect.connected(handle.connection_id());
+            }
+            tokio::select! {
+                message = self.read_message() => {
+                    connected = match message {
+                        Some(message) => {
+                            let actions = session.lock().unwrap().on_message(&message);
+                            self.execute(actions, handle, sender).await
+                        }
+                        None => false,
+                    };
+                }
+                Some(message) = outbound.recv() => {
+                    cancel_on_disconnect.reported(handle.connection_id(), Some(handle.comp_id()), &message);
+                    let actions = match message.to_writer() {
+                        Some(writer) => session.lock().unwrap().send_app_message(writer),
+                        None => Vec::new(),
+                    };
+                    connected = self.execute(actions, handle, sender).await;
+                }
+                _ = timer.tick() => {
+                    let actions = session.lock().unwrap().on_timer();
+                    connected = self.execute(actions, handle, sender).await;
+                }
+            }
+        }
+
+        if logged_on {
+            cancel_on_disconnect.disconnected(handle.connection_id(), grace, sender);
+        }
+        let mut session = session.lock().unwrap();
+        println!("[{}] Connection closed", session.session_id());
+        session.on_disconnect();
+    }
+
+    /// Carries out the session's actions, returning false once the connection should close.
+    async fn execute(&mut self, actions: Vec<SessionAction>, handle: &SessionHandle, sender: &OmsDispatcher) -> bool {
+        for action in actions {
+            match action {
+                SessionAction::Send(outbound) => {
+                    if self.stream.write_all(outbound.as_bytes()).await.is_err() {
+                        return false;
+                    }
+                }
+                SessionAction::Deliver(fix_message) => {
+                    handle.cancel_on_disconnect().0.entered(handle.connection_id(), Some(handle.comp_id()), &fix_message);
+                    if sender.send(OmsRequest::new(fix_message, handle.reply_to()).with_session(handle.comp_id())).await.is_err() {
+                        eprintln!("Failed to send message to channel");
+                    }
+                }
+                SessionAction::Disconnect => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Removes and returns the first complete message from `buffer`, i.e. everything up to and
+/// including the SOH that terminates the CheckSum (10) field.
+fn next_frame(buffer: &mut String) -> Option<String> {
+    let trailer = format!("{}{}", SOH, TRAILER_START);
+    let trailer_pos = buffer.find(&trailer)?;
+    let end = trailer_pos + trailer.len() + buffer[trailer_pos + trailer.len()..].find(SOH)? + 1;
+
+    let frame: String = buffer.drain(..end).collect();
+    Some(frame.trim_start().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_frame_splits_on_checksum() {
+        let soh = |message: &str| message.replace('|', "\x01");
+        let mut buffer = soh("8=FIX.4.2|35=0|110=x|10=123|\n8=FIX.4.2|35=1|10=");
+
+        assert_eq!(next_frame(&mut buffer), Some(soh("8=FIX.4.2|35=0|110=x|10=123|")));
+        assert_eq!(next_frame(&mut buffer), None);
+
+        buffer.push_str(&soh("045|"));
+        assert_eq!(next_frame(&mut buffer), Some(soh("8=FIX.4.2|35=1|10=045|")));
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::fix::fix_message::FixMessage;
+use crate::oms::oms_request::ReplyTo;
+use crate::session::cancel_on_disconnect::CancelOnDisconnect;
+use crate::session::fix_session::FixSession;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// A session together with the queue of application messages the OMS wants sent on it. The
+/// queue outlives connections; whichever connection carries the session drains it.
+pub struct SessionHandle {
+    session: Arc<Mutex<FixSession>>,
+    /// CompID of the counterparty, who sends as the session's target.
+    comp_id: String,
+    /// The session ID, which every connection carrying the session shares.
+    connection_id: String,
+    cancel_on_disconnect: CancelOnDisconnect,
+    /// How long the session may be gone before the orders entered through it are canceled, if at all.
+    cancel_on_disconnect_grace: Option<Duration>,
+    outbound_sender: UnboundedSender<FixMessage>,
+    outbound: tokio::sync::Mutex<UnboundedReceiver<FixMessage>>,
+}
+
+impl SessionHandle {
+    pub fn new(session: FixSession) -> Self {
+        let (outbound_sender, outbound) = mpsc::unbounded_channel();
+        SessionHandle {
+            comp_id: session.session_id().target_comp_id.clone(),
+            connection_id: session.session_id().to_string(),
+            cancel_on_disconnect: CancelOnDisconnect::default(),
+            cancel_on_disconnect_grace: None,
+            session: Arc::new(Mutex::new(session)),
+            outbound_sender,
+            outbound: tokio::sync::Mutex::new(outbound),
+        }
+    }
+
+    pub fn with_cancel_on_disconnect(mut self, cancel_on_disconnect: CancelOnDisconnect, grace: Option<Duration>) -> Self {
+        self.cancel_on_disconnect = cancel_on_disconnect;
+        self.cancel_on_disconnect_grace = grace;
+        self
+    }
+
+    pub fn session(&self) -> &Arc<Mutex<FixSession>> {
+        &self.session
+    }
+
+    pub fn comp_id(&self) -> &str {
+        &self.comp_id
+    }
+
+    /// What cancel-on-disconnect knows the session's connections by.
+    pub fn connection_id(&self) -> &str {
+        &self.connection_id
+    }
+
+    pub fn cancel_on_disconnect(&self) -> (&CancelOnDisconnect, Option<Duration>) {
+        (&self.cancel_on_disconnect, self.cancel_on_disconnect_grace)
+    }
+
+    /// Route for OMS replies to messages received on this session.
+    pub fn reply_to(&self) -> ReplyTo {
+        ReplyTo::Connection(self.outbound_sender.clone())
+    }
+
+    pub(crate) fn outbound(&self) -> &tokio::sync::Mutex<UnboundedReceiver<FixMessage>> {
+        &self.outbound
+    }
+}
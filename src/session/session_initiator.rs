@@ -0,0 +1,42 @@
+use crate::oms::oms_dispatcher::OmsDispatcher;
+use crate::session::session_connection::SessionConnection;
+use crate::session::session_handle::SessionHandle;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+const SCHEDULE_WAIT: Duration = Duration::from_secs(1);
+
+/// Connects out to a counterparty, logs on, and reconnects after every disconnect. Outside the
+/// session's schedule no connection is attempted.
+pub struct SessionInitiator {
+    address: String,
+    handle: Arc<SessionHandle>,
+    sender: OmsDispatcher,
+    reconnect_interval: Duration,
+}
+
+impl SessionInitiator {
+    pub fn new(address: String, handle: Arc<SessionHandle>, sender: OmsDispatcher, reconnect_interval: Duration) -> Self {
+        SessionInitiator { address, handle, sender, reconnect_interval }
+    }
+
+    pub async fn run(self) {
+        let session = self.handle.session();
+        loop {
+            if !session.lock().unwrap().is_session_time() {
+                tokio::time::sleep(SCHEDULE_WAIT).await;
+                continue;
+            }
+            match TcpStream::connect(&self.address).await {
+                Ok(stream) => {
+                    println!("[{}] Connected to {}", session.lock().unwrap().session_id(), self.address);
+                    let actions = session.lock().unwrap().logon();
+                    SessionConnection::new(stream).run(&self.handle, actions, &self.sender).await;
+                }
+                Err(e) => eprintln!("Failed to connect to {}: {}", self.address, e),
+            }
+            tokio::time::sleep(self.reconnect_interval).await;
+        }
+    }
+}
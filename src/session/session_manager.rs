@@ -0,0 +1,118 @@
+use crate::auth::access_control::AccessControl;
+use crate::config::server_config::{SessionConfig, SessionRole};
+use crate::fix::data_dictionary::DataDictionary;
+use crate::oms::oms_dispatcher::OmsDispatcher;
+use crate::session::cancel_on_disconnect::CancelOnDisconnect;
+use crate::session::fix_session::{FixSession, SessionId};
+use crate::session::message_store::{FileMessageStore, MemoryMessageStore, MessageStore};
+use crate::session::session_acceptor::SessionAcceptor;
+use crate::session::session_clock::Clock;
+use crate::session::session_handle::SessionHandle;
+use crate::session::session_initiator::SessionInitiator;
+use crate::session::session_schedule::SessionSchedule;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Owns every configured session and starts the acceptors and initiators that carry them.
+pub struct SessionManager {
+    configs: Vec<SessionConfig>,
+    sessions: HashMap<SessionId, Arc<SessionHandle>>,
+    cancel_on_disconnect: CancelOnDisconnect,
+}
+
+impl SessionManager {
+    pub fn new(configs: &[SessionConfig], access_control: &AccessControl, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let mut dictionaries: HashMap<_, Arc<DataDictionary>> = HashMap::new();
+        let mut sessions = HashMap::new();
+        let cancel_on_disconnect = CancelOnDisconnect::default();
+
+        for config in configs {
+            let session_id = SessionId::new(&config.begin_string, &config.sender_comp_id, &config.target_comp_id);
+            let store: Box<dyn MessageStore> = match &config.message_store_path {
+                Some(path) => Box::new(FileMessageStore::open(path)
+                    .map_err(|e| format!("Failed to open message store for {}: {}", session_id, e))?),
+                None => Box::new(MemoryMessageStore::new()),
+            };
+
+            let mut session = FixSession::new(session_id.clone(), config.heart_bt_int, store, Arc::clone(&clock))
+                .with_reset_policy(config.reset_policy)
+                .with_entitlements(access_control.entitlements());
+            if let Some(authenticator) = access_control.authenticator() {
+                session = session.with_authenticator(authenticator);
+            }
+            if let (Some(username), Some(password)) = (&config.username, &config.password) {
+                session = session.with_credentials(username.clone(), password.clone());
+            }
+            if let Some(path) = &config.data_dictionary {
+                let dictionary = match dictionaries.get(path) {
+                    Some(dictionary) => Arc::clone(dictionary),
+                    None => {
+                        let dictionary = Arc::new(DataDictionary::load(path)?);
+                        dictionaries.insert(path.clone(), Arc::clone(&dictionary));
+                        dictionary
+                    }
+                };
+                session = session.with_data_dictionary(dictionary);
+            }
+            if let Some(schedule) = &config.schedule {
+                session = session.with_schedule(SessionSchedule::new(schedule)?);
+            }
+
+            let grace = config.cancel_on_disconnect_secs.map(Duration::from_secs);
+            let handle = SessionHandle::new(session).with_cancel_on_disconnect(cancel_on_disconnect.clone(), grace);
+            sessions.insert(session_id, Arc::new(handle));
+        }
+
+        Ok(SessionManager { configs: configs.to_vec(), sessions, cancel_on_disconnect })
+    }
+
+    /// Which counterparties are connected, shared with the WebSocket endpoint so that a
+    /// counterparty on either keeps its orders.
+    pub fn cancel_on_disconnect(&self) -> CancelOnDisconnect {
+        self.cancel_on_disconnect.clone()
+    }
+
+    pub fn session(&self, session_id: &SessionId) -> Option<Arc<SessionHandle>> {
+        self.sessions.get(session_id).cloned()
+    }
+
+    /// Spawns one acceptor per listening address, one initiator per initiator session, and a
+    /// task that applies schedule-driven resets to sessions with no live connection.
+    pub fn start(&self, sender: OmsDispatcher) {
+        let mut acceptors: HashMap<String, HashMap<SessionId, Arc<SessionHandle>>> = HashMap::new();
+
+        for config in &self.configs {
+            let session_id = SessionId::new(&config.begin_string, &config.sender_comp_id, &config.target_comp_id);
+            let session = Arc::clone(&self.sessions[&session_id]);
+            match config.role {
+                SessionRole::Acceptor => {
+                    acceptors.entry(config.address()).or_default().insert(session_id, session);
+                }
+                SessionRole::Initiator => {
+                    let reconnect_interval = Duration::from_secs(config.reconnect_interval);
+                    let initiator = SessionInitiator::new(config.address(), session, sender.clone(), reconnect_interval);
+                    tokio::spawn(initiator.run());
+                }
+            }
+        }
+
+        for (address, sessions) in acceptors {
+            let acceptor = Arc::new(SessionAcceptor::new(address, sessions, sender.clone()));
+            tokio::spawn(acceptor.listen());
+        }
+
+        let sessions: Vec<_> = self.sessions.values().cloned().collect();
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+            loop {
+                timer.tick().await;
+                for session in &sessions {
+                    session.session().lock().unwrap().check_schedule();
+                }
+            }
+        });
+    }
+}
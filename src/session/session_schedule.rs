@@ -0,0 +1,222 @@
+use crate::config::server_config::ScheduleConfig;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// When a session is allowed to be logged on, evaluated in the schedule's time zone.
+///
+/// Three shapes are supported:
+/// - daily: `start_time` to `end_time` every day, crossing midnight when the end is earlier
+///   than the start and running a full 24 hours when they are equal;
+/// - daily on selected `weekdays`, where a day names the date the session starts on;
+/// - weekly: from `start_day` at `start_time` to `end_day` at `end_time`.
+#[derive(Debug, Clone)]
+pub struct SessionSchedule {
+    start_time: NaiveTime,
+    end_time: NaiveTime,
+    time_zone: Tz,
+    weekdays: Option<Vec<Weekday>>,
+    weekly: Option<(Weekday, Weekday)>,
+}
+
+impl SessionSchedule {
+    pub fn new(config: &ScheduleConfig) -> Result<Self, String> {
+        let parse_time = |time: &str| NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .map_err(|_| format!("Invalid schedule time {}", time));
+        let parse_day = |day: &str| day.parse::<Weekday>()
+            .map_err(|_| format!("Invalid schedule day {}", day));
+
+        let time_zone = config.time_zone.parse::<Tz>()
+            .map_err(|_| format!("Invalid schedule time zone {}", config.time_zone))?;
+
+        let weekly = match (&config.start_day, &config.end_day) {
+            (Some(start_day), Some(end_day)) => Some((parse_day(start_day)?, parse_day(end_day)?)),
+            (None, None) => None,
+            _ => return Err("Weekly schedules need both start_day and end_day".to_string()),
+        };
+
+        let weekdays = match &config.weekdays {
+            Some(_) if weekly.is_some() => return Err("A schedule cannot have both weekdays and start_day/end_day".to_string()),
+            Some(days) => Some(days.iter().map(|day| parse_day(day)).collect::<Result<Vec<_>, _>>()?),
+            None => None,
+        };
+
+        Ok(SessionSchedule {
+            start_time: parse_time(&config.start_time)?,
+            end_time: parse_time(&config.end_time)?,
+            time_zone,
+            weekdays,
+            weekly,
+        })
+    }
+
+    pub fn is_session_time(&self, now: DateTime<Utc>) -> bool {
+        match self.current_session_start(now) {
+            Some(start) => now < self.session_end(start),
+            None => false,
+        }
+    }
+
+    /// Start of the most recent session period that began at or before `now`, whether or not
+    /// that period is still open.
+    pub fn current_session_start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local_date = now.with_timezone(&self.time_zone).date_naive();
+        (0..=7)
+            .map(|days_back| local_date - Duration::days(days_back))
+            .filter(|date| self.starts_on(date.weekday()))
+            .map(|date| self.to_utc(date.and_time(self.start_time)))
+            .find(|start| *start <= now)
+    }
+
+    /// First session start strictly after `after`.
+    pub fn next_session_start(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local_date = after.with_timezone(&self.time_zone).date_naive();
+        (0..=8)
+            .map(|days| local_date + Duration::days(days))
+            .filter(|date| self.starts_on(date.weekday()))
+            .map(|date| self.to_utc(date.and_time(self.start_time)))
+            .find(|start| *start > after)
+    }
+
+    /// End of the session open at `now` or, outside session hours, of the next one to open.
+    pub fn next_session_end(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.current_session_start(now).map(|start| self.session_end(start)) {
+            Some(end) if end > now => Some(end),
+            _ => self.next_session_start(now).map(|start| self.session_end(start)),
+        }
+    }
+
+    pub fn time_zone(&self) -> Tz {
+        self.time_zone
+    }
+
+    fn starts_on(&self, weekday: Weekday) -> bool {
+        match (&self.weekly, &self.weekdays) {
+            (Some((start_day, _)), _) => weekday == *start_day,
+            (None, Some(weekdays)) => weekdays.contains(&weekday),
+            (None, None) => true,
+        }
+    }
+
+    fn session_end(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let start_date = start.with_timezone(&self.time_zone).date_naive();
+        let end_date = match self.weekly {
+            Some((_, end_day)) => (0..=7)
+                .map(|days| start_date + Duration::days(days))
+                .find(|date| date.weekday() == end_day && self.to_utc(date.and_time(self.end_time)) > start)
+                .unwrap_or(start_date),
+            None if self.end_time > self.start_time => start_date,
+            None => start_date + Duration::days(1),
+        };
+        self.to_utc(end_date.and_time(self.end_time))
+    }
+
+    fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let resolved = self.time_zone.from_local_datetime(&local);
+        match resolved.earliest() {
+            Some(time) => time.with_timezone(&Utc),
+            // Local time falls in a DST gap; the wall clock skips ahead by the shift.
+            None => self.to_utc(local + Duration::hours(1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start_time: &str, end_time: &str, time_zone: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            start_time: start_time.to_string(),
+            end_time: end_time.to_string(),
+            time_zone: time_zone.to_string(),
+            weekdays: None,
+            start_day: None,
+            end_day: None,
+        }
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_daily_schedule_in_london_follows_daylight_saving() {
+        let schedule = SessionSchedule::new(&schedule("07:00:00", "17:30:00", "Europe/London")).unwrap();
+
+        // Winter: London is UTC+0
+        assert!(!schedule.is_session_time(utc(2024, 1, 15, 6, 59)));
+        assert!(schedule.is_session_time(utc(2024, 1, 15, 7, 0)));
+        assert!(!schedule.is_session_time(utc(2024, 1, 15, 17, 30)));
+        // Summer: London is UTC+1
+        assert!(schedule.is_session_time(utc(2024, 7, 15, 6, 0)));
+        assert!(!schedule.is_session_time(utc(2024, 7, 15, 16, 30)));
+    }
+
+    #[test]
+    fn test_overnight_schedule() {
+        let schedule = SessionSchedule::new(&schedule("22:00:00", "06:00:00", "UTC")).unwrap();
+
+        assert!(schedule.is_session_time(utc(2024, 1, 15, 23, 0)));
+        assert!(schedule.is_session_time(utc(2024, 1, 16, 5, 59)));
+        assert!(!schedule.is_session_time(utc(2024, 1, 16, 12, 0)));
+        assert_eq!(schedule.current_session_start(utc(2024, 1, 16, 5, 0)), Some(utc(2024, 1, 15, 22, 0)));
+    }
+
+    #[test]
+    fn test_weekday_schedule_skips_weekend() {
+        let mut config = schedule("07:00:00", "17:30:00", "Europe/London");
+        config.weekdays = Some(["Mon", "Tue", "Wed", "Thu", "Fri"].iter().map(|day| day.to_string()).collect());
+        let schedule = SessionSchedule::new(&config).unwrap();
+
+        // 2024-01-19 is a Friday, 2024-01-20 a Saturday
+        assert!(schedule.is_session_time(utc(2024, 1, 19, 12, 0)));
+        assert!(!schedule.is_session_time(utc(2024, 1, 20, 12, 0)));
+        assert_eq!(schedule.current_session_start(utc(2024, 1, 21, 12, 0)), Some(utc(2024, 1, 19, 7, 0)));
+    }
+
+    #[test]
+    fn test_weekly_schedule_in_new_york() {
+        let mut config = schedule("17:00:00", "17:00:00", "America/New_York");
+        config.start_day = Some("Sun".to_string());
+        config.end_day = Some("Fri".to_string());
+        let schedule = SessionSchedule::new(&config).unwrap();
+
+        // 2024-01-14 is a Sunday; New York is UTC-5 in January
+        assert!(!schedule.is_session_time(utc(2024, 1, 14, 21, 59)));
+        assert!(schedule.is_session_time(utc(2024, 1, 14, 22, 0)));
+        assert!(schedule.is_session_time(utc(2024, 1, 17, 3, 0)));
+        assert!(schedule.is_session_time(utc(2024, 1, 19, 21, 59)));
+        assert!(!schedule.is_session_time(utc(2024, 1, 19, 22, 0)));
+        assert!(!schedule.is_session_time(utc(2024, 1, 20, 12, 0)));
+    }
+
+    #[test]
+    fn test_next_session_start_and_end() {
+        let mut config = schedule("07:00:00", "17:30:00", "Europe/London");
+        config.weekdays = Some(["Mon", "Tue", "Wed", "Thu", "Fri"].iter().map(|day| day.to_string()).collect());
+        let schedule = SessionSchedule::new(&config).unwrap();
+
+        // 2024-01-19 is a Friday
+        assert_eq!(schedule.next_session_end(utc(2024, 1, 19, 12, 0)), Some(utc(2024, 1, 19, 17, 30)));
+        assert_eq!(schedule.next_session_end(utc(2024, 1, 19, 18, 0)), Some(utc(2024, 1, 22, 17, 30)));
+        assert_eq!(schedule.next_session_start(utc(2024, 1, 19, 7, 0)), Some(utc(2024, 1, 22, 7, 0)));
+        assert_eq!(schedule.next_session_start(utc(2024, 1, 18, 20, 0)), Some(utc(2024, 1, 19, 7, 0)));
+    }
+
+    #[test]
+    fn test_full_day_schedule_rolls_over_at_start_time() {
+        let schedule = SessionSchedule::new(&schedule("00:00:00", "00:00:00", "UTC")).unwrap();
+
+        assert!(schedule.is_session_time(utc(2024, 1, 15, 23, 59)));
+        assert_eq!(schedule.current_session_start(utc(2024, 1, 16, 0, 1)), Some(utc(2024, 1, 16, 0, 0)));
+    }
+
+    #[test]
+    fn test_invalid_schedules_are_rejected() {
+        let mut half_weekly = schedule("07:00:00", "17:30:00", "UTC");
+        half_weekly.start_day = Some("Mon".to_string());
+
+        assert!(SessionSchedule::new(&schedule("07:00:00", "17:30:00", "Mars/Olympus")).is_err());
+        assert!(SessionSchedule::new(&half_weekly).is_err());
+    }
+}